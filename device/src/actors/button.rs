@@ -13,6 +13,7 @@ pub trait FromButtonEvent<M> {
         Self: Sized;
 }
 
+#[derive(Clone, Copy)]
 pub enum ButtonEvent {
     Pressed,
     Released,
@@ -66,7 +67,7 @@ impl<'a, P: WaitForAnyEdge + InputPin + 'a, A: Actor + FromButtonEvent<A::Messag
                 if let Some(handler) = self.handler {
                     let mut message = A::from(event);
                     if let Some(m) = message.take() {
-                        let _ = handler.notify(m);
+                        handler.notify_async(m).await;
                     }
                 }
             }
@@ -0,0 +1,166 @@
+use crate::{
+    actors::button::{ButtonEvent, FromButtonEvent},
+    kernel::{
+        actor::{Actor, ActorError, Address, MessageAdapter, Recipient},
+        util::Slots,
+    },
+};
+use core::future::Future;
+use core::marker::PhantomData;
+use core::pin::Pin;
+
+/// Converts an event published on an `EventBus` into a message for a subscriber.
+pub trait FromEvent<E, M> {
+    fn from(event: E) -> Option<M>
+    where
+        Self: Sized;
+}
+
+/// A type-erased subscriber of an `EventBus`, delivering events to an actor
+/// through a `Recipient` converting them with a `FromEvent` implementation.
+pub struct Subscriber<E: 'static> {
+    recipient: Recipient<'static, E>,
+}
+
+impl<E: Clone + 'static> Subscriber<E> {
+    /// Create a subscriber delivering to the actor behind `address`, converting events
+    /// using the adapter `F`. Events the adapter returns `None` for are not delivered.
+    pub fn new<A, F>(address: Address<'static, A>) -> Self
+    where
+        A: Actor + 'static,
+        F: FromEvent<E, A::Message<'static>>,
+    {
+        Self {
+            recipient: address.recipient_with::<E, EventAdapter<F>>(),
+        }
+    }
+
+    fn notify(&self, event: E) -> Result<(), ActorError> {
+        self.recipient.notify(event)
+    }
+}
+
+impl<E: 'static> Copy for Subscriber<E> {}
+
+impl<E: 'static> Clone for Subscriber<E> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+/// Converts events for a `Recipient` using the `FromEvent` implementation of `F`.
+struct EventAdapter<F>(PhantomData<F>);
+
+impl<E, M, F: FromEvent<E, M>> MessageAdapter<E, M> for EventAdapter<F> {
+    fn adapt(event: E) -> Option<M> {
+        F::from(event)
+    }
+}
+
+pub enum EventBusMessage<E: 'static> {
+    Subscribe(Subscriber<E>),
+    Publish(E),
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum EventBusError {
+    TooManySubscribers,
+    /// The bus was restarted or stopped before handling the subscription.
+    Abandoned,
+}
+
+/// An actor fanning out published events to up to `N` subscribers of any actor type.
+///
+/// Events are delivered using `notify`, so a subscriber with a full queue misses
+/// the event rather than holding up the other subscribers.
+pub struct EventBus<E: Clone + 'static, const N: usize> {
+    subscribers: [Option<Subscriber<E>>; N],
+}
+
+impl<E: Clone + 'static, const N: usize> EventBus<E, N> {
+    pub fn new() -> Self {
+        Self {
+            subscribers: [None; N],
+        }
+    }
+}
+
+impl<E: Clone + 'static, const N: usize> Unpin for EventBus<E, N> {}
+
+impl<E: Clone + 'static, const N: usize> Actor for EventBus<E, N> {
+    type MessageQueueSize = Slots<4>;
+    type Message<'m> = EventBusMessage<E>;
+    type Response = Result<(), EventBusError>;
+    type OnStartFuture<'m> = impl Future<Output = ()> + 'm;
+    type OnMessageFuture<'m> = impl Future<Output = Self::Response> + 'm;
+
+    fn on_start(self: Pin<&'_ mut Self>) -> Self::OnStartFuture<'_> {
+        async move {}
+    }
+
+    fn on_message<'m>(
+        mut self: Pin<&'m mut Self>,
+        message: Self::Message<'m>,
+    ) -> Self::OnMessageFuture<'m> {
+        async move {
+            match message {
+                EventBusMessage::Subscribe(subscriber) => {
+                    match self.subscribers.iter_mut().find(|s| s.is_none()) {
+                        Some(slot) => {
+                            slot.replace(subscriber);
+                            Ok(())
+                        }
+                        None => Err(EventBusError::TooManySubscribers),
+                    }
+                }
+                EventBusMessage::Publish(event) => {
+                    for (i, subscriber) in self.subscribers.iter().flatten().enumerate() {
+                        if subscriber.notify(event.clone()).is_err() {
+                            warn!("Subscriber {} missed an event", i);
+                        }
+                    }
+                    Ok(())
+                }
+            }
+        }
+    }
+}
+
+impl<'a, E: Clone + 'static, const N: usize> Address<'a, EventBus<E, N>> {
+    /// Subscribe an actor to events on this bus, converting them using its `FromEvent`
+    /// implementation.
+    pub async fn subscribe<A>(&self, address: Address<'static, A>) -> Result<(), EventBusError>
+    where
+        A: Actor + FromEvent<E, A::Message<'static>> + 'static,
+    {
+        self.subscribe_with::<A, A>(address).await
+    }
+
+    /// Subscribe an actor to events on this bus, converting them using the `FromEvent`
+    /// implementation of the adapter `F`.
+    pub async fn subscribe_with<A, F>(
+        &self,
+        address: Address<'static, A>,
+    ) -> Result<(), EventBusError>
+    where
+        A: Actor + 'static,
+        F: FromEvent<E, A::Message<'static>>,
+    {
+        let subscriber = Subscriber::new::<A, F>(address);
+        self.request_async(EventBusMessage::Subscribe(subscriber))
+            .await
+            .unwrap_or(Err(EventBusError::Abandoned))
+    }
+
+    /// Publish an event to all subscribers, waiting for room in the bus queue if necessary.
+    pub async fn publish(&self, event: E) {
+        self.notify_async(EventBusMessage::Publish(event)).await
+    }
+}
+
+impl<const N: usize> FromButtonEvent<EventBusMessage<ButtonEvent>> for EventBus<ButtonEvent, N> {
+    fn from(event: ButtonEvent) -> Option<EventBusMessage<ButtonEvent>> {
+        Some(EventBusMessage::Publish(event))
+    }
+}
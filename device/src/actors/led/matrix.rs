@@ -1,8 +1,10 @@
-use crate::kernel::{actor::Actor, util::ImmediateFuture};
+use crate::kernel::{
+    actor::{Actor, HighPriority, Priority},
+    util::{ImmediateFuture, Slots},
+};
 use core::future::Future;
 use core::pin::Pin;
 use embedded_hal::digital::v2::OutputPin;
-use heapless::consts::U2;
 
 // Led matrix driver supporting up to 32x32 led matrices.
 pub struct LEDMatrix<P, const ROWS: usize, const COLS: usize>
@@ -94,12 +96,15 @@ where
     }
 }
 
+/// Runs on the high priority executor of a `PrioritySpawner`, so that rendering is not held
+/// up by other actors, which requires the pins to be `Send`.
 impl<P, const ROWS: usize, const COLS: usize> Actor for LEDMatrix<P, ROWS, COLS>
 where
-    P: OutputPin,
+    P: OutputPin + Send,
 {
     // Needed in order to handle concurrent apply and notify
-    type MessageQueueSize<'m> = U2;
+    type MessageQueueSize = Slots<2>;
+    type Executor = HighPriority;
 
     #[rustfmt::skip]
     type Message<'m> = MatrixCommand<'m>;
@@ -108,6 +113,14 @@ where
     #[rustfmt::skip]
     type OnMessageFuture<'m> where P: 'm = impl Future<Output = ()> + 'm;
 
+    fn priority(message: &Self::Message<'_>) -> Priority {
+        match message {
+            // Keep the refresh rate steady regardless of queued updates
+            MatrixCommand::Render => Priority::High,
+            _ => Priority::Normal,
+        }
+    }
+
     fn on_start(self: Pin<&'_ mut Self>) -> Self::OnStartFuture<'_> {
         ImmediateFuture::new()
     }
@@ -140,13 +153,46 @@ pub enum MatrixCommand<'m> {
     ApplyFrame(&'m dyn ToFrame),
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::actor::ActorContext;
+    use crate::testutil::*;
+    use embedded_hal::digital::v2::InputPin;
+
+    #[test]
+    fn test_render_before_queued_commands() {
+        let runner = Box::leak(Box::new(TestRunner::new()));
+        let row = runner.pin(false);
+        let col = runner.pin(false);
+        let matrix = Box::leak(Box::new(ActorContext::new(LEDMatrix::new([row], [col]))));
+
+        let address = matrix.mount((), TestSpawner::new());
+        assert!(address.notify(MatrixCommand::On(0, 0)).is_ok());
+        assert!(address.notify(MatrixCommand::On(0, 0)).is_ok());
+        assert!(address.notify(MatrixCommand::Render).is_ok());
+
+        // Rendered before the led is turned on
+        step_actor(matrix);
+        assert!(row.is_high().unwrap());
+        assert!(col.is_high().unwrap());
+
+        step_actor(matrix);
+        step_actor(matrix);
+        assert!(address.notify(MatrixCommand::Render).is_ok());
+        step_actor(matrix);
+        assert!(col.is_low().unwrap());
+    }
+}
+
+// Frames are passed by reference to the matrix, which may run on another executor
 #[cfg(feature = "defmt")]
-pub trait ToFrame: core::fmt::Debug + defmt::Format {
+pub trait ToFrame: core::fmt::Debug + defmt::Format + Sync {
     fn to_frame(&self) -> Frame;
 }
 
 #[cfg(not(feature = "defmt"))]
-pub trait ToFrame: core::fmt::Debug {
+pub trait ToFrame: core::fmt::Debug + Sync {
     fn to_frame(&self) -> Frame;
 }
 
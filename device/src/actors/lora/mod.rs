@@ -1,16 +1,9 @@
 use crate::{
-    kernel::actor::{Actor, Address},
+    actor,
+    kernel::{actor::Address, util::Slots},
     traits::lora::*,
 };
-use core::{future::Future, pin::Pin};
-
-/// Messages handled by lora actor
-pub enum LoraRequest<'m> {
-    Configure(&'m LoraConfig),
-    Join(ConnectMode),
-    Send(QoS, Port, &'m [u8]),
-    SendRecv(QoS, Port, &'m [u8], &'m mut [u8]),
-}
+use core::future::Future;
 
 impl<'a, D> LoraDriver for Address<'a, LoraActor<D>>
 where
@@ -20,10 +13,9 @@ where
     type ConfigureFuture<'m> where 'a: 'm = impl Future<Output = Result<(), LoraError>> + 'm;
     fn configure<'m>(&'m mut self, config: &'m LoraConfig) -> Self::ConfigureFuture<'m> {
         async move {
-            self.request(LoraRequest::Configure(config))
-                .unwrap()
+            self.call_configure(config)
                 .await
-                .map(|_| ())
+                .unwrap_or(Err(LoraError::Abandoned))
         }
     }
 
@@ -31,10 +23,9 @@ where
     type JoinFuture<'m> where 'a: 'm = impl Future<Output = Result<(), LoraError>> + 'm;
     fn join<'m>(&'m mut self, mode: ConnectMode) -> Self::JoinFuture<'m> {
         async move {
-            self.request(LoraRequest::Join(mode))
-                .unwrap()
+            self.call_join(mode)
                 .await
-                .map(|_| ())
+                .unwrap_or(Err(LoraError::Abandoned))
         }
     }
 
@@ -42,10 +33,9 @@ where
     type SendFuture<'m> where 'a: 'm = impl Future<Output = Result<(), LoraError>> + 'm;
     fn send<'m>(&'m mut self, qos: QoS, port: Port, data: &'m [u8]) -> Self::SendFuture<'m> {
         async move {
-            self.request(LoraRequest::Send(qos, port, data))
-                .unwrap()
+            self.call_send(qos, port, data)
                 .await
-                .map(|_| ())
+                .unwrap_or(Err(LoraError::Abandoned))
         }
     }
 
@@ -59,9 +49,9 @@ where
         rx: &'m mut [u8],
     ) -> Self::SendRecvFuture<'m> {
         async move {
-            self.request(LoraRequest::SendRecv(qos, port, data, rx))
-                .unwrap()
+            self.call_send_recv(qos, port, data, rx)
                 .await
+                .unwrap_or(Err(LoraError::Abandoned))
         }
     }
 }
@@ -71,6 +61,8 @@ where
     D: LoraDriver + 'static,
 {
     driver: D,
+    joined: bool,
+    address: Option<Address<'static, Self>>,
 }
 
 impl<D> LoraActor<D>
@@ -78,43 +70,159 @@ where
     D: LoraDriver + 'static,
 {
     pub fn new(driver: D) -> Self {
-        Self { driver }
+        Self {
+            driver,
+            joined: false,
+            address: None,
+        }
+    }
+
+    /// Put a message aside until the network has been joined
+    fn stash(&self, message: LoraActorMessage<'_, D>) -> Result<(), LoraError> {
+        self.address
+            .unwrap()
+            .stash(self, message)
+            .map_err(|_| LoraError::NotReady)
     }
 }
 
-impl<D> Actor for LoraActor<D>
+/// Requests handled by the lora actor, available as methods on the actor address
+#[actor]
+impl<D> LoraActor<D>
 where
     D: LoraDriver + 'static,
 {
     type Configuration = ();
+    type MessageQueueSize = Slots<4>;
+    type StashSize = Slots<2>;
 
-    #[rustfmt::skip]
-    type Message<'m> where D: 'm = LoraRequest<'m>;
-    type Response = Result<usize, LoraError>;
+    fn on_mount(&mut self, address: Address<'static, Self>, _: Self::Configuration) {
+        self.address.replace(address);
+    }
 
-    #[rustfmt::skip]
-    type OnStartFuture<'m> where D: 'm = impl Future<Output = ()> + 'm;
-    fn on_start(self: Pin<&'_ mut Self>) -> Self::OnStartFuture<'_> {
-        async move {}
+    /// Configure the lora driver
+    pub async fn configure(&mut self, config: &'m LoraConfig) -> Result<(), LoraError> {
+        self.driver.configure(config).await
     }
 
-    #[rustfmt::skip]
-    type OnMessageFuture<'m> where D: 'm = impl Future<Output = Self::Response> + 'm;
-    fn on_message<'m>(
-        self: Pin<&'m mut Self>,
-        message: Self::Message<'m>,
-    ) -> Self::OnMessageFuture<'m> {
-        async move {
-            let this = unsafe { self.get_unchecked_mut() };
-            let driver = &mut this.driver;
-            match message {
-                LoraRequest::Configure(config) => driver.configure(config).await.map(|_| 0),
-                LoraRequest::Join(mode) => driver.join(mode).await.map(|_| 0),
-                LoraRequest::Send(qos, port, buf) => driver.send(qos, port, buf).await.map(|_| 0),
-                LoraRequest::SendRecv(qos, port, buf, rx) => {
-                    driver.send_recv(qos, port, buf, rx).await
-                }
-            }
+    /// Join a lora network
+    pub async fn join(&mut self, mode: ConnectMode) -> Result<(), LoraError> {
+        self.driver.join(mode).await?;
+        self.joined = true;
+        self.address.unwrap().unstash(self);
+        Ok(())
+    }
+
+    /// Send data to the network, once it has been joined
+    pub async fn send(&mut self, qos: QoS, port: Port, data: &'m [u8]) -> Result<(), LoraError> {
+        if !self.joined {
+            return self.stash(LoraActorMessage::Send(qos, port, data));
+        }
+        self.driver.send(qos, port, data).await
+    }
+
+    /// Send data and receive the response into `rx` once the network has been joined,
+    /// returning the number of bytes received
+    pub async fn send_recv(
+        &mut self,
+        qos: QoS,
+        port: Port,
+        data: &'m [u8],
+        rx: &'m mut [u8],
+    ) -> Result<usize, LoraError> {
+        if !self.joined {
+            return self
+                .stash(LoraActorMessage::SendRecv(qos, port, data, rx))
+                .map(|_| 0);
+        }
+        self.driver.send_recv(qos, port, data, rx).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::actor::ActorContext;
+    use crate::testutil::*;
+    use core::future::{ready, Ready};
+    use core::task::Poll;
+
+    /// A driver that refuses to send before the network is joined
+    struct MockDriver {
+        joined: bool,
+    }
+
+    impl LoraDriver for MockDriver {
+        type ConfigureFuture<'a> = Ready<Result<(), LoraError>>;
+        fn configure<'a>(&'a mut self, _: &'a LoraConfig) -> Self::ConfigureFuture<'a> {
+            ready(Ok(()))
+        }
+
+        type JoinFuture<'a> = Ready<Result<(), LoraError>>;
+        fn join<'a>(&'a mut self, _: ConnectMode) -> Self::JoinFuture<'a> {
+            self.joined = true;
+            ready(Ok(()))
+        }
+
+        type SendFuture<'a> = Ready<Result<(), LoraError>>;
+        fn send<'a>(&'a mut self, _: QoS, _: Port, _: &'a [u8]) -> Self::SendFuture<'a> {
+            ready(if self.joined {
+                Ok(())
+            } else {
+                Err(LoraError::NotReady)
+            })
         }
+
+        type SendRecvFuture<'a> = Ready<Result<usize, LoraError>>;
+        fn send_recv<'a>(
+            &'a mut self,
+            _: QoS,
+            _: Port,
+            _: &'a [u8],
+            _: &'a mut [u8],
+        ) -> Self::SendRecvFuture<'a> {
+            ready(if self.joined {
+                Ok(0)
+            } else {
+                Err(LoraError::NotReady)
+            })
+        }
+    }
+
+    #[test]
+    fn test_send_before_join() {
+        let spawner = TestSpawner::new();
+        let actor = Box::leak(Box::new(ActorContext::new(LoraActor::new(MockDriver {
+            joined: false,
+        }))));
+
+        let address = actor.mount((), spawner);
+
+        let waker = futures::task::noop_waker_ref();
+        let mut cx = std::task::Context::from_waker(waker);
+
+        let data = [1, 2, 3];
+        let send = address.call_send(QoS::Confirmed, 1, &data);
+        futures::pin_mut!(send);
+        assert!(send.as_mut().poll(&mut cx).is_pending());
+
+        // The send is stashed until the network is joined
+        step_actor(actor);
+        assert!(send.as_mut().poll(&mut cx).is_pending());
+
+        let join = address.call_join(ConnectMode::OTAA);
+        futures::pin_mut!(join);
+        assert!(join.as_mut().poll(&mut cx).is_pending());
+        step_actor(actor);
+        assert!(matches!(
+            join.as_mut().poll(&mut cx),
+            Poll::Ready(Ok(Ok(())))
+        ));
+
+        step_actor(actor);
+        assert!(matches!(
+            send.as_mut().poll(&mut cx),
+            Poll::Ready(Ok(Ok(())))
+        ));
     }
 }
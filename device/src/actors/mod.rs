@@ -1,4 +1,5 @@
 pub mod button;
+pub mod event_bus;
 pub mod led;
 pub mod lora;
 pub mod socket;
@@ -1,26 +1,24 @@
-use crate::kernel::actor::{Actor, Address};
+use crate::kernel::{
+    actor::{Actor, Address, Priority, Recipient},
+    util::Slots,
+};
 use core::future::Future;
 use core::pin::Pin;
 use embassy::time::{Duration, Timer};
-use heapless::consts;
 
-pub struct Ticker<'a, A: Actor + 'static>
+pub struct Ticker<'a, M: Copy + 'static>
 where
-    A::Message<'a>: Copy,
     Self: 'static,
 {
     interval: Duration,
-    message: A::Message<'a>,
-    actor: Option<Address<'a, A>>,
+    message: M,
+    actor: Option<Recipient<'a, M>>,
     me: Option<Address<'a, Self>>,
     running: bool,
 }
 
-impl<'a, A: Actor + 'a> Ticker<'a, A>
-where
-    A::Message<'a>: Copy,
-{
-    pub fn new(interval: Duration, message: A::Message<'a>) -> Self {
+impl<'a, M: Copy + 'a> Ticker<'a, M> {
+    pub fn new(interval: Duration, message: M) -> Self {
         Self {
             interval,
             message,
@@ -37,12 +35,13 @@ pub enum TickerCommand {
     Stop,
 }
 
-impl<'a, A: Actor + 'a> Actor for Ticker<'a, A>
-where
-    A::Message<'a>: Copy,
-{
-    type MessageQueueSize<'m> = consts::U4;
-    type Configuration = Address<'a, A>;
+impl<'a, M: Copy + 'a> Actor for Ticker<'a, M> {
+    type MessageQueueSize = Slots<4>;
+    // Room for both a start and a stop
+    type PriorityQueueSize = Slots<2>;
+    // Only ever notified, so no signal slots are needed for responses
+    type PendingRequests = Slots<0>;
+    type Configuration = Recipient<'a, M>;
     #[rustfmt::skip]
     type Message<'m> where 'a: 'm = TickerCommand;
     #[rustfmt::skip]
@@ -55,6 +54,15 @@ where
         self.actor.replace(config);
     }
 
+    fn priority(message: &Self::Message<'_>) -> Priority {
+        match message {
+            // Start and stop right away rather than after queued ticks, keeping them
+            // in the order they were sent
+            TickerCommand::Start | TickerCommand::Stop => Priority::High,
+            _ => Priority::Normal,
+        }
+    }
+
     fn on_start(self: Pin<&mut Self>) -> Self::OnStartFuture<'_> {
         async move {
             self.me.unwrap().notify(TickerCommand::Start).unwrap();
@@ -73,9 +81,8 @@ where
                         // Wait the configured interval before sending the message
                         Timer::after(this.interval).await;
                         if let Some(actor) = this.actor {
-                            // We continue even if we get an error, trying again
-                            // next tick.
-                            let _ = actor.notify(this.message);
+                            // Wait for the receiver to have room rather than dropping the tick
+                            actor.notify_async(this.message).await;
                         }
                         this.me.unwrap().notify(TickerCommand::Tick).unwrap();
                     }
@@ -91,3 +98,61 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::actor::ActorContext;
+    use crate::testutil::*;
+
+    #[test]
+    fn test_stop_before_queued_ticks() {
+        let spawner = TestSpawner::new();
+        let handler = Box::leak(Box::new(ActorContext::new(DummyActor::new())));
+        let ticker = Box::leak(Box::new(ActorContext::new(Ticker::new(
+            Duration::from_secs(1),
+            TestMessage(0),
+        ))));
+
+        let recipient = handler.mount((), spawner).recipient();
+        let address = ticker.mount(recipient, spawner);
+
+        // Fill up the regular queue
+        while address.notify(TickerCommand::Tick).is_ok() {}
+        assert!(address.notify(TickerCommand::Stop).is_ok());
+
+        // Stop is handled first, leaving the regular queue full
+        step_actor(ticker);
+        assert!(address.notify(TickerCommand::Tick).is_err());
+
+        step_actor(ticker);
+        assert!(address.notify(TickerCommand::Tick).is_ok());
+    }
+
+    #[test]
+    fn test_start_then_stop() {
+        let spawner = TestSpawner::new();
+        let handler = Box::leak(Box::new(ActorContext::new(DummyActor::new())));
+        let ticker = Box::leak(Box::new(ActorContext::new(Ticker::new(
+            Duration::from_secs(1),
+            TestMessage(0),
+        ))));
+
+        let recipient = handler.mount((), spawner).recipient();
+        let address = ticker.mount(recipient, spawner);
+
+        assert!(address.notify(TickerCommand::Start).is_ok());
+        assert!(address.notify(TickerCommand::Stop).is_ok());
+
+        // Start queues a tick, which then finds the ticker stopped
+        step_actor(ticker);
+        step_actor(ticker);
+        step_actor(ticker);
+
+        // No further tick was queued, leaving the whole regular queue
+        for _ in 0..4 {
+            assert!(address.notify(TickerCommand::Tick).is_ok());
+        }
+        assert!(address.notify(TickerCommand::Tick).is_err());
+    }
+}
@@ -1,35 +1,31 @@
 use crate::kernel::{
-    actor::{Actor, Address},
+    actor::{Actor, Recipient},
     util::ImmediateFuture,
 };
 use core::future::Future;
 use core::pin::Pin;
 use embassy::time;
 
-pub struct Timer<'a, A: Actor + 'static> {
-    _marker: core::marker::PhantomData<&'a A>,
+pub struct Timer<'a, M: 'a> {
+    _marker: core::marker::PhantomData<&'a M>,
 }
 
-pub enum TimerMessage<'m, A: Actor + 'static> {
+pub enum TimerMessage<'m, M> {
     Delay(time::Duration),
-    Schedule(time::Duration, Address<'m, A>, Option<A::Message<'m>>),
+    Schedule(time::Duration, Recipient<'m, M>, Option<M>),
 }
 
-impl<'m, A: Actor + 'm> TimerMessage<'m, A> {
+impl<'m, M> TimerMessage<'m, M> {
     pub fn delay(duration: time::Duration) -> Self {
         TimerMessage::Delay(duration)
     }
 
-    pub fn schedule(
-        duration: time::Duration,
-        destination: Address<'m, A>,
-        message: A::Message<'m>,
-    ) -> Self {
+    pub fn schedule(duration: time::Duration, destination: Recipient<'m, M>, message: M) -> Self {
         TimerMessage::Schedule(duration, destination, Some(message))
     }
 }
 
-impl<'a, A: Actor + 'a> Timer<'a, A> {
+impl<'a, M: 'a> Timer<'a, M> {
     pub fn new() -> Self {
         Self {
             _marker: core::marker::PhantomData,
@@ -37,9 +33,9 @@ impl<'a, A: Actor + 'a> Timer<'a, A> {
     }
 }
 
-impl<'a, A: Actor + 'a> Actor for Timer<'a, A> {
+impl<'a, M: 'a> Actor for Timer<'a, M> {
     #[rustfmt::skip]
-    type Message<'m> where 'a: 'm = TimerMessage<'m, A>;
+    type Message<'m> where 'a: 'm = TimerMessage<'m, M>;
     #[rustfmt::skip]
     type OnStartFuture<'m> where 'a: 'm = ImmediateFuture;
     #[rustfmt::skip]
@@ -58,9 +54,9 @@ impl<'a, A: Actor + 'a> Actor for Timer<'a, A> {
                 TimerMessage::Delay(dur) => {
                     time::Timer::after(dur).await;
                 }
-                TimerMessage::Schedule(dur, address, mut message) => {
+                TimerMessage::Schedule(dur, recipient, mut message) => {
                     time::Timer::after(dur).await;
-                    let _ = address.notify(message.take().unwrap());
+                    let _ = recipient.notify(message.take().unwrap());
                 }
             }
         }
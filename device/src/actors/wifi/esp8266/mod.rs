@@ -57,6 +57,7 @@ where
     RESET: OutputPin + 'static,
 {
     type Primary = AdapterActor<Esp8266Controller<'static>>;
+    type ShutdownFuture = impl Future<Output = ()>;
 
     fn mount<S: ActorSpawner>(
         &'static self,
@@ -72,6 +73,13 @@ where
             panic!("Attempted to mount package twice!")
         }
     }
+
+    fn shutdown(&'static self) -> Self::ShutdownFuture {
+        async move {
+            self.wifi.shutdown().await;
+            self.modem.shutdown().await;
+        }
+    }
 }
 
 /// Convenience actor implementation of modem
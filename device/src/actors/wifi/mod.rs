@@ -1,39 +1,18 @@
 use crate::{
-    kernel::actor::{Actor, Address},
+    actor,
+    kernel::{actor::Address, util::Slots},
     traits::{
         ip::{IpAddress, IpProtocol, SocketAddress},
         tcp::{TcpError, TcpStack},
         wifi::{Join, JoinError, WifiSupplicant},
     },
 };
-use heapless::consts;
 
 use core::future::Future;
-use core::pin::Pin;
 
 #[cfg(feature = "wifi+esp8266")]
 pub mod esp8266;
 
-/// Actor messages handled by network adapter actors
-pub enum AdapterRequest<'m> {
-    Join(Join<'m>),
-    Open,
-    Connect(u8, IpProtocol, SocketAddress),
-    Write(u8, &'m [u8]),
-    Read(u8, &'m mut [u8]),
-    Close(u8),
-}
-
-/// Actor responses returned by network adapter actors
-pub enum AdapterResponse {
-    Join(Result<IpAddress, JoinError>),
-    Open(u8),
-    Connect(Result<(), TcpError>),
-    Write(Result<usize, TcpError>),
-    Read(Result<usize, TcpError>),
-    Close,
-}
-
 pub trait Adapter: WifiSupplicant + TcpStack<SocketHandle = u8> {}
 
 impl<'a, A> WifiSupplicant for Address<'a, AdapterActor<A>>
@@ -44,10 +23,9 @@ where
     type JoinFuture<'m> where 'a: 'm = impl Future<Output = Result<IpAddress, JoinError>>;
     fn join<'m>(&'m mut self, join: Join<'m>) -> Self::JoinFuture<'m> {
         async move {
-            self.request(AdapterRequest::Join(join))
-                .unwrap()
+            self.call_join(join)
                 .await
-                .join()
+                .unwrap_or(Err(JoinError::Abandoned))
         }
     }
 }
@@ -59,9 +37,9 @@ where
     type SocketHandle = A::SocketHandle;
 
     #[rustfmt::skip]
-    type OpenFuture<'m> where 'a: 'm = impl Future<Output = Self::SocketHandle>;
+    type OpenFuture<'m> where 'a: 'm = impl Future<Output = Result<Self::SocketHandle, TcpError>>;
     fn open<'m>(&'m mut self) -> Self::OpenFuture<'m> {
-        async move { self.request(AdapterRequest::Open).unwrap().await.open() }
+        async move { self.call_open().await.unwrap_or(Err(TcpError::Abandoned)) }
     }
 
     #[rustfmt::skip]
@@ -73,10 +51,9 @@ where
         dst: SocketAddress,
     ) -> Self::ConnectFuture<'m> {
         async move {
-            self.request(AdapterRequest::Connect(handle, proto, dst))
-                .unwrap()
+            self.call_connect(handle, proto, dst)
                 .await
-                .connect()
+                .unwrap_or(Err(TcpError::Abandoned))
         }
     }
 
@@ -84,10 +61,9 @@ where
     type WriteFuture<'m> where 'a: 'm, A: 'm = impl Future<Output = Result<usize, TcpError>>;
     fn write<'m>(&'m mut self, handle: Self::SocketHandle, buf: &'m [u8]) -> Self::WriteFuture<'m> {
         async move {
-            self.request(AdapterRequest::Write(handle, buf))
-                .unwrap()
+            self.call_write(handle, buf)
                 .await
-                .write()
+                .unwrap_or(Err(TcpError::Abandoned))
         }
     }
 
@@ -99,10 +75,9 @@ where
         buf: &'m mut [u8],
     ) -> Self::ReadFuture<'m> {
         async move {
-            self.request(AdapterRequest::Read(handle, buf))
-                .unwrap()
+            self.call_read(handle, buf)
                 .await
-                .read()
+                .unwrap_or(Err(TcpError::Abandoned))
         }
     }
 
@@ -110,112 +85,189 @@ where
     type CloseFuture<'m> where 'a: 'm, A: 'm = impl Future<Output = ()>;
     fn close<'m>(&'m mut self, handle: Self::SocketHandle) -> Self::CloseFuture<'m> {
         async move {
-            self.request(AdapterRequest::Close(handle)).unwrap().await;
+            // The socket is gone either way if the adapter was restarted
+            let _ = self.call_close(handle).await;
         }
     }
 }
 
-impl AdapterResponse {
-    fn open(self) -> u8 {
-        match self {
-            AdapterResponse::Open(handle) => handle,
-            _ => panic!("unexpected response type"),
-        }
+pub struct AdapterActor<N: Adapter> {
+    driver: Option<N>,
+}
+
+impl<N: Adapter> AdapterActor<N> {
+    pub fn new() -> Self {
+        Self { driver: None }
     }
+}
 
-    fn join(self) -> Result<IpAddress, JoinError> {
-        match self {
-            AdapterResponse::Join(result) => result,
-            _ => panic!("unexpected response type"),
-        }
+/// Requests handled by network adapter actors, available as methods on the actor address
+#[actor]
+impl<N: Adapter> AdapterActor<N> {
+    type Configuration = N;
+    type MessageQueueSize = Slots<4>;
+
+    fn on_mount(&mut self, _: Address<'static, Self>, config: Self::Configuration) {
+        self.driver.replace(config);
     }
 
-    fn connect(self) -> Result<(), TcpError> {
-        match self {
-            AdapterResponse::Connect(result) => result,
-            _ => panic!("unexpected response type"),
-        }
+    /// Join a wifi network
+    pub async fn join(&mut self, join: Join<'m>) -> Result<IpAddress, JoinError> {
+        self.driver.as_mut().unwrap().join(join).await
     }
 
-    fn write(self) -> Result<usize, TcpError> {
-        match self {
-            AdapterResponse::Write(result) => result,
-            _ => panic!("unexpected response type"),
-        }
+    /// Open a new socket
+    pub async fn open(&mut self) -> Result<u8, TcpError> {
+        self.driver.as_mut().unwrap().open().await
     }
 
-    fn read(self) -> Result<usize, TcpError> {
-        match self {
-            AdapterResponse::Read(result) => result,
-            _ => panic!("unexpected response type"),
-        }
+    /// Connect a socket to a remote address
+    pub async fn connect(
+        &mut self,
+        handle: u8,
+        proto: IpProtocol,
+        dst: SocketAddress,
+    ) -> Result<(), TcpError> {
+        self.driver
+            .as_mut()
+            .unwrap()
+            .connect(handle, proto, dst)
+            .await
     }
 
-    fn close(self) {
-        match self {
-            AdapterResponse::Close => (),
-            _ => panic!("unexpected response type"),
-        }
+    /// Write data to a socket
+    pub async fn write(&mut self, handle: u8, buf: &'m [u8]) -> Result<usize, TcpError> {
+        self.driver.as_mut().unwrap().write(handle, buf).await
     }
-}
 
-pub struct AdapterActor<N: Adapter> {
-    driver: Option<N>,
-}
+    /// Read data from a socket
+    pub async fn read(&mut self, handle: u8, buf: &'m mut [u8]) -> Result<usize, TcpError> {
+        self.driver.as_mut().unwrap().read(handle, buf).await
+    }
 
-impl<N: Adapter> AdapterActor<N> {
-    pub fn new() -> Self {
-        Self { driver: None }
+    /// Close a socket
+    pub async fn close(&mut self, handle: u8) {
+        self.driver.as_mut().unwrap().close(handle).await
     }
 }
 
-impl<N: Adapter> Actor for AdapterActor<N> {
-    type Configuration = N;
-    type MessageQueueSize<'m>
-    where
-        N: 'm,
-    = consts::U4;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::actor::ActorContext;
+    use crate::testutil::*;
+    use core::cell::Cell;
+    use core::future::{ready, Ready};
+    use std::rc::Rc;
 
-    #[rustfmt::skip]
-    type Message<'m> where N: 'm = AdapterRequest<'m>;
-    type Response = AdapterResponse;
+    /// An adapter that fails to use sockets that are not open
+    struct MockAdapter {
+        joined: bool,
+        sockets: [bool; 8],
+    }
 
-    fn on_mount(&mut self, _: Address<'static, Self>, config: Self::Configuration) {
-        self.driver.replace(config);
+    impl WifiSupplicant for MockAdapter {
+        type JoinFuture<'m> = Ready<Result<IpAddress, JoinError>>;
+        fn join<'m>(&'m mut self, _: Join<'m>) -> Self::JoinFuture<'m> {
+            self.joined = true;
+            ready(Ok(IpAddress::new_v4(192, 168, 1, 2)))
+        }
     }
 
-    #[rustfmt::skip]
-    type OnStartFuture<'m> where N: 'm = impl Future<Output = ()> + 'm;
-    fn on_start(self: Pin<&'_ mut Self>) -> Self::OnStartFuture<'_> {
-        async move {}
+    impl TcpStack for MockAdapter {
+        type SocketHandle = u8;
+
+        type OpenFuture<'m> = Ready<Result<u8, TcpError>>;
+        fn open<'m>(&'m mut self) -> Self::OpenFuture<'m> {
+            let index = self.sockets.iter().position(|open| !open).unwrap();
+            self.sockets[index] = true;
+            ready(Ok(index as u8))
+        }
+
+        type ConnectFuture<'m> = Ready<Result<(), TcpError>>;
+        fn connect<'m>(
+            &'m mut self,
+            handle: u8,
+            _: IpProtocol,
+            _: SocketAddress,
+        ) -> Self::ConnectFuture<'m> {
+            ready(if self.joined && self.sockets[handle as usize] {
+                Ok(())
+            } else {
+                Err(TcpError::ConnectError)
+            })
+        }
+
+        type WriteFuture<'m> = Ready<Result<usize, TcpError>>;
+        fn write<'m>(&'m mut self, handle: u8, buf: &'m [u8]) -> Self::WriteFuture<'m> {
+            ready(if self.sockets[handle as usize] {
+                Ok(buf.len())
+            } else {
+                Err(TcpError::WriteError)
+            })
+        }
+
+        type ReadFuture<'m> = Ready<Result<usize, TcpError>>;
+        fn read<'m>(&'m mut self, handle: u8, buf: &'m mut [u8]) -> Self::ReadFuture<'m> {
+            ready(if self.sockets[handle as usize] {
+                // Echo the handle, so that the reader can tell the socket it read from
+                buf[0] = handle;
+                Ok(1)
+            } else {
+                Err(TcpError::ReadError)
+            })
+        }
+
+        type CloseFuture<'m> = Ready<()>;
+        fn close<'m>(&'m mut self, handle: u8) -> Self::CloseFuture<'m> {
+            self.sockets[handle as usize] = false;
+            ready(())
+        }
     }
 
-    #[rustfmt::skip]
-    type OnMessageFuture<'m> where N: 'm = impl Future<Output = Self::Response> + 'm;
-    fn on_message<'m>(
-        self: Pin<&'m mut Self>,
-        message: Self::Message<'m>,
-    ) -> Self::OnMessageFuture<'m> {
-        async move {
-            let this = unsafe { self.get_unchecked_mut() };
-            let driver = this.driver.as_mut().unwrap();
-            match message {
-                AdapterRequest::Join(join) => AdapterResponse::Join(driver.join(join).await),
-                AdapterRequest::Open => AdapterResponse::Open(driver.open().await),
-                AdapterRequest::Connect(handle, proto, addr) => {
-                    AdapterResponse::Connect(driver.connect(handle, proto, addr).await)
-                }
-                AdapterRequest::Write(handle, buf) => {
-                    AdapterResponse::Write(driver.write(handle, buf).await)
-                }
-                AdapterRequest::Read(handle, buf) => {
-                    AdapterResponse::Read(driver.read(handle, buf).await)
-                }
-                AdapterRequest::Close(handle) => {
-                    driver.close(handle).await;
-                    AdapterResponse::Close
-                }
+    impl Adapter for MockAdapter {}
+
+    #[test]
+    fn test_requests_interleaved() {
+        explore(32, |scheduler| {
+            let actor = Box::leak(Box::new(ActorContext::new(AdapterActor::new())));
+            let address = actor.mount(
+                MockAdapter {
+                    joined: false,
+                    sockets: [false; 8],
+                },
+                scheduler,
+            );
+            let completed = Rc::new(Cell::new(0));
+
+            // More clients than there is room for requests, so some wait for others
+            for _ in 0..6 {
+                let mut address = address;
+                let completed = completed.clone();
+                scheduler.spawn(async move {
+                    address.join(Join::Open).await.unwrap();
+
+                    let socket = address.open().await.unwrap();
+                    let server = SocketAddress::new(IpAddress::new_v4(192, 168, 1, 1), 80);
+                    address
+                        .connect(socket, IpProtocol::Tcp, server)
+                        .await
+                        .unwrap();
+                    let written = address.write(socket, b"ping").await.unwrap();
+                    assert_eq!(4, written);
+
+                    let mut buf = [0; 1];
+                    let read = address.read(socket, &mut buf).await.unwrap();
+                    assert_eq!(1, read);
+                    assert_eq!(socket, buf[0]);
+
+                    address.close(socket).await;
+                    completed.set(completed.get() + 1);
+                });
             }
-        }
+
+            scheduler.run();
+            assert_eq!(6, completed.get());
+        });
     }
 }
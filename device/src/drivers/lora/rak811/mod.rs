@@ -9,6 +9,7 @@ use crate::{
     kernel::{
         actor::{Actor, Address},
         channel::*,
+        util::Slots,
     },
     traits::lora::*,
 };
@@ -26,7 +27,6 @@ use embassy::{
 use embedded_hal::digital::v2::OutputPin;
 use futures::future::{select, Either};
 use futures::pin_mut;
-use heapless::consts::U2;
 pub use protocol::*;
 
 const RECV_BUFFER_LEN: usize = 256;
@@ -59,15 +59,15 @@ impl Initialized {
 
 pub struct Rak811Driver {
     initialized: Initialized,
-    command_channel: Channel<CommandBuffer, U2>,
-    response_channel: Channel<Response, U2>,
+    command_channel: Channel<CommandBuffer, Slots<2>>,
+    response_channel: Channel<Response, Slots<2>>,
 }
 
 pub struct Rak811Controller<'a> {
     config: LoraConfig,
     initialized: &'a Initialized,
-    command_producer: ChannelSender<'a, CommandBuffer, U2>,
-    response_consumer: ChannelReceiver<'a, Response, U2>,
+    command_producer: ChannelSender<'a, CommandBuffer, Slots<2>>,
+    response_consumer: ChannelReceiver<'a, Response, Slots<2>>,
 }
 
 pub struct Rak811Modem<'a, UART, RESET>
@@ -79,8 +79,8 @@ where
     uart: UART,
     reset: RESET,
     parse_buffer: Buffer,
-    command_consumer: ChannelReceiver<'a, CommandBuffer, U2>,
-    response_producer: ChannelSender<'a, Response, U2>,
+    command_consumer: ChannelReceiver<'a, CommandBuffer, Slots<2>>,
+    response_producer: ChannelSender<'a, Response, Slots<2>>,
 }
 
 impl Rak811Driver {
@@ -120,8 +120,8 @@ where
         initialized: &'a Initialized,
         uart: UART,
         reset: RESET,
-        command_consumer: ChannelReceiver<'a, CommandBuffer, U2>,
-        response_producer: ChannelSender<'a, Response, U2>,
+        command_consumer: ChannelReceiver<'a, CommandBuffer, Slots<2>>,
+        response_producer: ChannelSender<'a, Response, Slots<2>>,
     ) -> Self {
         Self {
             initialized,
@@ -317,8 +317,8 @@ impl<'a> LoraDriver for Rak811Controller<'a> {
 impl<'a> Rak811Controller<'a> {
     pub fn new(
         initialized: &'a Initialized,
-        command_producer: ChannelSender<'a, CommandBuffer, U2>,
-        response_consumer: ChannelReceiver<'a, Response, U2>,
+        command_producer: ChannelSender<'a, CommandBuffer, Slots<2>>,
+        response_consumer: ChannelReceiver<'a, Response, Slots<2>>,
     ) -> Self {
         Self {
             config: LoraConfig::new(),
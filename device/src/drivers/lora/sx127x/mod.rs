@@ -1,3 +1,4 @@
+use crate::kernel::pool::{Pool, PoolBox};
 use crate::traits::lora::*;
 use core::future::Future;
 use embassy::time::*;
@@ -18,6 +19,12 @@ mod sx127x_radio;
 
 use sx127x_radio::{RadioPhyEvent, Sx127xRadio as Radio};
 
+const DOWNLINK_LEN: usize = 255;
+
+/// Buffers for downlink data, which is handed to the caller of `send_recv` before the
+/// next message is sent.
+static DOWNLINK_POOL: Pool<DOWNLINK_LEN, 1> = Pool::new();
+
 enum DriverState<SPI, CS, RESET, E>
 where
     SPI: Transfer<u8, Error = E> + Write<u8, Error = E> + 'static,
@@ -49,7 +56,7 @@ pub enum DriverEvent {
     JoinFailed,
     SessionExpired,
     Ack,
-    AckWithData(usize, [u8; 255]),
+    AckWithData(usize, PoolBox<DOWNLINK_LEN>),
     AckTimeout,
     None,
 }
@@ -134,9 +141,12 @@ where
                                 fcnt_down,
                                 data,
                             );
-                            let mut buf = [0; 255];
-                            buf[0..data.len()].copy_from_slice(&data[0..data.len()]);
-                            return DriverEvent::AckWithData(data.len(), buf);
+                            if let Some(mut buf) = DOWNLINK_POOL.alloc() {
+                                buf[0..data.len()].copy_from_slice(&data[0..data.len()]);
+                                return DriverEvent::AckWithData(data.len(), buf);
+                            }
+                            warn!("No buffer available for downlink data, dropping it");
+                            return DriverEvent::Ack;
                         } else {
                             trace!("Downlink received \t\t(FcntDown={})", fcnt_down);
                             return DriverEvent::Ack;
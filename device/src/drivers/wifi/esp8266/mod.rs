@@ -12,7 +12,11 @@ mod socket_pool;
 use socket_pool::SocketPool;
 
 use crate::{
-    kernel::channel::*,
+    kernel::{
+        channel::*,
+        pool::{Pool, PoolBox},
+        util::Slots,
+    },
     traits::{
         ip::{IpAddress, IpProtocol, SocketAddress},
         tcp::{TcpError, TcpStack},
@@ -32,10 +36,18 @@ use embassy::{
 use embedded_hal::digital::v2::OutputPin;
 use futures::future::{select, Either};
 use futures::pin_mut;
-use heapless::consts::U2;
 use protocol::{Command, ConnectionType, Response as AtResponse};
 
 pub const BUFFER_LEN: usize = 512;
+const COMMAND_LEN: usize = 256;
+
+/// Buffers for data received from the modem, of which a single one is in use by the
+/// controller at a time.
+static DATA_POOL: Pool<BUFFER_LEN, 2> = Pool::new();
+
+/// Buffers for commands and data written to the modem, of which a single one is in use
+/// by the controller at a time.
+static COMMAND_POOL: Pool<COMMAND_LEN, 2> = Pool::new();
 
 #[derive(Debug, Clone, Copy)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -51,7 +63,14 @@ pub enum DriverError {
     OperationNotSupported,
 }
 
-type CommandBuffer = (usize, [u8; 256]);
+type CommandBuffer = (usize, PoolBox<COMMAND_LEN>);
+
+/// Copy bytes to be written to the modem into a buffer from the command pool.
+fn command_buffer(bytes: &[u8]) -> Result<CommandBuffer, DriverError> {
+    let mut buffer = COMMAND_POOL.alloc().ok_or(DriverError::WriteError)?;
+    buffer[0..bytes.len()].copy_from_slice(bytes);
+    Ok((bytes.len(), buffer))
+}
 
 pub struct Initialized {
     signal: Signal<Result<(), DriverError>>,
@@ -81,9 +100,9 @@ impl Initialized {
 pub struct Esp8266Controller<'a> {
     initialized: &'a Initialized,
     socket_pool: SocketPool,
-    command_producer: ChannelSender<'a, CommandBuffer, U2>,
-    response_consumer: ChannelReceiver<'a, AtResponse, U2>,
-    notification_consumer: ChannelReceiver<'a, AtResponse, U2>,
+    command_producer: ChannelSender<'a, CommandBuffer, Slots<2>>,
+    response_consumer: ChannelReceiver<'a, AtResponse, Slots<2>>,
+    notification_consumer: ChannelReceiver<'a, AtResponse, Slots<2>>,
 }
 
 pub struct Esp8266Modem<'a, UART, ENABLE, RESET>
@@ -97,16 +116,16 @@ where
     enable: ENABLE,
     reset: RESET,
     parse_buffer: Buffer,
-    command_consumer: ChannelReceiver<'a, CommandBuffer, U2>,
-    response_producer: ChannelSender<'a, AtResponse, U2>,
-    notification_producer: ChannelSender<'a, AtResponse, U2>,
+    command_consumer: ChannelReceiver<'a, CommandBuffer, Slots<2>>,
+    response_producer: ChannelSender<'a, AtResponse, Slots<2>>,
+    notification_producer: ChannelSender<'a, AtResponse, Slots<2>>,
 }
 
 pub struct Esp8266Driver {
     initialized: Initialized,
-    command_channel: Channel<CommandBuffer, U2>,
-    response_channel: Channel<AtResponse, U2>,
-    notification_channel: Channel<AtResponse, U2>,
+    command_channel: Channel<CommandBuffer, Slots<2>>,
+    response_channel: Channel<AtResponse, Slots<2>>,
+    notification_channel: Channel<AtResponse, Slots<2>>,
 }
 
 impl Esp8266Driver {
@@ -152,9 +171,9 @@ where
         uart: UART,
         enable: ENABLE,
         reset: RESET,
-        command_consumer: ChannelReceiver<'a, CommandBuffer, U2>,
-        response_producer: ChannelSender<'a, AtResponse, U2>,
-        notification_producer: ChannelSender<'a, AtResponse, U2>,
+        command_consumer: ChannelReceiver<'a, CommandBuffer, Slots<2>>,
+        response_producer: ChannelSender<'a, AtResponse, Slots<2>>,
+        notification_producer: ChannelSender<'a, AtResponse, Slots<2>>,
     ) -> Self {
         Self {
             initialized,
@@ -324,6 +343,7 @@ where
                 | AtResponse::ReadyForData
                 | AtResponse::ReceivedDataToSend(..)
                 | AtResponse::DataReceived(..)
+                | AtResponse::DataDropped(..)
                 | AtResponse::SendOk
                 | AtResponse::SendFail
                 | AtResponse::WifiConnectionFailure(..)
@@ -355,9 +375,9 @@ where
 impl<'a> Esp8266Controller<'a> {
     pub fn new(
         initialized: &'a Initialized,
-        command_producer: ChannelSender<'a, CommandBuffer, U2>,
-        response_consumer: ChannelReceiver<'a, AtResponse, U2>,
-        notification_consumer: ChannelReceiver<'a, AtResponse, U2>,
+        command_producer: ChannelSender<'a, CommandBuffer, Slots<2>>,
+        response_consumer: ChannelReceiver<'a, AtResponse, Slots<2>>,
+        notification_consumer: ChannelReceiver<'a, AtResponse, Slots<2>>,
     ) -> Self {
         Self {
             initialized,
@@ -379,10 +399,9 @@ impl<'a> Esp8266Controller<'a> {
         );
 
         bytes.push_str("\r\n").unwrap();
-        let bs = bytes.as_bytes();
-        let mut data = [0; 256];
-        data[0..bs.len()].copy_from_slice(&bs[0..bs.len()]);
-        self.command_producer.send((bs.len(), data)).await;
+        self.command_producer
+            .send(command_buffer(bytes.as_bytes())?)
+            .await;
         Ok(self.response_consumer.receive().await)
     }
 
@@ -458,9 +477,9 @@ impl<'a> TcpStack for Esp8266Controller<'a> {
     type SocketHandle = u8;
 
     #[rustfmt::skip]
-    type OpenFuture<'m> where 'a: 'm = impl Future<Output = Self::SocketHandle> + 'm;
+    type OpenFuture<'m> where 'a: 'm = impl Future<Output = Result<Self::SocketHandle, TcpError>> + 'm;
     fn open<'m>(&'m mut self) -> Self::OpenFuture<'m> {
-        async move { self.socket_pool.open().await }
+        async move { Ok(self.socket_pool.open().await) }
     }
 
     #[rustfmt::skip]
@@ -498,9 +517,8 @@ impl<'a> TcpStack for Esp8266Controller<'a> {
                 Ok(AtResponse::Ok) => {
                     match self.response_consumer.receive().await {
                         AtResponse::ReadyForData => {
-                            let mut data = [0; 256];
-                            data[0..buf.len()].copy_from_slice(&buf[0..buf.len()]);
-                            self.command_producer.send((buf.len(), data)).await;
+                            let data = command_buffer(buf).map_err(|_| TcpError::WriteError)?;
+                            self.command_producer.send(data).await;
                             let mut data_sent: Option<usize> = None;
                             loop {
                                 match self.response_consumer.receive().await {
@@ -565,6 +583,7 @@ impl<'a> TcpStack for Esp8266Controller<'a> {
                             Ok(len)
                         }
                         Ok(AtResponse::Ok) => Ok(0),
+                        Ok(AtResponse::DataDropped(len)) => Err(TcpError::DataDropped(len)),
                         Ok(r) => {
                             warn!("Unexpected response: {:?}", r);
                             Err(TcpError::ReadError)
@@ -585,6 +604,8 @@ impl<'a> TcpStack for Esp8266Controller<'a> {
                             return Ok(rp);
                         }
                     }
+                    // The data read so far is followed by a gap, so the caller must know
+                    Err(e @ TcpError::DataDropped(_)) => return Err(e),
                     Err(e) => {
                         if rp == 0 {
                             return Err(e);
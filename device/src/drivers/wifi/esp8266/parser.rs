@@ -15,7 +15,7 @@ use crate::traits::ip::{IpAddress, IpAddressV4};
 use super::{
     num::{atoi_u8, atoi_usize},
     protocol::{FirmwareInfo, IpAddresses, ResolverAddresses, Response, WifiConnectionFailure},
-    BUFFER_LEN,
+    DATA_POOL,
 };
 
 fn parse_u8(input: &[u8]) -> IResult<&[u8], u8> {
@@ -307,13 +307,20 @@ named!(
         crlf >>
         ok >>
         ( {
-            let mut buf = [0; BUFFER_LEN];
-            for (i, b) in data.iter().enumerate() {
-                //log::info!( "copy {} @ {}", *b as char, i);
-                buf[i] = *b;
+            match DATA_POOL.alloc() {
+                Some(mut buf) => {
+                    for (i, b) in data.iter().enumerate() {
+                        //log::info!( "copy {} @ {}", *b as char, i);
+                        buf[i] = *b;
+                    }
+                    //log::info!("------------> onwards {:?}", buf);
+                    Response::DataReceived(buf, len)
+                }
+                None => {
+                    warn!("No buffer available for {} bytes of received data, dropping them", len);
+                    Response::DataDropped(len)
+                }
             }
-            //log::info!("------------> onwards {:?}", buf);
-            Response::DataReceived(buf, len)
         } )
     )
 );
@@ -1,4 +1,5 @@
 use super::BUFFER_LEN;
+use crate::kernel::pool::PoolBox;
 use crate::traits::ip::{IpAddress, IpAddressV4, SocketAddress};
 use core::fmt;
 use core::fmt::{Debug, Write};
@@ -119,7 +120,6 @@ impl<'a> Command<'a> {
 }
 
 /// Responses (including unsolicited) which may be parsed from the board.
-#[allow(clippy::large_enum_variant)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Response {
     None,
@@ -131,7 +131,8 @@ pub enum Response {
     SendOk,
     SendFail,
     DataAvailable { link_id: usize, len: usize },
-    DataReceived([u8; BUFFER_LEN], usize),
+    DataReceived(PoolBox<BUFFER_LEN>, usize),
+    DataDropped(usize),
     WifiConnected,
     WifiConnectionFailure(WifiConnectionFailure),
     WifiDisconnect,
@@ -164,6 +165,7 @@ impl defmt::Format for Response {
             }
             //Response::DataReceived(d, l) => dump_data("DataReceived", d, *l, f),
             Response::DataReceived(_, _) => defmt::write!(f, "DataReceived"),
+            Response::DataDropped(len) => defmt::write!(f, "DataDropped {}", len),
             Response::WifiConnected => defmt::write!(f, "WifiConnected"),
             Response::WifiConnectionFailure(v) => defmt::write!(f, "WifiConnectionFailure {}", v),
             Response::WifiDisconnect => defmt::write!(f, "WifiDisconnect"),
@@ -199,6 +201,7 @@ impl Debug for Response {
                 .finish(),
             //Response::DataReceived(d, l) => dump_data("DataReceived", d, *l, f),
             Response::DataReceived(_, _) => f.write_str("DataReceived"),
+            Response::DataDropped(len) => f.debug_tuple("DataDropped").field(len).finish(),
             Response::WifiConnected => f.write_str("WifiConnected"),
             Response::WifiConnectionFailure(v) => {
                 f.debug_tuple("WifiConnectionFailure").field(v).finish()
@@ -312,7 +315,7 @@ mod test {
         let mut buf = ArrayString::<256>::new();
         let data = b"FOO\0BAR";
 
-        let mut array = [0u8; super::BUFFER_LEN];
+        let mut array = super::super::DATA_POOL.alloc().unwrap();
         for (&x, p) in data.iter().zip(array.iter_mut()) {
             *p = x;
         }
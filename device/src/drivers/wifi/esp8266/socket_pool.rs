@@ -3,7 +3,8 @@ use core::future::Future;
 use core::pin::Pin;
 use core::task::{Context, Poll, Waker};
 
-use heapless::{consts::U8, spsc::Queue};
+use crate::kernel::util::WakerSet;
+use heapless::consts::U8;
 
 #[derive(PartialEq)]
 enum SocketState {
@@ -21,14 +22,15 @@ impl Default for SocketState {
 
 pub(crate) struct SocketPool {
     sockets: RefCell<[SocketState; 4]>,
-    waiters: RefCell<Queue<Waker, U8>>,
+    // Tasks waiting for a socket to be closed. Any beyond eight busy-poll until one is.
+    waiters: WakerSet<U8>,
 }
 
 impl SocketPool {
     pub(crate) fn new() -> Self {
         Self {
             sockets: Default::default(),
-            waiters: RefCell::new(Queue::new()),
+            waiters: WakerSet::new(),
         }
     }
 
@@ -42,6 +44,8 @@ impl SocketPool {
         match sockets[index] {
             SocketState::HalfClosed => {
                 sockets[index] = SocketState::Closed;
+                drop(sockets);
+                self.waiters.wake();
             }
             SocketState::Open | SocketState::Connected => {
                 sockets[index] = SocketState::HalfClosed;
@@ -58,7 +62,7 @@ impl SocketPool {
         sockets[index] == SocketState::Closed || sockets[index] == SocketState::HalfClosed
     }
 
-    fn poll_open(&self, waker: &Waker, waiting: bool) -> Poll<u8> {
+    fn poll_open(&self, waker: &Waker) -> Poll<u8> {
         let mut sockets = self.sockets.borrow_mut();
         let available = sockets
             .iter()
@@ -70,9 +74,7 @@ impl SocketPool {
             sockets[index] = SocketState::Open;
             Poll::Ready(index as u8)
         } else {
-            if !waiting {
-                self.waiters.borrow_mut().enqueue(waker.clone()).unwrap();
-            }
+            self.waiters.register(waker);
             Poll::Pending
         }
     }
@@ -80,33 +82,28 @@ impl SocketPool {
 
 pub(crate) struct OpenFuture<'a> {
     pool: &'a SocketPool,
-    waiting: bool,
 }
 
 impl<'a> OpenFuture<'a> {
     fn new(pool: &'a SocketPool) -> Self {
-        Self {
-            pool,
-            waiting: false,
-        }
+        Self { pool }
     }
 }
 impl<'a> Future for OpenFuture<'a> {
     type Output = u8;
 
-    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let result = self.pool.poll_open(cx.waker(), self.waiting);
-        if result.is_pending() {
-            self.waiting = true;
-        }
-        result
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.pool.poll_open(cx.waker())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testutil::*;
+    use core::cell::Cell;
     use futures::executor::block_on;
+    use std::rc::Rc;
 
     #[test]
     fn max_simultaneous_sockets() {
@@ -121,4 +118,39 @@ mod tests {
             assert_eq!(expected, actual);
         }
     }
+
+    #[test]
+    fn test_waiters_woken_on_close() {
+        explore(32, |scheduler| {
+            let pool: &'static SocketPool = Box::leak(Box::new(SocketPool::new()));
+            let in_use = Rc::new(Cell::new([false; 4]));
+            let completed = Rc::new(Cell::new(0));
+
+            // More clients than sockets, and than waiters that are woken without polling
+            for _ in 0..12 {
+                let in_use = in_use.clone();
+                let completed = completed.clone();
+                scheduler.spawn(async move {
+                    let socket = pool.open().await;
+                    let mut sockets = in_use.get();
+                    assert!(!sockets[socket as usize], "socket {} opened twice", socket);
+                    sockets[socket as usize] = true;
+                    in_use.set(sockets);
+
+                    yield_now().await;
+
+                    sockets = in_use.get();
+                    sockets[socket as usize] = false;
+                    in_use.set(sockets);
+                    // Closed by the client, then by the modem
+                    pool.close(socket);
+                    pool.close(socket);
+                    completed.set(completed.get() + 1);
+                });
+            }
+
+            scheduler.run();
+            assert_eq!(12, completed.get());
+        });
+    }
 }
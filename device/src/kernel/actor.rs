@@ -1,16 +1,22 @@
+#[cfg(feature = "metrics")]
+use super::metrics::ActorMetrics;
 use super::{
-    channel::{Channel, ChannelError, ChannelReceive, ChannelReceiver, ChannelSender},
+    channel::{Channel, ChannelError, ChannelReceive, ChannelReceiver, ChannelSend, ChannelSender},
+    metrics::{HandlerStart, MetricsRecorder},
     signal::{SignalFuture, SignalSlot},
-    util::ImmediateFuture,
+    trace,
+    util::{Capacity, ImmediateFuture, RingBuffer, Slots},
 };
-use core::cell::{RefCell, UnsafeCell};
+use atomic_polyfill::{AtomicU32, AtomicU8, Ordering};
+use core::cell::{Cell, RefCell, UnsafeCell};
 use core::future::Future;
+use core::marker::PhantomData;
 use core::pin::Pin;
 use core::task::{Context, Poll};
-use embassy::executor::{raw::Task, SpawnError, SpawnToken, Spawner};
-use embassy::util::DropBomb;
-use generic_array::GenericArray;
-use heapless::{consts, ArrayLength};
+use embassy::executor::{raw::Task, SendSpawner, SpawnError, SpawnToken, Spawner};
+use embassy::time::{Duration, Timer};
+use embassy::util::{AtomicWaker, DropBomb, Signal};
+use futures::future::poll_fn;
 
 /// Trait that each actor must implement. An Actor must specify a message type
 /// it acts on, and an implementation of a message handler in `on_message`.
@@ -20,11 +26,31 @@ use heapless::{consts, ArrayLength};
 pub trait Actor: Sized {
     /// Max length of the message queue for this actor. Defaults to 1 for
     /// low footprint by default.
-    type MessageQueueSize<'a>: ArrayLength<ActorMessage<'a, Self>>
-        + ArrayLength<SignalSlot<Self::Response>>
-    where
-        Self: 'a,
-    = consts::U1;
+    type MessageQueueSize: Capacity = Slots<1>;
+
+    /// Max length of the high priority message queue for this actor. Messages for which
+    /// `priority` returns `Priority::High` are enqueued here, and handled before any
+    /// message in the regular queue. Defaults to 1.
+    type PriorityQueueSize: Capacity = Slots<1>;
+
+    /// Max number of requests to this actor awaiting a response at the same time, each
+    /// holding on to a signal slot until the response has been received. Defaults to the
+    /// length of the message queue.
+    ///
+    /// Actors that are only ever notified may set this to `Slots<0>`, in which case
+    /// requests fail with `SignalError::NoAvailableSignal`.
+    type PendingRequests: Capacity = Self::MessageQueueSize;
+
+    /// Max number of messages that can be put aside with `Address::stash` until the actor
+    /// is ready to handle them. Defaults to 0, disabling the stash.
+    type StashSize: Capacity = Slots<0>;
+
+    /// How many messages are handled at the same time. Defaults to `Exclusive`, handling
+    /// each message with exclusive access to the actor in `on_message`.
+    ///
+    /// With `Shared`, messages are instead handled with shared access to the actor in
+    /// `ConcurrentActor::on_shared_message`, which the actor must then implement.
+    type Concurrency: Concurrency<Self> = Exclusive;
 
     /// The configuration that this actor will expect when mounted.
     type Configuration = ();
@@ -70,6 +96,73 @@ pub trait Actor: Sized {
         self: Pin<&'m mut Self>,
         message: Self::Message<'m>,
     ) -> Self::OnMessageFuture<'m>;
+
+    /// The executor this actor should run on when spawned with a `PrioritySpawner`.
+    /// Defaults to `NormalPriority`.
+    ///
+    /// Actors with `HighPriority` are moved to another executor, so they and their
+    /// messages must be `Send`.
+    type Executor: ExecutorPriority<Self> = NormalPriority;
+
+    /// What happens when a notification sent through an `IsrAddress` finds the queue
+    /// full. Defaults to `OverflowPolicy::DropNewest`.
+    const OVERFLOW_POLICY: OverflowPolicy = OverflowPolicy::DropNewest;
+
+    /// The priority of a message, deciding which queue it is delivered through.
+    ///
+    /// The default implementation treats every message as `Priority::Normal`.
+    fn priority(_: &Self::Message<'_>) -> Priority {
+        Priority::Normal
+    }
+
+    /// Called when the actor is stopped or about to be restarted. Any message
+    /// processing in progress has been dropped at this point.
+    ///
+    /// The default implementation does nothing.
+    fn on_stop(&mut self) {}
+
+    /// Called when the actor is restarted, after `on_stop`, allowing it to reset its
+    /// state before `on_start` is called again.
+    ///
+    /// The default implementation does nothing.
+    fn on_restart(&mut self) {}
+}
+
+/// An actor handling messages with shared access to itself, allowing several messages to
+/// be handled at the same time. Implemented by actors with a `Concurrency` of `Shared`.
+pub trait ConcurrentActor: Actor {
+    /// The future type returned in `on_shared_message`, usually derived from an `async move`
+    /// block in the implementation.
+    type OnSharedMessageFuture<'a>: Future<Output = Self::Response>
+    where
+        Self: 'a;
+
+    /// Handle an incoming message with shared access to the actor, allowing other messages
+    /// to be handled at the same time. Called instead of `on_message`.
+    fn on_shared_message<'m>(
+        self: Pin<&'m Self>,
+        message: Self::Message<'m>,
+    ) -> Self::OnSharedMessageFuture<'m>;
+}
+
+/// Priority of a message sent to an actor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Priority {
+    Normal,
+    High,
+}
+
+/// What to do with a notification sent from interrupt context when the queue is full.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum OverflowPolicy {
+    /// Drop the notification being sent, returning an error.
+    DropNewest,
+    /// Drop the oldest notification in the queue to make room. Requests are never dropped,
+    /// so if the queue only holds requests, the notification being sent is dropped and an
+    /// error returned, as with `DropNewest`.
+    DropOldest,
 }
 
 /// A handle to another actor for dispatching messages.
@@ -87,6 +180,10 @@ impl<'a, A: Actor> Address<'a, A> {
     pub fn new(state: &'a ActorContext<'a, A>) -> Self {
         Self { state }
     }
+
+    pub(crate) fn context(&self) -> &'a ActorContext<'a, A> {
+        self.state
+    }
 }
 
 impl<'a, A: Actor> Address<'a, A> {
@@ -103,6 +200,9 @@ impl<'a, A: Actor> Address<'a, A> {
     /// ensure that the response to the request is fully `.await`'d before returning.
     /// Leaving an in-flight request dangling while references have gone out of lifetime
     /// scope will result in a panic.
+    ///
+    /// If the actor is restarted or stopped before it has responded, the returned future
+    /// resolves to `ActorError::Abandoned`.
     #[must_use = "The returned future must be awaited"]
     pub fn request<'m>(&self, message: A::Message<'m>) -> Result<RequestFuture<'a, A>, ActorError>
     where
@@ -111,6 +211,71 @@ impl<'a, A: Actor> Address<'a, A> {
         self.state.request(message)
     }
 
+    /// Perform an _async_ message request to the actor behind this address, giving up
+    /// if no response has been received within the provided timeout. If an error occurs
+    /// when enqueueing the message on the destination actor, an error is returned.
+    ///
+    /// The timeout starts when the returned future is first polled. Unlike `request`, the
+    /// returned future may be dropped at any time. If the actor has not yet started
+    /// processing the message, it will be skipped. If the actor is restarted or stopped
+    /// while processing the message, `ActorError::Abandoned` is returned.
+    #[must_use = "The returned future must be awaited"]
+    pub fn request_with_timeout(
+        &self,
+        message: A::Message<'a>,
+        timeout: Duration,
+    ) -> Result<RequestTimeoutFuture<'a, A>, ActorError> {
+        self.state.request_with_timeout(message, timeout)
+    }
+
+    /// Perform an _async_ message request to the actor behind this address, waiting
+    /// for a free signal slot and space in the destination actor's queue if necessary.
+    ///
+    /// The returned future completes when the receiving actor have processed the
+    /// message, and the result from processing is made available. If the actor is
+    /// restarted or stopped before it has responded, `ActorError::Abandoned` is returned.
+    ///
+    /// Up to four tasks wait for a signal slot without polling. Any further tasks waiting
+    /// at the same time busy-poll until a slot is released.
+    ///
+    /// # Panics
+    /// While the request message may contain non-static references, the user must
+    /// ensure that the returned future is fully `.await`'d before returning. Dropping it
+    /// once the message has been enqueued, before it completes, will result in a panic.
+    /// Use `request_cancellable` for messages that live as long as the actor.
+    pub async fn request_async<'m>(
+        &self,
+        message: A::Message<'m>,
+    ) -> Result<A::Response, ActorError>
+    where
+        'a: 'm,
+    {
+        self.state.request_async(message).await
+    }
+
+    /// Perform an _async_ message request to the actor behind this address, waiting
+    /// for a free signal slot and space in the destination actor's queue if necessary.
+    ///
+    /// Unlike `request_async`, the returned future may be dropped at any time, such as
+    /// when racing it against a timer. If the actor has not yet started processing the
+    /// message, it will be skipped.
+    pub async fn request_cancellable(
+        &self,
+        message: A::Message<'a>,
+    ) -> Result<A::Response, ActorError> {
+        self.state.request_cancellable(message).await
+    }
+
+    /// Perform a message request to the actor behind this address without waiting for a
+    /// free signal slot or queue space, returning the future of its response. Dropping the
+    /// future cancels the request, like `request_cancellable`.
+    pub(crate) fn try_request_cancellable(
+        &self,
+        message: A::Message<'a>,
+    ) -> Result<SignalFuture<'a, A::Response>, ActorError> {
+        self.state.enqueue_request(message).map(SignalFuture::new)
+    }
+
     /// Perform an message notification to the actor behind this address. If an error
     /// occurs when enqueueing the message on the destination actor, an error is returned.
     ///
@@ -120,8 +285,126 @@ impl<'a, A: Actor> Address<'a, A> {
     pub fn notify<'m>(&self, message: A::Message<'a>) -> Result<(), ActorError> {
         self.state.notify(message)
     }
+
+    /// Perform an message notification to the actor behind this address, waiting for
+    /// space in the destination actor's queue if it is full.
+    ///
+    /// The returned future completes once the message is enqueued.
+    ///
+    /// Up to four tasks wait for space in the queue without polling. Any further tasks
+    /// waiting at the same time busy-poll until a message is dequeued.
+    pub async fn notify_async(&self, message: A::Message<'a>) {
+        self.state.notify_async(message).await
+    }
+
+    /// Report a fatal error in the actor behind this address to its supervisor, if any.
+    /// The actor keeps running until the supervisor decides what to do.
+    pub fn fail(&self) {
+        self.state.fail()
+    }
+
+    /// Restart the actor behind this address. Any message processing in progress is
+    /// dropped, and the actor is started again once `on_stop` and `on_restart` have run.
+    ///
+    /// A request that was being processed is abandoned, and its requester receives
+    /// `ActorError::Abandoned`.
+    pub fn restart(&self) {
+        self.state.restart()
+    }
+
+    /// Stop the actor behind this address. Any message processing in progress is
+    /// dropped, `on_stop` is called and the actor task exits.
+    pub fn stop(&self) {
+        self.state.stop()
+    }
+
+    /// Get a snapshot of the runtime metrics of the actor behind this address.
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> ActorMetrics {
+        self.state.metrics()
+    }
+
+    /// Take over responding to the request currently being handled by the actor behind
+    /// this address, returning `None` if the message being handled is a notification.
+    ///
+    /// This may only be called by the actor itself while handling a message, before the
+    /// handler has returned, passing itself as `actor`. Returns `None` if `actor` is not
+    /// the actor behind this address. The value returned from the handler is then dropped,
+    /// and the requester is answered once the returned `Responder` is used, allowing the
+    /// actor to handle other messages in the meantime.
+    pub fn defer(&self, actor: &A) -> Option<Responder<'a, A::Response>> {
+        if self.state.is_actor(actor) {
+            self.state.defer()
+        } else {
+            None
+        }
+    }
+
+    /// Put the message being handled aside until `unstash` is called, returning the
+    /// message if the stash is full.
+    ///
+    /// This may only be called by the actor itself while handling a message, passing
+    /// itself as `actor` along with the message it is handling. The message is returned
+    /// if `actor` is not the actor behind this address. If the message is a request, the
+    /// value returned from the handler is dropped, and the request is answered once the
+    /// message is handled again.
+    pub fn stash<'m>(&self, actor: &A, message: A::Message<'m>) -> Result<(), A::Message<'m>>
+    where
+        'a: 'm,
+    {
+        if self.state.is_actor(actor) {
+            self.state.stash(message)
+        } else {
+            Err(message)
+        }
+    }
+
+    /// Handle the stashed messages again, in the order they were stashed, before any new
+    /// messages. May only be called by the actor itself, passing itself as `actor`, when
+    /// its state has changed. Does nothing if `actor` is not the actor behind this address.
+    pub fn unstash(&self, actor: &A) {
+        if self.state.is_actor(actor) {
+            self.state.unstash()
+        }
+    }
+
+    /// Create an `IsrAddress` for notifying the actor behind this address from interrupt
+    /// handlers and HAL callbacks.
+    pub fn isr_address(&self) -> IsrAddress<'a, A> {
+        IsrAddress { state: self.state }
+    }
+
+    /// Create a type-erased `Recipient` for the actor behind this address.
+    pub fn recipient(&self) -> Recipient<'a, A::Message<'a>> {
+        Recipient {
+            context: self.state as *const _ as *const (),
+            notify: ActorContext::<'a, A>::notify_erased,
+            poll_notify: ActorContext::<'a, A>::poll_notify_erased,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Create a type-erased `Recipient` for the actor behind this address, accepting messages
+    /// of type `M` and converting them using the adapter `F`. Messages the adapter returns
+    /// `None` for are discarded.
+    pub fn recipient_with<M: Clone, F>(&self) -> Recipient<'a, M>
+    where
+        F: MessageAdapter<M, A::Message<'a>>,
+    {
+        Recipient {
+            context: self.state as *const _ as *const (),
+            notify: ActorContext::<'a, A>::notify_adapted_erased::<M, F>,
+            poll_notify: ActorContext::<'a, A>::poll_notify_adapted_erased::<M, F>,
+            _marker: PhantomData,
+        }
+    }
 }
 
+// Safety: Every operation available through an address either uses atomics or
+// critical sections, so that it may be used from other executors and interrupts.
+unsafe impl<'a, A: Actor> Send for Address<'a, A> where A::Message<'a>: Send {}
+unsafe impl<'a, A: Actor> Sync for Address<'a, A> where A::Message<'a>: Send {}
+
 impl<'a, A: Actor> Copy for Address<'a, A> {}
 
 impl<'a, A: Actor> Clone for Address<'a, A> {
@@ -130,18 +413,157 @@ impl<'a, A: Actor> Clone for Address<'a, A> {
     }
 }
 
-pub struct MessageChannel<'a, T, N>
+/// A handle for responding to a deferred request, obtained with `Address::defer`.
+///
+/// Each pending responder holds on to one of the signal slots of the actor, limiting
+/// the number of outstanding requests to `PendingRequests`. Dropping a responder
+/// without responding abandons the request. A responder that is leaked never releases
+/// its slot, and the requester only gives up if it used `request_with_timeout`.
+pub struct Responder<'a, T: Send> {
+    signal: Option<&'a SignalSlot<T>>,
+}
+
+impl<'a, T: Send> Responder<'a, T> {
+    fn new(signal: &'a SignalSlot<T>) -> Self {
+        Self {
+            signal: Some(signal),
+        }
+    }
+
+    /// Complete the request with the provided response.
+    pub fn respond(mut self, value: T) {
+        self.signal.take().unwrap().signal(value);
+    }
+
+    /// Returns true if the requester has given up waiting for the response.
+    pub fn is_cancelled(&self) -> bool {
+        self.signal.map(|s| s.is_cancelled()).unwrap_or(true)
+    }
+}
+
+impl<'a, T: Send> Drop for Responder<'a, T> {
+    fn drop(&mut self) {
+        if let Some(signal) = self.signal.take() {
+            signal.abandon();
+        }
+    }
+}
+
+/// A handle to an actor that is safe to use from interrupt context, created with
+/// `Address::isr_address`.
+///
+/// Notifications sent through it never wait for space in the queue. Instead, the
+/// `OVERFLOW_POLICY` of the actor decides which message is dropped when the queue
+/// is full, and the number of messages dropped is counted.
+pub struct IsrAddress<'a, A>
+where
+    A: Actor + 'static,
+{
+    state: &'a ActorContext<'a, A>,
+}
+
+impl<'a, A: Actor> IsrAddress<'a, A> {
+    /// Notify the actor behind this address. With `OverflowPolicy::DropNewest`, an error is
+    /// returned if the queue is full. With `OverflowPolicy::DropOldest`, the notification is
+    /// enqueued unless the queue only holds requests.
+    pub fn notify_from_isr(&self, message: A::Message<'a>) -> Result<(), ActorError> {
+        self.state.notify_from_isr(message)
+    }
+
+    /// The number of messages dropped because the queue was full when notifying through
+    /// an `IsrAddress`.
+    pub fn dropped(&self) -> u32 {
+        self.state.isr_drops.load(Ordering::Relaxed)
+    }
+}
+
+// Safety: Notifying from interrupt context only uses atomics and critical sections.
+unsafe impl<'a, A: Actor> Send for IsrAddress<'a, A> where A::Message<'a>: Send {}
+unsafe impl<'a, A: Actor> Sync for IsrAddress<'a, A> where A::Message<'a>: Send {}
+
+impl<'a, A: Actor> Copy for IsrAddress<'a, A> {}
+
+impl<'a, A: Actor> Clone for IsrAddress<'a, A> {
+    fn clone(&self) -> Self {
+        Self { state: self.state }
+    }
+}
+
+/// Converts messages sent through a `Recipient` created with `Address::recipient_with`
+/// into messages of the actor behind it.
+pub trait MessageAdapter<M, T> {
+    fn adapt(message: M) -> Option<T>;
+}
+
+/// A type-erased handle to any actor accepting messages of type `M`.
+///
+/// Unlike an `Address`, a recipient does not depend on the type of the actor behind it,
+/// allowing generic components to send messages to different kinds of actors.
+pub struct Recipient<'a, M> {
+    context: *const (),
+    notify: unsafe fn(*const (), M) -> Result<(), ActorError>,
+    poll_notify: unsafe fn(*const (), &mut Option<M>, &mut Context<'_>) -> Poll<()>,
+    _marker: PhantomData<&'a ()>,
+}
+
+impl<'a, M> Recipient<'a, M> {
+    /// Perform an message notification to the actor behind this recipient. If an error
+    /// occurs when enqueueing the message on the destination actor, an error is returned.
+    pub fn notify(&self, message: M) -> Result<(), ActorError> {
+        unsafe { (self.notify)(self.context, message) }
+    }
+
+    /// Perform an message notification to the actor behind this recipient, waiting for
+    /// space in the destination actor's queue if it is full.
+    pub fn notify_async(&self, message: M) -> RecipientNotify<'a, M> {
+        RecipientNotify {
+            recipient: *self,
+            message: Some(message),
+        }
+    }
+}
+
+// Safety: See `Address`, which a recipient is a type-erased version of.
+unsafe impl<'a, M: Send> Send for Recipient<'a, M> {}
+unsafe impl<'a, M: Send> Sync for Recipient<'a, M> {}
+
+impl<'a, M> Copy for Recipient<'a, M> {}
+
+impl<'a, M> Clone for Recipient<'a, M> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+/// Future returned by `Recipient::notify_async`, completing once the message is enqueued.
+pub struct RecipientNotify<'a, M> {
+    recipient: Recipient<'a, M>,
+    message: Option<M>,
+}
+
+impl<'a, M> Unpin for RecipientNotify<'a, M> {}
+
+impl<'a, M> Future for RecipientNotify<'a, M> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        unsafe { (this.recipient.poll_notify)(this.recipient.context, &mut this.message, cx) }
+    }
+}
+
+pub struct MessageChannel<'a, T, C>
 where
-    N: ArrayLength<T>,
+    C: Capacity,
 {
-    channel: UnsafeCell<Channel<T, N>>,
-    channel_sender: UnsafeCell<Option<ChannelSender<'a, T, N>>>,
-    channel_receiver: UnsafeCell<Option<ChannelReceiver<'a, T, N>>>,
+    channel: UnsafeCell<Channel<T, C>>,
+    channel_sender: UnsafeCell<Option<ChannelSender<'a, T, C>>>,
+    channel_receiver: UnsafeCell<Option<ChannelReceiver<'a, T, C>>>,
 }
 
-impl<'a, T, N> MessageChannel<'a, T, N>
+impl<'a, T, C> MessageChannel<'a, T, C>
 where
-    N: ArrayLength<T>,
+    C: Capacity,
 {
     pub fn new() -> Self {
         Self {
@@ -158,24 +580,75 @@ where
     }
 
     pub fn send<'m>(&self, message: T) -> Result<(), ChannelError> {
-        let sender = unsafe { &mut *self.channel_sender.get() }.as_mut().unwrap();
+        let sender = unsafe { &*self.channel_sender.get() }.as_ref().unwrap();
         sender.try_send(message)
     }
 
-    pub fn receive<'m>(&self) -> ChannelReceive<'m, 'a, T, N> {
+    pub fn is_full(&self) -> bool {
+        let sender = unsafe { &*self.channel_sender.get() }.as_ref().unwrap();
+        sender.is_full()
+    }
+
+    /// Enqueue a message, removing the oldest message for which `displaceable` returns
+    /// true to make room if the channel is full. The message removed is returned, or the
+    /// message being sent if there was no room and no message could be removed.
+    pub fn send_displacing<F: FnMut(&T) -> bool>(
+        &self,
+        message: T,
+        displaceable: F,
+    ) -> Result<Option<T>, T> {
+        let sender = unsafe { &*self.channel_sender.get() }.as_ref().unwrap();
+        let receiver = unsafe { &*self.channel_receiver.get() }.as_ref().unwrap();
+        critical_section::with(|_| {
+            let displaced = if sender.is_full() {
+                match receiver.try_remove_first(displaceable) {
+                    Some(displaced) => Some(displaced),
+                    None => return Err(message),
+                }
+            } else {
+                None
+            };
+            sender.try_send(message).ok().unwrap();
+            Ok(displaced)
+        })
+    }
+
+    pub fn send_async<'m>(&'m self, message: T) -> ChannelSend<'m, 'a, T, C> {
+        let sender = unsafe { &*self.channel_sender.get() }.as_ref().unwrap();
+        sender.send(message)
+    }
+
+    pub fn poll_send_with<F: FnOnce() -> T>(&self, cx: &mut Context<'_>, f: F) -> Poll<()> {
+        let sender = unsafe { &*self.channel_sender.get() }.as_ref().unwrap();
+        sender.poll_enqueue_with(cx, f)
+    }
+
+    pub fn receive<'m>(&self) -> ChannelReceive<'m, 'a, T, C> {
         let receiver = unsafe { &*self.channel_receiver.get() }.as_ref().unwrap();
         receiver.receive()
     }
+
+    pub fn poll_receive(&self, cx: &mut Context<'_>) -> Poll<T> {
+        let receiver = unsafe { &*self.channel_receiver.get() }.as_ref().unwrap();
+        receiver.poll_dequeue(cx)
+    }
+
+    pub fn try_receive(&self) -> Result<T, ChannelError> {
+        let receiver = unsafe { &*self.channel_receiver.get() }.as_ref().unwrap();
+        receiver.try_receive()
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum ActorError {
     Channel(ChannelError),
     Signal(SignalError),
+    Timeout,
+    Abandoned,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum SignalError {
     NoAvailableSignal,
@@ -191,19 +664,168 @@ impl ActorSpawner for Spawner {
     }
 }
 
-enum ActorState<'a, A: Actor + 'static, N>
+/// Starts actors on another executor than the one mounting them, which requires the
+/// actors and their messages to be `Send`.
+pub trait HighPrioritySpawner: Clone + Copy {
+    fn start_high<A: Actor + Send>(
+        &self,
+        actor: &'static ActorContext<'static, A>,
+    ) -> Result<(), SpawnError>
+    where
+        A::Message<'static>: Send;
+}
+
+/// Spawns actors on another executor than the one mounting them, such as an embassy
+/// `InterruptExecutor`, allowing those actors to preempt the ones running in thread mode.
+#[derive(Clone, Copy)]
+pub struct SendActorSpawner {
+    spawner: SendSpawner,
+}
+
+impl SendActorSpawner {
+    /// Create a spawner for the executor behind the provided `SendSpawner`.
+    pub fn new(spawner: SendSpawner) -> Self {
+        Self { spawner }
+    }
+}
+
+impl HighPrioritySpawner for SendActorSpawner {
+    fn start_high<A: Actor + Send>(
+        &self,
+        actor: &'static ActorContext<'static, A>,
+    ) -> Result<(), SpawnError>
+    where
+        A::Message<'static>: Send,
+    {
+        self.spawner.spawn(actor.spawn())
+    }
+}
+
+/// Spawns actors with an `Executor` of `HighPriority` with one spawner, usually for an
+/// interrupt executor, and all other actors with another.
+#[derive(Clone, Copy)]
+pub struct PrioritySpawner<N: ActorSpawner, H: HighPrioritySpawner> {
+    normal: N,
+    high: H,
+}
+
+impl<N: ActorSpawner, H: HighPrioritySpawner> PrioritySpawner<N, H> {
+    pub fn new(normal: N, high: H) -> Self {
+        Self { normal, high }
+    }
+}
+
+impl<N: ActorSpawner, H: HighPrioritySpawner> ActorSpawner for PrioritySpawner<N, H> {
+    fn start<A: Actor>(&self, actor: &'static ActorContext<'static, A>) -> Result<(), SpawnError> {
+        A::Executor::start(self, actor)
+    }
+}
+
+/// Decides which spawner of a `PrioritySpawner` starts an actor, see `Actor::Executor`.
+pub trait ExecutorPriority<A: Actor> {
+    fn start<N: ActorSpawner, H: HighPrioritySpawner>(
+        spawner: &PrioritySpawner<N, H>,
+        actor: &'static ActorContext<'static, A>,
+    ) -> Result<(), SpawnError>;
+}
+
+/// Runs the actor on the executor mounting it.
+pub struct NormalPriority;
+
+impl<A: Actor> ExecutorPriority<A> for NormalPriority {
+    fn start<N: ActorSpawner, H: HighPrioritySpawner>(
+        spawner: &PrioritySpawner<N, H>,
+        actor: &'static ActorContext<'static, A>,
+    ) -> Result<(), SpawnError> {
+        spawner.normal.start(actor)
+    }
+}
+
+/// Runs the actor on the high priority executor, only available to actors that are `Send`
+/// along with their messages.
+pub struct HighPriority;
+
+impl<A: Actor + Send> ExecutorPriority<A> for HighPriority
+where
+    A::Message<'static>: Send,
+{
+    fn start<N: ActorSpawner, H: HighPrioritySpawner>(
+        spawner: &PrioritySpawner<N, H>,
+        actor: &'static ActorContext<'static, A>,
+    ) -> Result<(), SpawnError> {
+        spawner.high.start_high(actor)
+    }
+}
+
+/// Decides how many messages an actor handles at the same time, see `Actor::Concurrency`.
+pub trait Concurrency<A: Actor> {
+    /// Whether messages are handled with shared access to the actor.
+    const SHARED: bool;
+
+    /// Max number of messages handled with shared access at the same time.
+    type MaxInFlight: Capacity;
+
+    /// The future handling a message with shared access to the actor.
+    type HandlerFuture<'a>: Future<Output = A::Response>
+    where
+        A: 'a;
+
+    /// Handle a message with shared access to the actor, only called if `SHARED` is true.
+    fn on_shared_message<'m>(actor: Pin<&'m A>, message: A::Message<'m>)
+        -> Self::HandlerFuture<'m>;
+}
+
+/// Handles one message at a time, with exclusive access to the actor.
+pub struct Exclusive;
+
+impl<A: Actor> Concurrency<A> for Exclusive {
+    const SHARED: bool = false;
+
+    type MaxInFlight = Slots<0>;
+    type HandlerFuture<'a> = core::future::Pending<A::Response>;
+
+    fn on_shared_message<'m>(_: Pin<&'m A>, _: A::Message<'m>) -> Self::HandlerFuture<'m> {
+        unreachable!("messages are handled with exclusive access")
+    }
+}
+
+/// Handles up to `N` messages at the same time with shared access to the actor, only
+/// available to actors implementing `ConcurrentActor`.
+pub struct Shared<const N: usize>;
+
+impl<A: ConcurrentActor, const N: usize> Concurrency<A> for Shared<N> {
+    const SHARED: bool = true;
+
+    type MaxInFlight = Slots<N>;
+    type HandlerFuture<'a> = A::OnSharedMessageFuture<'a>;
+
+    fn on_shared_message<'m>(
+        actor: Pin<&'m A>,
+        message: A::Message<'m>,
+    ) -> Self::HandlerFuture<'m> {
+        actor.on_shared_message(message)
+    }
+}
+
+enum ActorState<'a, A: Actor + 'static>
 where
     A: Actor + 'static,
-    N: ArrayLength<ActorMessage<'a, A>>,
 {
     Idle,
     Start(A::OnStartFuture<'a>),
     Process,
-    Receive(ChannelReceive<'a, 'a, ActorMessage<'a, A>, N>),
-    Request(A::OnMessageFuture<'a>, *const SignalSlot<A::Response>),
+    Request(A::OnMessageFuture<'a>),
     Notify(A::OnMessageFuture<'a>),
 }
 
+/// A message being handled concurrently with others by an actor, along with the
+/// signal slot its response is delivered to, if it is a request.
+pub struct InFlight<'a, A: Actor + 'a> {
+    future: <A::Concurrency as Concurrency<A>>::HandlerFuture<'a>,
+    signal: Option<*const SignalSlot<A::Response>>,
+    started: HandlerStart,
+}
+
 pub struct ActorFuture<'a, A>
 where
     A: Actor + 'static,
@@ -211,6 +833,14 @@ where
     context: &'a ActorContext<'a, A>,
 }
 
+// Safety: The actor and its messages are `Send`, and only the task polling the future
+// touches the message processing state. Everything else in the context that is shared
+// with other tasks is accessed through atomics or critical sections.
+unsafe impl<A: Actor + Send + 'static> Send for ActorFuture<'static, A> where
+    A::Message<'static>: Send
+{
+}
+
 impl<'a, A> Future for ActorFuture<'a, A>
 where
     A: Actor + 'static,
@@ -221,21 +851,41 @@ where
     }
 }
 
-/// A context for an actor, providing signal and message queue. The QLEN parameter
-/// is a const generic parameter, and needs to be at least 2 in order for the underlying
-/// heapless queue to work. (Due to missing const generic expressions)
+// Lifecycle states of an actor context
+const RUNNING: u8 = 0;
+const RESTARTING: u8 = 1;
+const STOPPING: u8 = 2;
+const STOPPED: u8 = 3;
+// Not yet mounted, or shut down since, so there is no task to stop
+const UNMOUNTED: u8 = 4;
+
+/// A context for an actor, providing signal and message queue. The message queues and
+/// the signal slots for pending requests are sized independently, by the
+/// `MessageQueueSize`, `PriorityQueueSize` and `PendingRequests` of the actor.
 #[rustfmt::skip]
 pub struct ActorContext<'a, A>
 where
     A: Actor + 'static,
 {
     task: Task<ActorFuture<'static, A>>,
-    state: RefCell<Option<ActorState<'a, A, A::MessageQueueSize<'a>>>>,
+    state: RefCell<Option<ActorState<'a, A>>>,
     actor: UnsafeCell<A>,
-    channel: MessageChannel<'a, ActorMessage<'a, A>, A::MessageQueueSize<'a>>,
-    // NOTE: This wastes an extra signal because heapless requires at least 2 slots and
-    // const generic expressions doesn't work in this case.
-    signals: UnsafeCell<GenericArray<SignalSlot<A::Response>, A::MessageQueueSize<'a>>>,
+    channel: MessageChannel<'a, ActorMessage<'a, A>, A::MessageQueueSize>,
+    priority_channel: MessageChannel<'a, ActorMessage<'a, A>, A::PriorityQueueSize>,
+    signals: <A::PendingRequests as Capacity>::Array<SignalSlot<A::Response>>,
+    // The signal of the request being handled, unless its response has been deferred.
+    request: Cell<Option<*const SignalSlot<A::Response>>>,
+    in_flight: UnsafeCell<<<A::Concurrency as Concurrency<A>>::MaxInFlight as Capacity>::Array<Option<InFlight<'a, A>>>>,
+    stash: RingBuffer<ActorMessage<'a, A>, A::StashSize>,
+    // Number of stashed messages to handle before receiving new ones
+    replay: Cell<usize>,
+    // Number of notifications from interrupt context dropped because the queue was full
+    isr_drops: AtomicU32,
+    lifecycle: AtomicU8,
+    waker: AtomicWaker,
+    failures: Signal<()>,
+    stopped: Signal<()>,
+    metrics: MetricsRecorder,
 }
 
 impl<'a, A> ActorContext<'a, A>
@@ -248,37 +898,309 @@ where
             state: RefCell::new(Some(ActorState::Idle)),
             actor: UnsafeCell::new(actor),
             channel: MessageChannel::new(),
-            signals: UnsafeCell::new(Default::default()),
+            priority_channel: MessageChannel::new(),
+            signals: <A::PendingRequests as Capacity>::array(SignalSlot::default),
+            request: Cell::new(None),
+            in_flight: UnsafeCell::new(
+                <<A::Concurrency as Concurrency<A>>::MaxInFlight as Capacity>::array(|| None),
+            ),
+            stash: RingBuffer::new(),
+            replay: Cell::new(0),
+            isr_drops: AtomicU32::new(0),
+            lifecycle: AtomicU8::new(UNMOUNTED),
+            waker: AtomicWaker::new(),
+            failures: Signal::new(),
+            stopped: Signal::new(),
+            metrics: MetricsRecorder::new(),
         }
     }
 
-    /// Acquire a signal slot if there are any free available
-    fn acquire_signal(&self) -> Result<&SignalSlot<A::Response>, SignalError> {
-        let signals = unsafe { &mut *self.signals.get() };
-        let mut i = 0;
-        while i < signals.len() {
-            if signals[i].acquire() {
-                return Ok(&signals[i]);
-            }
-            i += 1;
+    /// Get a snapshot of the runtime metrics of this actor.
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> ActorMetrics {
+        self.metrics.snapshot()
+    }
+
+    /// Report a fatal error to the supervisor of this actor.
+    pub fn fail(&self) {
+        self.failures.signal(());
+    }
+
+    /// Wait for the actor to report a fatal error.
+    pub(crate) async fn wait_failure(&self) {
+        self.failures.wait().await
+    }
+
+    /// Request the actor to be restarted the next time its task is polled.
+    pub fn restart(&self) {
+        if self
+            .lifecycle
+            .compare_exchange(RUNNING, RESTARTING, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+        {
+            self.waker.wake();
         }
-        Err(SignalError::NoAvailableSignal)
     }
 
-    /// Perform a request to this actor. The result from processing the request will be provided when the future completes.
-    /// The returned future _must_ be awaited before dropped. If it is not
-    /// awaited, it will panic.
-    fn request<'m>(&'a self, message: A::Message<'m>) -> Result<RequestFuture<'a, A>, ActorError>
-    where
-        'a: 'm,
-    {
-        let signal = self.acquire_signal()?;
-        // Safety: This is OK because A::Message is Sized.
-        let message = unsafe { core::mem::transmute_copy::<_, A::Message<'a>>(&message) };
-        let message = ActorMessage::Request(message, signal);
-        self.channel.send(message)?;
-        let sig = SignalFuture::new(signal);
-        Ok(RequestFuture::new(sig))
+    /// Request the actor to be stopped the next time its task is polled. Does nothing if
+    /// the actor has not been mounted.
+    pub fn stop(&self) {
+        match self.lifecycle.swap(STOPPING, Ordering::AcqRel) {
+            previous @ (STOPPED | UNMOUNTED) => self.lifecycle.store(previous, Ordering::Release),
+            _ => self.waker.wake(),
+        }
+    }
+
+    /// Stop the actor and wait for its task to exit, dropping any messages left in its
+    /// queue. Requests that were not processed are abandoned. The actor may be mounted
+    /// again afterwards.
+    ///
+    /// Returns immediately if the actor has not been mounted.
+    pub async fn shutdown(&'a self) {
+        match self.lifecycle.load(Ordering::Acquire) {
+            UNMOUNTED => return,
+            STOPPED => {}
+            _ => {
+                self.stop();
+                self.stopped.wait().await;
+            }
+        }
+        while let Some(message) = self.try_dequeue() {
+            if let ActorMessage::Request(_, signal) = message {
+                unsafe { &*signal }.abandon();
+            }
+        }
+        critical_section::with(|_| self.replay.set(0));
+        while let Some(message) = critical_section::with(|_| self.stash.dequeue()) {
+            if let ActorMessage::Request(_, signal) = message {
+                unsafe { &*signal }.abandon();
+            }
+        }
+        self.stopped.reset();
+        self.failures.reset();
+        self.lifecycle.store(UNMOUNTED, Ordering::Release);
+    }
+
+    /// Drop any message processing in progress and let the actor know it has stopped.
+    fn halt(&self) {
+        drop(self.state.borrow_mut().replace(ActorState::Idle));
+        if let Some(signal) = self.take_request() {
+            unsafe { &*signal }.abandon();
+        }
+        for flight in unsafe { &mut *self.in_flight.get() }.as_mut().iter_mut() {
+            if let Some(InFlight { future, signal, .. }) = flight.take() {
+                drop(future);
+                if let Some(signal) = signal {
+                    unsafe { &*signal }.abandon();
+                }
+            }
+        }
+        self.metrics.handler_aborted();
+        // Stashed messages are handled again once the actor has started
+        self.unstash();
+        unsafe { &mut *self.actor.get() }.on_stop();
+    }
+
+    fn is_concurrent() -> bool {
+        <A::Concurrency as Concurrency<A>>::SHARED
+    }
+
+    /// Poll the messages being handled concurrently, starting to handle new messages
+    /// whenever there is room for them.
+    fn poll_concurrent(&'a self, cx: &mut Context<'_>) -> Poll<()> {
+        let in_flight = unsafe { &mut *self.in_flight.get() }.as_mut();
+        loop {
+            let mut progress = false;
+            for slot in in_flight.iter_mut() {
+                if slot.is_none() {
+                    if let Poll::Ready(message) = self.poll_receive(cx) {
+                        progress = true;
+                        let (message, signal) = match message {
+                            ActorMessage::Request(_, signal)
+                                if unsafe { &*signal }.release_if_cancelled() =>
+                            {
+                                continue;
+                            }
+                            ActorMessage::Request(message, signal) => (message, Some(signal)),
+                            ActorMessage::Notify(message) => (message, None),
+                        };
+                        let started = self.metrics.shared_handler_started();
+                        trace::started(self);
+                        let actor = unsafe { Pin::new_unchecked(&*self.actor.get()) };
+                        let future = A::Concurrency::on_shared_message(actor, message);
+                        slot.replace(InFlight {
+                            future,
+                            signal,
+                            started,
+                        });
+                    }
+                }
+
+                if let Some(flight) = slot.as_mut() {
+                    // Make the request available to `defer` while its handler runs
+                    self.set_request(flight.signal);
+                    let r = unsafe { Pin::new_unchecked(&mut flight.future) }.poll(cx);
+                    let signal = self.take_request();
+                    flight.signal = signal;
+                    if let Poll::Ready(value) = r {
+                        progress = true;
+                        self.metrics.shared_handler_finished(flight.started);
+                        trace::finished(self);
+                        slot.take();
+                        if let Some(signal) = signal {
+                            unsafe { &*signal }.signal(value);
+                        }
+                    }
+                }
+            }
+            if !progress {
+                return Poll::Pending;
+            }
+        }
+    }
+
+    // The request, stash and replay state is shared with addresses, which may be used
+    // from other executors and interrupts, and is only accessed in critical sections.
+
+    fn set_request(&self, signal: Option<*const SignalSlot<A::Response>>) {
+        critical_section::with(|_| self.request.set(signal))
+    }
+
+    fn take_request(&self) -> Option<*const SignalSlot<A::Response>> {
+        critical_section::with(|_| self.request.take())
+    }
+
+    /// Deliver the response to the request being handled, unless it has been deferred.
+    fn respond(&self, value: A::Response) {
+        if let Some(signal) = self.take_request() {
+            unsafe { &*signal }.signal(value);
+        }
+    }
+
+    /// Returns true if `actor` is the actor held by this context.
+    fn is_actor(&self, actor: &A) -> bool {
+        core::ptr::eq(actor, self.actor.get())
+    }
+
+    /// Take over responding to the request being handled, if any.
+    fn defer(&'a self) -> Option<Responder<'a, A::Response>> {
+        self.take_request()
+            .map(|signal| Responder::new(unsafe { &*signal }))
+    }
+
+    /// Acquire a signal slot if there are any free available
+    fn acquire_signal(&self) -> Result<&SignalSlot<A::Response>, SignalError> {
+        let signals = self.signals.as_ref();
+        let mut i = 0;
+        while i < signals.len() {
+            if signals[i].acquire() {
+                return Ok(&signals[i]);
+            }
+            i += 1;
+        }
+        Err(SignalError::NoAvailableSignal)
+    }
+
+    /// Poll for a free signal slot, registering for wake up when any slot is released.
+    fn poll_acquire_signal(&self, cx: &mut Context<'_>) -> Poll<&SignalSlot<A::Response>> {
+        let signals = self.signals.as_ref();
+        // Register before trying in order to not miss a release in between
+        for signal in signals.iter() {
+            signal.register_release(cx.waker());
+        }
+        match self.acquire_signal() {
+            Ok(signal) => Poll::Ready(signal),
+            Err(_) => Poll::Pending,
+        }
+    }
+
+    /// Perform a request to this actor. The result from processing the request will be provided when the future completes.
+    /// The returned future _must_ be awaited before dropped. If it is not
+    /// awaited, it will panic.
+    fn request<'m>(&'a self, message: A::Message<'m>) -> Result<RequestFuture<'a, A>, ActorError>
+    where
+        'a: 'm,
+    {
+        let signal = self.enqueue_request(message)?;
+        let sig = SignalFuture::new(signal);
+        Ok(RequestFuture::new(sig))
+    }
+
+    /// Perform a request to this actor that gives up after the provided timeout. The returned
+    /// future may be dropped at any time, which cancels the request.
+    fn request_with_timeout(
+        &'a self,
+        message: A::Message<'a>,
+        timeout: Duration,
+    ) -> Result<RequestTimeoutFuture<'a, A>, ActorError> {
+        let signal = self.enqueue_request(message)?;
+        let sig = SignalFuture::new(signal);
+        Ok(RequestTimeoutFuture::new(sig, timeout))
+    }
+
+    /// Enqueue a request message, returning the signal slot the response will be delivered to.
+    fn enqueue_request<'m>(
+        &'a self,
+        message: A::Message<'m>,
+    ) -> Result<&'a SignalSlot<A::Response>, ActorError>
+    where
+        'a: 'm,
+    {
+        let signal = self.acquire_signal().map_err(|e| {
+            self.metrics.request_dropped();
+            e
+        })?;
+        // Safety: This is OK because A::Message is Sized.
+        let msg = unsafe { core::mem::transmute_copy::<_, A::Message<'a>>(&message) };
+        core::mem::forget(message);
+        let message = ActorMessage::Request(msg, signal);
+        if let Err(e) = self.send(message) {
+            signal.release();
+            self.metrics.request_dropped();
+            return Err(e.into());
+        }
+        trace::request(self);
+        Ok(signal)
+    }
+
+    /// Perform a request to this actor, waiting for a free signal slot and queue space.
+    /// Once the message has been enqueued, the returned future _must_ be awaited before
+    /// dropped. If it is not awaited, it will panic.
+    async fn request_async<'m>(&'a self, message: A::Message<'m>) -> Result<A::Response, ActorError>
+    where
+        'a: 'm,
+    {
+        // Safety: This is OK because A::Message is Sized.
+        let msg = unsafe { core::mem::transmute_copy::<_, A::Message<'a>>(&message) };
+        core::mem::forget(message);
+        let signal = self.enqueue_request_async(msg).await;
+        RequestFuture::new(SignalFuture::new(signal)).await
+    }
+
+    /// Perform a request to this actor, waiting for a free signal slot and queue space. The
+    /// returned future may be dropped at any time, which cancels the request.
+    async fn request_cancellable(
+        &'a self,
+        message: A::Message<'a>,
+    ) -> Result<A::Response, ActorError> {
+        let signal = self.enqueue_request_async(message).await;
+        SignalFuture::new(signal).await.ok_or(ActorError::Abandoned)
+    }
+
+    /// Wait for a free signal slot and queue space, and enqueue a request message, returning
+    /// the signal slot the response will be delivered to. If the returned future is dropped
+    /// before the message is enqueued, the message is dropped and the slot released.
+    async fn enqueue_request_async(
+        &'a self,
+        message: A::Message<'a>,
+    ) -> &'a SignalSlot<A::Response> {
+        let signal = poll_fn(|cx| self.poll_acquire_signal(cx)).await;
+        let acquired = AcquiredSignal(signal);
+        self.send_async(ActorMessage::Request(message, signal))
+            .await;
+        core::mem::forget(acquired);
+        trace::request(self);
+        signal
     }
 
     /// Perform a notification on this actor. The returned future _must_ be awaited before dropped. If it is not
@@ -289,8 +1211,274 @@ where
     {
         let message = ActorMessage::Notify(message);
 
-        let sent = self.channel.send(message)?;
-        Ok(sent)
+        match self.send(message) {
+            Ok(sent) => {
+                trace::notify(self);
+                Ok(sent)
+            }
+            Err(e) => {
+                self.metrics.notify_dropped();
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Perform a notification on this actor, returning the message if the queue is full.
+    pub(crate) fn try_notify(&'a self, message: A::Message<'a>) -> Result<(), A::Message<'a>> {
+        critical_section::with(|_| {
+            let full = match A::priority(&message) {
+                Priority::High => self.priority_channel.is_full(),
+                Priority::Normal => self.channel.is_full(),
+            };
+            if full {
+                Err(message)
+            } else {
+                self.notify(message).ok().unwrap();
+                Ok(())
+            }
+        })
+    }
+
+    /// Perform a notification on this actor, waiting for space in the queue if it is full.
+    async fn notify_async(&'a self, message: A::Message<'a>) {
+        self.send_async(ActorMessage::Notify(message)).await;
+        trace::notify(self);
+    }
+
+    /// Perform a notification on this actor from interrupt context, applying the overflow
+    /// policy of the actor if the queue is full.
+    fn notify_from_isr(&'a self, message: A::Message<'a>) -> Result<(), ActorError> {
+        let message = ActorMessage::Notify(message);
+        match A::OVERFLOW_POLICY {
+            OverflowPolicy::DropNewest => {
+                if let Err(e) = self.send(message) {
+                    self.isr_drops.fetch_add(1, Ordering::Relaxed);
+                    self.metrics.notify_dropped();
+                    return Err(e.into());
+                }
+            }
+            OverflowPolicy::DropOldest => match self.send_displacing(message) {
+                Ok(None) => {}
+                Ok(Some(_)) => {
+                    self.isr_drops.fetch_add(1, Ordering::Relaxed);
+                    self.metrics.notify_dropped();
+                }
+                Err(_) => {
+                    self.isr_drops.fetch_add(1, Ordering::Relaxed);
+                    self.metrics.notify_dropped();
+                    return Err(ChannelError::ChannelFull.into());
+                }
+            },
+        }
+        trace::notify(self);
+        Ok(())
+    }
+
+    fn is_high_priority(message: &ActorMessage<'a, A>) -> bool {
+        let message = match message {
+            ActorMessage::Request(message, _) => message,
+            ActorMessage::Notify(message) => message,
+        };
+        A::priority(message) == Priority::High
+    }
+
+    // The queue depth is counted in the same critical section as the message is enqueued
+    // or dequeued in, so that it never drifts from the actual number of queued messages.
+
+    /// Enqueue a message in the queue matching its priority.
+    fn send(&self, message: ActorMessage<'a, A>) -> Result<(), ChannelError> {
+        critical_section::with(|_| {
+            let result = if Self::is_high_priority(&message) {
+                self.priority_channel.send(message)
+            } else {
+                self.channel.send(message)
+            };
+            result.map(|_| self.metrics.enqueued())
+        })
+    }
+
+    /// Enqueue a message in the queue matching its priority, dropping the oldest
+    /// notification in that queue if it is full. Requests are never dropped, so the message
+    /// is returned if the queue only holds requests.
+    fn send_displacing(
+        &self,
+        message: ActorMessage<'a, A>,
+    ) -> Result<Option<ActorMessage<'a, A>>, ActorMessage<'a, A>> {
+        let displaceable = |queued: &ActorMessage<'a, A>| matches!(queued, ActorMessage::Notify(_));
+        critical_section::with(|_| {
+            let result = if Self::is_high_priority(&message) {
+                self.priority_channel.send_displacing(message, displaceable)
+            } else {
+                self.channel.send_displacing(message, displaceable)
+            };
+            if let Ok(displaced) = &result {
+                if displaced.is_some() {
+                    self.metrics.dequeued();
+                }
+                self.metrics.enqueued();
+            }
+            result
+        })
+    }
+
+    /// Enqueue a message in the queue matching its priority, waiting for space if it is full.
+    async fn send_async(&'a self, message: ActorMessage<'a, A>) {
+        let high = Self::is_high_priority(&message);
+        let mut message = Some(message);
+        poll_fn(|cx| {
+            let enqueue = || {
+                self.metrics.enqueued();
+                message.take().unwrap()
+            };
+            if high {
+                self.priority_channel.poll_send_with(cx, enqueue)
+            } else {
+                self.channel.poll_send_with(cx, enqueue)
+            }
+        })
+        .await
+    }
+
+    /// Poll for a message from one of the queues.
+    fn poll_dequeue<C: Capacity>(
+        &self,
+        channel: &MessageChannel<'a, ActorMessage<'a, A>, C>,
+        cx: &mut Context<'_>,
+    ) -> Poll<ActorMessage<'a, A>> {
+        critical_section::with(|_| {
+            let message = channel.poll_receive(cx);
+            if message.is_ready() {
+                self.metrics.dequeued();
+            }
+            message
+        })
+    }
+
+    /// Take the next message from the queues, if any, high priority messages first.
+    fn try_dequeue(&self) -> Option<ActorMessage<'a, A>> {
+        critical_section::with(|_| {
+            let message = self
+                .priority_channel
+                .try_receive()
+                .or_else(|_| self.channel.try_receive())
+                .ok();
+            if message.is_some() {
+                self.metrics.dequeued();
+            }
+            message
+        })
+    }
+
+    /// Put the message being handled in the stash.
+    fn stash<'m>(&'a self, message: A::Message<'m>) -> Result<(), A::Message<'m>>
+    where
+        'a: 'm,
+    {
+        critical_section::with(|_| {
+            if self.stash.is_full() {
+                return Err(message);
+            }
+            // Safety: The message was enqueued with the lifetime 'a, see `enqueue_request`.
+            let msg = unsafe { core::mem::transmute_copy::<_, A::Message<'a>>(&message) };
+            core::mem::forget(message);
+            let message = match self.request.take() {
+                Some(signal) => ActorMessage::Request(msg, signal),
+                None => ActorMessage::Notify(msg),
+            };
+            let _ = self.stash.enqueue(message);
+            Ok(())
+        })
+    }
+
+    /// Handle every message currently in the stash before receiving new messages.
+    fn unstash(&self) {
+        critical_section::with(|_| self.replay.set(self.stash.len()));
+        self.waker.wake();
+    }
+
+    /// Poll for the next message, taking stashed messages being replayed first, and
+    /// then messages from the high priority queue.
+    fn poll_receive(&self, cx: &mut Context<'_>) -> Poll<ActorMessage<'a, A>> {
+        let replayed = critical_section::with(|_| {
+            let replay = self.replay.get();
+            if replay > 0 {
+                self.replay.set(replay - 1);
+                self.stash.dequeue()
+            } else {
+                None
+            }
+        });
+        if let Some(message) = replayed {
+            return Poll::Ready(message);
+        }
+        if let Poll::Ready(message) = self.poll_dequeue(&self.priority_channel, cx) {
+            return Poll::Ready(message);
+        }
+        self.poll_dequeue(&self.channel, cx)
+    }
+
+    /// Notify the actor behind a type-erased context pointer, used by `Recipient`.
+    unsafe fn notify_erased(context: *const (), message: A::Message<'a>) -> Result<(), ActorError> {
+        let context = &*(context as *const Self);
+        context.notify(message)
+    }
+
+    /// Poll for space in the queue of the actor behind a type-erased context pointer,
+    /// notifying it with the message once there is. Used by `Recipient`.
+    unsafe fn poll_notify_erased(
+        context: *const (),
+        message: &mut Option<A::Message<'a>>,
+        cx: &mut Context<'_>,
+    ) -> Poll<()> {
+        let context = &*(context as *const Self);
+        let priority = A::priority(message.as_ref().unwrap());
+        let notify = || {
+            context.metrics.enqueued();
+            ActorMessage::Notify(message.take().unwrap())
+        };
+        let result = match priority {
+            Priority::High => context.priority_channel.poll_send_with(cx, notify),
+            Priority::Normal => context.channel.poll_send_with(cx, notify),
+        };
+        if result.is_ready() {
+            trace::notify(context);
+        }
+        result
+    }
+
+    /// Notify the actor behind a type-erased context pointer with a message converted by
+    /// `F`, used by `Recipient`.
+    unsafe fn notify_adapted_erased<M, F: MessageAdapter<M, A::Message<'a>>>(
+        context: *const (),
+        message: M,
+    ) -> Result<(), ActorError> {
+        match F::adapt(message) {
+            Some(message) => Self::notify_erased(context, message),
+            None => Ok(()),
+        }
+    }
+
+    /// Poll for space in the queue of the actor behind a type-erased context pointer,
+    /// notifying it with a message converted by `F` once there is. Used by `Recipient`.
+    unsafe fn poll_notify_adapted_erased<M: Clone, F: MessageAdapter<M, A::Message<'a>>>(
+        context: *const (),
+        message: &mut Option<M>,
+        cx: &mut Context<'_>,
+    ) -> Poll<()> {
+        // The original message is kept until the converted one is enqueued, as the
+        // converted message is dropped if the queue is full.
+        let mut adapted = match message.clone().and_then(F::adapt) {
+            Some(adapted) => Some(adapted),
+            None => {
+                message.take();
+                return Poll::Ready(());
+            }
+        };
+        let result = Self::poll_notify_erased(context, &mut adapted, cx);
+        if result.is_ready() {
+            message.take();
+        }
+        result
     }
 
     /// Mount the underloying actor and initialize the channel.
@@ -302,6 +1490,8 @@ where
         let address = Address::new(self);
         unsafe { &mut *self.actor.get() }.on_mount(address, config);
         self.channel.initialize();
+        self.priority_channel.initialize();
+        self.lifecycle.store(RUNNING, Ordering::Release);
 
         spawner.start(self).unwrap();
         address
@@ -316,7 +1506,32 @@ where
 
     // Poll this actor to make progress
     pub(crate) fn poll(&'a self, cx: &mut Context<'_>) -> Poll<()> {
+        // Messages enqueued while polling are sent by this actor
+        let _scope = trace::enter(self);
+        self.waker.register(cx.waker());
         loop {
+            match self.lifecycle.load(Ordering::Acquire) {
+                RESTARTING => {
+                    self.halt();
+                    unsafe { &mut *self.actor.get() }.on_restart();
+                    // A stop requested in the meantime takes precedence
+                    let _ = self.lifecycle.compare_exchange(
+                        RESTARTING,
+                        RUNNING,
+                        Ordering::AcqRel,
+                        Ordering::Acquire,
+                    );
+                    continue;
+                }
+                STOPPING => {
+                    self.halt();
+                    self.lifecycle.store(STOPPED, Ordering::Release);
+                    self.stopped.signal(());
+                    return Poll::Ready(());
+                }
+                STOPPED => return Poll::Ready(()),
+                _ => {}
+            }
             let mut state = self.state.borrow_mut();
             match state.as_mut().unwrap() {
                 ActorState::Idle => {
@@ -331,37 +1546,52 @@ where
                         state.replace(ActorState::Process);
                     }
                 }
-                ActorState::Process => {
-                    state.replace(ActorState::Receive(self.channel.receive()));
+                ActorState::Process if Self::is_concurrent() => {
+                    return self.poll_concurrent(cx);
                 }
-                ActorState::Receive(fut) => {
-                    let r = unsafe { Pin::new_unchecked(fut) }.poll(cx);
+                ActorState::Process => {
+                    let r = self.poll_receive(cx);
                     match r {
                         Poll::Pending => {
                             return Poll::Pending;
                         }
-                        Poll::Ready(message) => match message {
-                            ActorMessage::Request(message, signal) => {
-                                let fut = unsafe { Pin::new_unchecked(&mut *self.actor.get()) }
-                                    .on_message(message);
-                                state.replace(ActorState::Request(fut, signal));
+                        Poll::Ready(message) => {
+                            match message {
+                                ActorMessage::Request(_, signal)
+                                    if unsafe { &*signal }.release_if_cancelled() =>
+                                {
+                                    // The requester gave up before the request was picked up
+                                    state.replace(ActorState::Process);
+                                }
+                                ActorMessage::Request(message, signal) => {
+                                    self.metrics.handler_started();
+                                    trace::started(self);
+                                    self.set_request(Some(signal));
+                                    let fut = unsafe { Pin::new_unchecked(&mut *self.actor.get()) }
+                                        .on_message(message);
+                                    state.replace(ActorState::Request(fut));
+                                }
+                                ActorMessage::Notify(message) => {
+                                    self.metrics.handler_started();
+                                    trace::started(self);
+                                    let fut = unsafe { Pin::new_unchecked(&mut *self.actor.get()) }
+                                        .on_message(message);
+                                    state.replace(ActorState::Notify(fut));
+                                }
                             }
-                            ActorMessage::Notify(message) => {
-                                let fut = unsafe { Pin::new_unchecked(&mut *self.actor.get()) }
-                                    .on_message(message);
-                                state.replace(ActorState::Notify(fut));
-                            }
-                        },
+                        }
                     }
                 }
-                ActorState::Request(fut, signal) => {
+                ActorState::Request(fut) => {
                     let r = unsafe { Pin::new_unchecked(fut) }.poll(cx);
                     match r {
                         Poll::Pending => {
                             return Poll::Pending;
                         }
                         Poll::Ready(value) => {
-                            unsafe { &**signal }.signal(value);
+                            self.metrics.handler_finished();
+                            trace::finished(self);
+                            self.respond(value);
                             state.replace(ActorState::Process);
                         }
                     }
@@ -371,6 +1601,8 @@ where
                     if r.is_pending() {
                         return Poll::Pending;
                     } else {
+                        self.metrics.handler_finished();
+                        trace::finished(self);
                         state.replace(ActorState::Process);
                     }
                 }
@@ -382,15 +1614,42 @@ where
     pub(crate) async fn process(&'a self) {
         // crate::log_stack!();
         let actor = unsafe { Pin::new_unchecked(&mut *self.actor.get()) };
-        match self.channel.receive().await {
+        let message = poll_fn(|cx| self.poll_receive(cx)).await;
+        match message {
+            ActorMessage::Request(_, signal) if unsafe { &*signal }.release_if_cancelled() => {}
+            ActorMessage::Request(message, signal) if Self::is_concurrent() => {
+                let started = self.metrics.shared_handler_started();
+                trace::started(self);
+                self.set_request(Some(signal));
+                let value = A::Concurrency::on_shared_message(actor.into_ref(), message).await;
+                self.metrics.shared_handler_finished(started);
+                trace::finished(self);
+                self.respond(value);
+            }
             ActorMessage::Request(message, signal) => {
                 // crate::log_stack!();
+                self.metrics.handler_started();
+                trace::started(self);
+                self.set_request(Some(signal));
                 let value = actor.on_message(message).await;
-                unsafe { &*signal }.signal(value);
+                self.metrics.handler_finished();
+                trace::finished(self);
+                self.respond(value);
+            }
+            ActorMessage::Notify(message) if Self::is_concurrent() => {
+                let started = self.metrics.shared_handler_started();
+                trace::started(self);
+                A::Concurrency::on_shared_message(actor.into_ref(), message).await;
+                self.metrics.shared_handler_finished(started);
+                trace::finished(self);
             }
             ActorMessage::Notify(message) => {
                 // crate::log_stack!();
+                self.metrics.handler_started();
+                trace::started(self);
                 actor.on_message(message).await;
+                self.metrics.handler_finished();
+                trace::finished(self);
             }
         }
     }
@@ -403,6 +1662,15 @@ where
         }
     }
 }
+/// Releases a signal slot acquired for a request that was never enqueued.
+struct AcquiredSignal<'s, T: Send>(&'s SignalSlot<T>);
+
+impl<T: Send> Drop for AcquiredSignal<'_, T> {
+    fn drop(&mut self) {
+        self.0.release();
+    }
+}
+
 pub struct RequestFuture<'a, A: Actor + 'a> {
     signal: SignalFuture<'a, A::Response>,
     bomb: Option<DropBomb>,
@@ -418,16 +1686,55 @@ impl<'a, A: Actor + 'a> RequestFuture<'a, A> {
 }
 
 impl<'a, A: Actor + 'a> Future for RequestFuture<'a, A> {
-    type Output = A::Response;
+    /// The response, or `ActorError::Abandoned` if the actor was restarted or stopped
+    /// before it responded.
+    type Output = Result<A::Response, ActorError>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let result = Pin::new(&mut self.signal).poll(cx);
-        if result.is_ready() {
+        if let Poll::Ready(value) = result {
             self.bomb.take().unwrap().defuse();
-            self.signal.release();
-            return result;
+            Poll::Ready(value.ok_or(ActorError::Abandoned))
         } else {
-            return Poll::Pending;
+            Poll::Pending
+        }
+    }
+}
+
+/// A request future that resolves to an error if no response arrives within the timeout.
+///
+/// Dropping this future cancels the request, and the signal slot is reclaimed once the
+/// actor is done with it.
+pub struct RequestTimeoutFuture<'a, A: Actor + 'a> {
+    signal: SignalFuture<'a, A::Response>,
+    timeout: Duration,
+    timer: Option<Timer>,
+}
+
+impl<'a, A: Actor + 'a> RequestTimeoutFuture<'a, A> {
+    pub fn new(signal: SignalFuture<'a, A::Response>, timeout: Duration) -> Self {
+        Self {
+            signal,
+            timeout,
+            timer: None,
+        }
+    }
+}
+
+impl<'a, A: Actor + 'a> Future for RequestTimeoutFuture<'a, A> {
+    type Output = Result<A::Response, ActorError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Poll::Ready(value) = Pin::new(&mut self.signal).poll(cx) {
+            return Poll::Ready(value.ok_or(ActorError::Abandoned));
+        }
+
+        let timeout = self.timeout;
+        let timer = self.timer.get_or_insert_with(|| Timer::after(timeout));
+        if Pin::new(timer).poll(cx).is_ready() {
+            Poll::Ready(Err(ActorError::Timeout))
+        } else {
+            Poll::Pending
         }
     }
 }
@@ -453,6 +1760,169 @@ pub enum ActorMessage<'m, A: Actor + 'm> {
 mod tests {
     use super::*;
     use crate::testutil::*;
+    use atomic_polyfill::AtomicUsize;
+    use core::future::{ready, Ready};
+
+    /// Defers `Wait` requests until they are released by another message
+    struct Deferring {
+        address: Option<Address<'static, Deferring>>,
+        pending: Option<Responder<'static, u32>>,
+    }
+
+    enum DeferMessage {
+        Wait,
+        Release(u32),
+        Abandon,
+    }
+
+    impl Actor for Deferring {
+        type MessageQueueSize = Slots<4>;
+        type Message<'m> = DeferMessage;
+        type Response = u32;
+        type OnStartFuture<'m> = ImmediateFuture;
+        type OnMessageFuture<'m> = Ready<u32>;
+
+        fn on_mount(&mut self, address: Address<'static, Self>, _: Self::Configuration) {
+            self.address.replace(address);
+        }
+
+        fn on_start(self: Pin<&'_ mut Self>) -> Self::OnStartFuture<'_> {
+            ImmediateFuture::new()
+        }
+
+        fn on_message<'m>(
+            self: Pin<&'m mut Self>,
+            message: Self::Message<'m>,
+        ) -> Self::OnMessageFuture<'m> {
+            let this = self.get_mut();
+            match message {
+                DeferMessage::Wait => this.pending = this.address.unwrap().defer(this),
+                DeferMessage::Release(value) => this.pending.take().unwrap().respond(value),
+                DeferMessage::Abandon => drop(this.pending.take()),
+            }
+            ready(0)
+        }
+    }
+
+    /// Handles up to two requests at a time, each completing once its gate is opened
+    struct Gated {
+        gates: &'static [Signal<()>; 2],
+    }
+
+    impl Actor for Gated {
+        type MessageQueueSize = Slots<4>;
+        type Concurrency = Shared<2>;
+        type Message<'m> = usize;
+        type Response = usize;
+        type OnStartFuture<'m> = ImmediateFuture;
+        type OnMessageFuture<'m> = core::future::Pending<usize>;
+
+        fn on_start(self: Pin<&'_ mut Self>) -> Self::OnStartFuture<'_> {
+            ImmediateFuture::new()
+        }
+
+        fn on_message<'m>(self: Pin<&'m mut Self>, _: usize) -> Self::OnMessageFuture<'m> {
+            unreachable!()
+        }
+    }
+
+    impl ConcurrentActor for Gated {
+        type OnSharedMessageFuture<'m> = impl Future<Output = usize> + 'm;
+
+        fn on_shared_message<'m>(
+            self: Pin<&'m Self>,
+            gate: usize,
+        ) -> Self::OnSharedMessageFuture<'m> {
+            async move {
+                self.gates[gate].wait().await;
+                gate
+            }
+        }
+    }
+
+    /// Counts the actors it has been asked to start
+    #[derive(Clone, Copy)]
+    struct CountingSpawner(&'static AtomicUsize);
+
+    impl ActorSpawner for CountingSpawner {
+        fn start<A: Actor>(&self, _: &'static ActorContext<'static, A>) -> Result<(), SpawnError> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    impl HighPrioritySpawner for CountingSpawner {
+        fn start_high<A: Actor + Send>(
+            &self,
+            actor: &'static ActorContext<'static, A>,
+        ) -> Result<(), SpawnError>
+        where
+            A::Message<'static>: Send,
+        {
+            self.start(actor)
+        }
+    }
+
+    /// An actor that asks to be run on a high priority executor
+    struct Urgent;
+
+    impl Actor for Urgent {
+        type Executor = HighPriority;
+
+        type Message<'m> = ();
+        type OnStartFuture<'m> = ImmediateFuture;
+        type OnMessageFuture<'m> = ImmediateFuture;
+
+        fn on_start(self: Pin<&'_ mut Self>) -> Self::OnStartFuture<'_> {
+            ImmediateFuture::new()
+        }
+
+        fn on_message<'m>(self: Pin<&'m mut Self>, _: ()) -> Self::OnMessageFuture<'m> {
+            ImmediateFuture::new()
+        }
+    }
+
+    /// Records the values it is notified with, dropping the oldest when the queue is full
+    struct Latest {
+        received: std::vec::Vec<u32>,
+    }
+
+    impl Actor for Latest {
+        const OVERFLOW_POLICY: OverflowPolicy = OverflowPolicy::DropOldest;
+
+        type MessageQueueSize = Slots<2>;
+        type Message<'m> = u32;
+        type OnStartFuture<'m> = ImmediateFuture;
+        type OnMessageFuture<'m> = ImmediateFuture;
+
+        fn on_start(self: Pin<&'_ mut Self>) -> Self::OnStartFuture<'_> {
+            ImmediateFuture::new()
+        }
+
+        fn on_message<'m>(self: Pin<&'m mut Self>, value: u32) -> Self::OnMessageFuture<'m> {
+            self.get_mut().received.push(value);
+            ImmediateFuture::new()
+        }
+    }
+
+    /// Has room for more messages than requests awaiting a response
+    struct Narrow;
+
+    impl Actor for Narrow {
+        type MessageQueueSize = Slots<2>;
+        type PendingRequests = Slots<1>;
+        type Message<'m> = ();
+        type OnStartFuture<'m> = ImmediateFuture;
+        type OnMessageFuture<'m> = ImmediateFuture;
+
+        fn on_start(self: Pin<&'_ mut Self>) -> Self::OnStartFuture<'_> {
+            ImmediateFuture::new()
+        }
+
+        fn on_message<'m>(self: Pin<&'m mut Self>, _: ()) -> Self::OnMessageFuture<'m> {
+            ImmediateFuture::new()
+        }
+    }
 
     #[test]
     fn test_multiple_notifications() {
@@ -501,4 +1971,450 @@ mod tests {
             step_actor(actor);
         }
     }
+
+    #[test]
+    fn test_request_async_dropped_before_enqueued() {
+        let spawner = TestSpawner::new();
+        let actor = Box::leak(Box::new(ActorContext::new(Narrow)));
+
+        let address = actor.mount((), spawner);
+
+        let waker = futures::task::noop_waker_ref();
+        let mut cx = std::task::Context::from_waker(waker);
+
+        let mut request = address.request(()).unwrap();
+
+        // Waiting for the signal slot, the future may be dropped without panicking
+        let fut = address.request_async(());
+        futures::pin_mut!(fut);
+        assert!(fut.as_mut().poll(&mut cx).is_pending());
+        drop(fut);
+
+        while Pin::new(&mut request).poll(&mut cx).is_pending() {
+            step_actor(actor);
+        }
+        assert!(address.request(()).is_ok());
+    }
+
+    #[test]
+    fn test_request_cancellable_dropped() {
+        let spawner = TestSpawner::new();
+        let actor = Box::leak(Box::new(ActorContext::new(Narrow)));
+
+        let address = actor.mount((), spawner);
+
+        let waker = futures::task::noop_waker_ref();
+        let mut cx = std::task::Context::from_waker(waker);
+
+        // Dropped once enqueued, the slot is reclaimed when the actor skips the request
+        let fut = address.request_cancellable(());
+        futures::pin_mut!(fut);
+        assert!(fut.as_mut().poll(&mut cx).is_pending());
+        drop(fut);
+        assert!(address.request(()).is_err());
+
+        step_actor(actor);
+        let mut request = address.request(()).unwrap();
+        while Pin::new(&mut request).poll(&mut cx).is_pending() {
+            step_actor(actor);
+        }
+    }
+
+    #[test]
+    fn test_notify_async_waits_for_space() {
+        let spawner = TestSpawner::new();
+        let actor = Box::leak(Box::new(ActorContext::new(DummyActor::new())));
+
+        let address = actor.mount((), spawner);
+
+        assert!(address.notify(TestMessage(0)).is_ok());
+
+        let waker = futures::task::noop_waker_ref();
+        let mut cx = std::task::Context::from_waker(waker);
+
+        let fut = address.notify_async(TestMessage(1));
+        futures::pin_mut!(fut);
+        assert!(fut.as_mut().poll(&mut cx).is_pending());
+
+        step_actor(actor);
+        assert!(fut.as_mut().poll(&mut cx).is_ready());
+    }
+
+    #[test]
+    fn test_recipient() {
+        let spawner = TestSpawner::new();
+        let actor = Box::leak(Box::new(ActorContext::new(Latest {
+            received: std::vec::Vec::new(),
+        })));
+
+        let recipient = actor.mount((), spawner).recipient();
+
+        let waker = futures::task::noop_waker_ref();
+        let mut cx = std::task::Context::from_waker(waker);
+
+        assert!(recipient.notify(1).is_ok());
+        assert!(recipient.notify(2).is_ok());
+        assert!(recipient.notify(3).is_err());
+
+        // Notifying asynchronously waits for space in the queue
+        let fut = recipient.notify_async(3);
+        futures::pin_mut!(fut);
+        assert!(fut.as_mut().poll(&mut cx).is_pending());
+
+        step_actor(actor);
+        assert!(fut.as_mut().poll(&mut cx).is_ready());
+
+        step_actor(actor);
+        step_actor(actor);
+        assert_eq!(&[1, 2, 3], &unsafe { &*actor.actor.get() }.received[..]);
+    }
+
+    /// Adapter only passing on even numbers
+    struct Even;
+
+    impl MessageAdapter<u8, u32> for Even {
+        fn adapt(message: u8) -> Option<u32> {
+            if message % 2 == 0 {
+                Some(message as u32)
+            } else {
+                None
+            }
+        }
+    }
+
+    #[test]
+    fn test_adapted_recipient() {
+        let spawner = TestSpawner::new();
+        let actor = Box::leak(Box::new(ActorContext::new(Latest {
+            received: std::vec::Vec::new(),
+        })));
+
+        let recipient = actor.mount((), spawner).recipient_with::<u8, Even>();
+
+        let waker = futures::task::noop_waker_ref();
+        let mut cx = std::task::Context::from_waker(waker);
+
+        assert!(recipient.notify(2).is_ok());
+        assert!(recipient.notify(3).is_ok());
+        assert!(recipient.notify(4).is_ok());
+        assert!(recipient.notify(6).is_err());
+
+        // Discarded messages complete without waiting for space in the queue
+        let fut = recipient.notify_async(5);
+        futures::pin_mut!(fut);
+        assert!(fut.as_mut().poll(&mut cx).is_ready());
+
+        // Converted messages are kept until there is space in the queue
+        let fut = recipient.notify_async(6);
+        futures::pin_mut!(fut);
+        assert!(fut.as_mut().poll(&mut cx).is_pending());
+
+        step_actor(actor);
+        assert!(fut.as_mut().poll(&mut cx).is_ready());
+
+        step_actor(actor);
+        step_actor(actor);
+        assert_eq!(&[2, 4, 6], &unsafe { &*actor.actor.get() }.received[..]);
+    }
+
+    #[test]
+    fn test_cancelled_request() {
+        let spawner = TestSpawner::new();
+        let actor = Box::leak(Box::new(ActorContext::new(DummyActor::new())));
+
+        let address = actor.mount((), spawner);
+
+        let result_fut_1 = address.request_with_timeout(TestMessage(0), Duration::from_secs(1));
+        assert!(result_fut_1.is_ok());
+
+        // Giving up keeps the slot reserved until the actor has seen the request
+        drop(result_fut_1);
+        assert!(address.request(TestMessage(1)).is_err());
+
+        step_actor(actor);
+
+        let result_fut_2 = address.request(TestMessage(1));
+        assert!(result_fut_2.is_ok());
+
+        let waker = futures::task::noop_waker_ref();
+        let mut cx = std::task::Context::from_waker(waker);
+
+        let mut fut_2 = result_fut_2.unwrap();
+        while Pin::new(&mut fut_2).poll(&mut cx).is_pending() {
+            step_actor(actor);
+        }
+    }
+
+    #[test]
+    fn test_deferred_response() {
+        let spawner = TestSpawner::new();
+        let actor = Box::leak(Box::new(ActorContext::new(Deferring {
+            address: None,
+            pending: None,
+        })));
+
+        let address = actor.mount((), spawner);
+
+        let waker = futures::task::noop_waker_ref();
+        let mut cx = std::task::Context::from_waker(waker);
+
+        let mut wait = address.request(DeferMessage::Wait).unwrap();
+        step_actor(actor);
+        assert!(Pin::new(&mut wait).poll(&mut cx).is_pending());
+
+        // The actor keeps handling messages while the first request is pending
+        let mut release = address.request(DeferMessage::Release(7)).unwrap();
+        step_actor(actor);
+        assert_eq!(Poll::Ready(Ok(0)), Pin::new(&mut release).poll(&mut cx));
+        assert_eq!(Poll::Ready(Ok(7)), Pin::new(&mut wait).poll(&mut cx));
+    }
+
+    #[test]
+    fn test_defer_other_actor() {
+        let spawner = TestSpawner::new();
+        let actor = Box::leak(Box::new(ActorContext::new(Deferring {
+            address: None,
+            pending: None,
+        })));
+
+        let address = actor.mount((), spawner);
+        let other = Deferring {
+            address: None,
+            pending: None,
+        };
+        assert!(address.defer(&other).is_none());
+    }
+
+    #[test]
+    fn test_dropped_responder() {
+        let spawner = TestSpawner::new();
+        let actor = Box::leak(Box::new(ActorContext::new(Deferring {
+            address: None,
+            pending: None,
+        })));
+
+        let address = actor.mount((), spawner);
+
+        let waker = futures::task::noop_waker_ref();
+        let mut cx = std::task::Context::from_waker(waker);
+
+        let mut wait = address
+            .request_with_timeout(DeferMessage::Wait, Duration::from_secs(1))
+            .unwrap();
+        step_actor(actor);
+        assert!(Pin::new(&mut wait).poll(&mut cx).is_pending());
+
+        address.notify(DeferMessage::Abandon).unwrap();
+        step_actor(actor);
+        match Pin::new(&mut wait).poll(&mut cx) {
+            Poll::Ready(Err(ActorError::Abandoned)) => {}
+            _ => panic!("expected the request to be abandoned"),
+        }
+    }
+
+    #[test]
+    fn test_dropped_responder_without_timeout() {
+        let spawner = TestSpawner::new();
+        let actor = Box::leak(Box::new(ActorContext::new(Deferring {
+            address: None,
+            pending: None,
+        })));
+
+        let address = actor.mount((), spawner);
+
+        let waker = futures::task::noop_waker_ref();
+        let mut cx = std::task::Context::from_waker(waker);
+
+        let mut wait = address.request(DeferMessage::Wait).unwrap();
+        step_actor(actor);
+        assert!(Pin::new(&mut wait).poll(&mut cx).is_pending());
+
+        address.notify(DeferMessage::Abandon).unwrap();
+        step_actor(actor);
+        assert_eq!(
+            Poll::Ready(Err(ActorError::Abandoned)),
+            Pin::new(&mut wait).poll(&mut cx)
+        );
+    }
+
+    #[test]
+    fn test_concurrent_requests() {
+        let spawner = TestSpawner::new();
+        let gates = Box::leak(Box::new([Signal::new(), Signal::new()]));
+        let actor = Box::leak(Box::new(ActorContext::new(Gated { gates })));
+
+        let address = actor.mount((), spawner);
+
+        let waker = futures::task::noop_waker_ref();
+        let mut cx = std::task::Context::from_waker(waker);
+
+        let mut first = address.request(0).unwrap();
+        let mut second = address.request(1).unwrap();
+        assert!(actor.poll(&mut cx).is_pending());
+
+        // The second request completes while the first one is still being handled
+        gates[1].signal(());
+        assert!(actor.poll(&mut cx).is_pending());
+        assert!(Pin::new(&mut first).poll(&mut cx).is_pending());
+        assert_eq!(Poll::Ready(Ok(1)), Pin::new(&mut second).poll(&mut cx));
+
+        gates[0].signal(());
+        assert!(actor.poll(&mut cx).is_pending());
+        assert_eq!(Poll::Ready(Ok(0)), Pin::new(&mut first).poll(&mut cx));
+
+        #[cfg(feature = "metrics")]
+        {
+            let metrics = address.metrics();
+            assert_eq!(2, metrics.processed);
+            assert!(metrics.peak_handler_ticks <= metrics.handler_ticks);
+        }
+    }
+
+    #[test]
+    fn test_shutdown_unmounted() {
+        let actor = Box::leak(Box::new(ActorContext::new(DummyActor::new())));
+
+        // There is no task to wait for
+        futures::executor::block_on(actor.shutdown());
+    }
+
+    #[test]
+    fn test_priority_spawner() {
+        let normal = Box::leak(Box::new(AtomicUsize::new(0)));
+        let high = Box::leak(Box::new(AtomicUsize::new(0)));
+        let spawner = PrioritySpawner::new(CountingSpawner(normal), CountingSpawner(high));
+
+        let dummy = Box::leak(Box::new(ActorContext::new(DummyActor::new())));
+        let urgent = Box::leak(Box::new(ActorContext::new(Urgent)));
+        dummy.mount((), spawner);
+        urgent.mount((), spawner);
+
+        assert_eq!(1, normal.load(Ordering::SeqCst));
+        assert_eq!(1, high.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_isr_drop_newest() {
+        let spawner = TestSpawner::new();
+        let actor = Box::leak(Box::new(ActorContext::new(DummyActor::new())));
+
+        let address = actor.mount((), spawner).isr_address();
+
+        assert!(address.notify_from_isr(TestMessage(0)).is_ok());
+        assert!(address.notify_from_isr(TestMessage(1)).is_err());
+        assert_eq!(1, address.dropped());
+
+        step_actor(actor);
+        assert!(address.notify_from_isr(TestMessage(1)).is_ok());
+        assert_eq!(1, address.dropped());
+    }
+
+    #[test]
+    fn test_isr_drop_oldest() {
+        let spawner = TestSpawner::new();
+        let actor = Box::leak(Box::new(ActorContext::new(Latest {
+            received: std::vec::Vec::new(),
+        })));
+
+        let address = actor.mount((), spawner).isr_address();
+
+        for value in 0..4 {
+            assert!(address.notify_from_isr(value).is_ok());
+        }
+        assert_eq!(2, address.dropped());
+
+        step_actor(actor);
+        step_actor(actor);
+        assert_eq!(&[2, 3], &unsafe { &*actor.actor.get() }.received[..]);
+    }
+
+    #[test]
+    fn test_isr_drop_oldest_keeps_requests() {
+        let spawner = TestSpawner::new();
+        let actor = Box::leak(Box::new(ActorContext::new(Latest {
+            received: std::vec::Vec::new(),
+        })));
+
+        let address = actor.mount((), spawner);
+        let isr_address = address.isr_address();
+
+        let waker = futures::task::noop_waker_ref();
+        let mut cx = std::task::Context::from_waker(waker);
+
+        // The notification queued after the request is displaced instead of the request
+        let mut request = address.request(1).unwrap();
+        assert!(isr_address.notify_from_isr(2).is_ok());
+        assert!(isr_address.notify_from_isr(3).is_ok());
+        assert_eq!(1, isr_address.dropped());
+
+        step_actor(actor);
+        step_actor(actor);
+        assert_eq!(Poll::Ready(Ok(())), Pin::new(&mut request).poll(&mut cx));
+        assert_eq!(&[1, 3], &unsafe { &*actor.actor.get() }.received[..]);
+
+        // With only requests queued, the notification is dropped
+        let mut first = address.request(4).unwrap();
+        let mut second = address.request(5).unwrap();
+        assert_eq!(
+            Err(ActorError::Channel(ChannelError::ChannelFull)),
+            isr_address.notify_from_isr(6)
+        );
+        assert_eq!(2, isr_address.dropped());
+
+        step_actor(actor);
+        step_actor(actor);
+        assert_eq!(Poll::Ready(Ok(())), Pin::new(&mut first).poll(&mut cx));
+        assert_eq!(Poll::Ready(Ok(())), Pin::new(&mut second).poll(&mut cx));
+        assert_eq!(&[1, 3, 4, 5], &unsafe { &*actor.actor.get() }.received[..]);
+    }
+
+    #[test]
+    fn test_pending_requests_sized_independently() {
+        let spawner = TestSpawner::new();
+        let actor = Box::leak(Box::new(ActorContext::new(Narrow)));
+
+        let address = actor.mount((), spawner);
+
+        let waker = futures::task::noop_waker_ref();
+        let mut cx = std::task::Context::from_waker(waker);
+
+        // The queue has room left, but there is no signal slot for another response
+        let mut request = address.request(()).unwrap();
+        assert!(matches!(
+            address.request(()),
+            Err(ActorError::Signal(SignalError::NoAvailableSignal))
+        ));
+        assert!(address.notify(()).is_ok());
+        assert!(address.notify(()).is_err());
+
+        while Pin::new(&mut request).poll(&mut cx).is_pending() {
+            step_actor(actor);
+        }
+
+        let mut request = address.request(()).unwrap();
+        while Pin::new(&mut request).poll(&mut cx).is_pending() {
+            step_actor(actor);
+        }
+    }
+
+    #[test]
+    fn test_requests_waiting_for_signal_interleaved() {
+        explore(32, |scheduler| {
+            let actor = Box::leak(Box::new(ActorContext::new(Narrow)));
+            let address = actor.mount((), scheduler);
+
+            // Only one request can be pending at a time, so the others wait for the signal
+            let completed = std::rc::Rc::new(Cell::new(0));
+            for _ in 0..3 {
+                let completed = completed.clone();
+                scheduler.spawn(async move {
+                    address.request_async(()).await.unwrap();
+                    completed.set(completed.get() + 1);
+                });
+            }
+
+            scheduler.run();
+            assert_eq!(3, completed.get());
+        });
+    }
 }
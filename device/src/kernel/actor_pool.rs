@@ -0,0 +1,313 @@
+use super::actor::{
+    Actor, ActorContext, ActorError, ActorSpawner, Address, RequestFuture, RequestTimeoutFuture,
+};
+use atomic_polyfill::{AtomicU32, AtomicUsize, Ordering};
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use embassy::time::Duration;
+
+/// A fixed number of identical actors behind a single `PoolAddress`, for spreading
+/// work over several instances of the same actor, such as a set of sockets.
+///
+/// Requests are dispatched to an instance that has no request from the pool in
+/// progress, if there is one, and otherwise round-robin. Notifications are dispatched
+/// round-robin, skipping instances with a full queue.
+pub struct ActorPool<A: Actor + 'static, const N: usize> {
+    actors: [ActorContext<'static, A>; N],
+    // Number of requests in progress through the pool, for each instance
+    requests: [AtomicU32; N],
+    next: AtomicUsize,
+}
+
+impl<A: Actor + 'static, const N: usize> ActorPool<A, N> {
+    const IDLE: AtomicU32 = AtomicU32::new(0);
+
+    pub fn new(actors: [A; N]) -> Self {
+        Self {
+            actors: actors.map(ActorContext::new),
+            requests: [Self::IDLE; N],
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Mount every actor of the pool with a copy of the provided configuration.
+    pub fn mount<S: ActorSpawner>(
+        &'static self,
+        config: A::Configuration,
+        spawner: S,
+    ) -> PoolAddress<A, N>
+    where
+        A::Configuration: Clone,
+    {
+        for actor in self.actors.iter() {
+            actor.mount(config.clone(), spawner);
+        }
+        PoolAddress { pool: self }
+    }
+
+    /// The index of the instance to receive the next message, in round-robin order.
+    fn next(&self) -> usize {
+        self.next.fetch_add(1, Ordering::Relaxed) % N
+    }
+
+    /// The index of the first idle instance starting at the next instance in round-robin
+    /// order, or the next instance if none are idle.
+    fn select(&self) -> usize {
+        let start = self.next();
+        (0..N)
+            .map(|i| (start + i) % N)
+            .find(|i| self.requests[*i].load(Ordering::Acquire) == 0)
+            .unwrap_or(start)
+    }
+
+    fn address(&'static self, index: usize) -> Address<'static, A> {
+        Address::new(&self.actors[index])
+    }
+}
+
+/// A handle to an `ActorPool`, dispatching each message to one of its actors.
+pub struct PoolAddress<A: Actor + 'static, const N: usize> {
+    pool: &'static ActorPool<A, N>,
+}
+
+impl<A: Actor + 'static, const N: usize> PoolAddress<A, N> {
+    /// Perform a request to an idle actor of the pool, or the next actor in round-robin
+    /// order if they are all busy. See `Address::request`.
+    ///
+    /// # Panics
+    /// While the request message may contain non-static references, the user must
+    /// ensure that the returned future is fully `.await`'d before returning.
+    /// Dropping it before it completes will result in a panic.
+    #[must_use = "The returned future must be awaited"]
+    pub fn request<'m>(
+        &self,
+        message: A::Message<'m>,
+    ) -> Result<PoolRequestFuture<RequestFuture<'static, A>>, ActorError> {
+        let index = self.pool.select();
+        let guard = RequestGuard::new(&self.pool.requests[index]);
+        let future = self.pool.address(index).request(message)?;
+        Ok(PoolRequestFuture {
+            future,
+            _guard: guard,
+        })
+    }
+
+    /// Perform a request to an idle actor of the pool, or the next actor in round-robin
+    /// order if they are all busy, giving up if no response has been received within the
+    /// provided timeout. See `Address::request_with_timeout`.
+    #[must_use = "The returned future must be awaited"]
+    pub fn request_with_timeout(
+        &self,
+        message: A::Message<'static>,
+        timeout: Duration,
+    ) -> Result<PoolRequestFuture<RequestTimeoutFuture<'static, A>>, ActorError> {
+        let index = self.pool.select();
+        let guard = RequestGuard::new(&self.pool.requests[index]);
+        let future = self
+            .pool
+            .address(index)
+            .request_with_timeout(message, timeout)?;
+        Ok(PoolRequestFuture {
+            future,
+            _guard: guard,
+        })
+    }
+
+    /// Perform a request to an idle actor of the pool, or the next actor in round-robin
+    /// order if they are all busy, waiting for a free signal slot and queue space if
+    /// necessary. See `Address::request_async`.
+    ///
+    /// # Panics
+    /// While the request message may contain non-static references, the user must
+    /// ensure that the returned future is fully `.await`'d before returning. Dropping it
+    /// once the message has been enqueued, before it completes, will result in a panic.
+    pub async fn request_async<'m>(
+        &self,
+        message: A::Message<'m>,
+    ) -> Result<A::Response, ActorError> {
+        let index = self.pool.select();
+        let _guard = RequestGuard::new(&self.pool.requests[index]);
+        self.pool.address(index).request_async(message).await
+    }
+
+    /// Perform a request to an idle actor of the pool, or the next actor in round-robin
+    /// order if they are all busy, waiting for a free signal slot and queue space if
+    /// necessary. The returned future may be dropped at any time, see
+    /// `Address::request_cancellable`.
+    pub async fn request_cancellable(
+        &self,
+        message: A::Message<'static>,
+    ) -> Result<A::Response, ActorError> {
+        let index = self.pool.select();
+        let _guard = RequestGuard::new(&self.pool.requests[index]);
+        self.pool.address(index).request_cancellable(message).await
+    }
+
+    /// Notify the next actor of the pool in round-robin order that has space in its queue,
+    /// returning an error if no actor has. See `Address::notify`.
+    pub fn notify(&self, mut message: A::Message<'static>) -> Result<(), ActorError> {
+        let start = self.pool.next();
+        for i in 0..N {
+            match self.pool.actors[(start + i) % N].try_notify(message) {
+                Ok(()) => return Ok(()),
+                Err(returned) => message = returned,
+            }
+        }
+        self.pool.address(start).notify(message)
+    }
+
+    /// Notify the next actor of the pool in round-robin order, waiting for space in its
+    /// queue if it is full. See `Address::notify_async`.
+    pub async fn notify_async(&self, message: A::Message<'static>) {
+        self.pool
+            .address(self.pool.next())
+            .notify_async(message)
+            .await
+    }
+}
+
+impl<A: Actor + 'static, const N: usize> Copy for PoolAddress<A, N> {}
+
+impl<A: Actor + 'static, const N: usize> Clone for PoolAddress<A, N> {
+    fn clone(&self) -> Self {
+        Self { pool: self.pool }
+    }
+}
+
+/// A request to an actor of an `ActorPool`, which counts as in progress on that actor
+/// until the future is dropped.
+pub struct PoolRequestFuture<F> {
+    future: F,
+    _guard: RequestGuard<'static>,
+}
+
+impl<F: Future + Unpin> Future for PoolRequestFuture<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.future).poll(cx)
+    }
+}
+
+/// Counts a request in progress for as long as it lives.
+struct RequestGuard<'a> {
+    requests: &'a AtomicU32,
+}
+
+impl<'a> RequestGuard<'a> {
+    fn new(requests: &'a AtomicU32) -> Self {
+        requests.fetch_add(1, Ordering::AcqRel);
+        Self { requests }
+    }
+}
+
+impl<'a> Drop for RequestGuard<'a> {
+    fn drop(&mut self) {
+        self.requests.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::util::ImmediateFuture;
+    use crate::testutil::*;
+    use core::future::{ready, Ready};
+
+    /// Responds to every request with its id
+    struct Worker(u32);
+
+    impl Actor for Worker {
+        type Message<'m> = ();
+        type Response = u32;
+        type OnStartFuture<'m> = ImmediateFuture;
+        type OnMessageFuture<'m> = Ready<u32>;
+
+        fn on_start(self: Pin<&'_ mut Self>) -> Self::OnStartFuture<'_> {
+            ImmediateFuture::new()
+        }
+
+        fn on_message<'m>(self: Pin<&'m mut Self>, _: ()) -> Self::OnMessageFuture<'m> {
+            ready(self.0)
+        }
+    }
+
+    #[test]
+    fn test_requests_to_idle_workers() {
+        let spawner = TestSpawner::new();
+        let pool = Box::leak(Box::new(ActorPool::new([Worker(0), Worker(1)])));
+
+        let address = pool.mount((), spawner);
+
+        let waker = futures::task::noop_waker_ref();
+        let mut cx = std::task::Context::from_waker(waker);
+
+        let first = address.request_async(());
+        futures::pin_mut!(first);
+        assert!(first.as_mut().poll(&mut cx).is_pending());
+
+        // The first worker is busy, so the second request goes to the other one
+        let second = address.request_async(());
+        futures::pin_mut!(second);
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+
+        step_actor(&pool.actors[1]);
+        assert_eq!(Poll::Ready(Ok(1)), second.as_mut().poll(&mut cx));
+
+        // Only the first worker has a request in progress now
+        let third = address.request_async(());
+        futures::pin_mut!(third);
+        assert!(third.as_mut().poll(&mut cx).is_pending());
+
+        step_actor(&pool.actors[0]);
+        assert_eq!(Poll::Ready(Ok(0)), first.as_mut().poll(&mut cx));
+        step_actor(&pool.actors[1]);
+        assert_eq!(Poll::Ready(Ok(1)), third.as_mut().poll(&mut cx));
+    }
+
+    #[test]
+    fn test_request_futures() {
+        let spawner = TestSpawner::new();
+        let pool = Box::leak(Box::new(ActorPool::new([Worker(0), Worker(1)])));
+
+        let address = pool.mount((), spawner);
+
+        let waker = futures::task::noop_waker_ref();
+        let mut cx = std::task::Context::from_waker(waker);
+
+        // The first worker is busy for as long as the first request is not dropped
+        let mut first = address.request(()).unwrap();
+        let mut second = address
+            .request_with_timeout((), Duration::from_secs(1))
+            .unwrap();
+
+        step_actor(&pool.actors[1]);
+        assert_eq!(Poll::Ready(Ok(1)), Pin::new(&mut second).poll(&mut cx));
+        step_actor(&pool.actors[0]);
+        assert_eq!(Poll::Ready(Ok(0)), Pin::new(&mut first).poll(&mut cx));
+        drop(first);
+
+        // Both workers are idle again, starting at the next in round-robin order
+        let mut third = address.request(()).unwrap();
+        step_actor(&pool.actors[0]);
+        assert_eq!(Poll::Ready(Ok(0)), Pin::new(&mut third).poll(&mut cx));
+    }
+
+    #[test]
+    fn test_notify_skips_full_workers() {
+        let spawner = TestSpawner::new();
+        let pool = Box::leak(Box::new(ActorPool::new([Worker(0), Worker(1)])));
+
+        let address = pool.mount((), spawner);
+
+        // Each worker has room for a single message
+        assert!(address.notify(()).is_ok());
+        assert!(address.notify(()).is_ok());
+        assert!(address.notify(()).is_err());
+
+        step_actor(&pool.actors[0]);
+        assert!(address.notify(()).is_ok());
+        assert!(address.notify(()).is_err());
+    }
+}
@@ -1,41 +1,45 @@
+use super::util::{Capacity, RingBuffer, WakerSet};
 use core::{
-    cell::{RefCell, UnsafeCell},
     future::Future,
     pin::Pin,
     task::{Context, Poll, Waker},
 };
 use embassy::util::AtomicWaker;
-use heapless::{
-    spsc::{Consumer, Producer, Queue},
-    ArrayLength,
-};
+use heapless::consts;
+
+/// The number of senders that can wait for space in a channel before they fall back to polling.
+///
+/// A sender waiting beyond this number is woken again as soon as it registers, so it
+/// busy-polls, keeping the executor awake, until a value is dequeued.
+type MaxWaitingSenders = consts::U4;
 
-struct ChannelInner<T, N>
+struct ChannelInner<T, C>
 where
-    N: ArrayLength<T>,
+    C: Capacity,
 {
-    queue: UnsafeCell<Queue<T, N>>,
-    sender_waker: AtomicWaker,
+    // Only accessed within critical sections
+    buffer: RingBuffer<T, C>,
+    sender_wakers: WakerSet<MaxWaitingSenders>,
     receiver_waker: AtomicWaker,
 }
 
-impl<T, N> Default for ChannelInner<T, N>
+impl<T, C> Default for ChannelInner<T, C>
 where
-    N: ArrayLength<T>,
+    C: Capacity,
 {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl<T, N> ChannelInner<T, N>
+impl<T, C> ChannelInner<T, C>
 where
-    N: ArrayLength<T>,
+    C: Capacity,
 {
     pub fn new() -> Self {
         Self {
-            queue: UnsafeCell::new(Queue::new()),
-            sender_waker: AtomicWaker::new(),
+            buffer: RingBuffer::new(),
+            sender_wakers: WakerSet::new(),
             receiver_waker: AtomicWaker::new(),
         }
     }
@@ -45,106 +49,133 @@ where
     }
 
     fn register_sender(&self, waker: &Waker) {
-        self.sender_waker.register(&waker);
+        self.sender_wakers.register(&waker);
     }
 
     fn wake_sender(&self) {
-        self.sender_waker.wake();
+        self.sender_wakers.wake();
     }
 
     fn wake_receiver(&self) {
         self.receiver_waker.wake();
     }
 
-    fn split(&mut self) -> (ChannelSender<'_, T, N>, ChannelReceiver<'_, T, N>) {
-        let (sender, receiver) = unsafe { (&mut *self.queue.get()).split() };
-        (
-            ChannelSender::new(sender, self),
-            ChannelReceiver::new(receiver, self),
-        )
+    fn is_full(&self) -> bool {
+        self.buffer.is_full()
+    }
+
+    /// Append a value to the buffer, returning it if the buffer is full. Must be called
+    /// within a critical section.
+    fn enqueue(&self, value: T) -> Result<(), T> {
+        self.buffer.enqueue(value)
+    }
+
+    /// Take the oldest value from the buffer. Must be called within a critical section.
+    fn dequeue(&self) -> Option<T> {
+        self.buffer.dequeue()
+    }
+
+    /// Take the oldest value matching `f` from the buffer. Must be called within a
+    /// critical section.
+    fn remove_first<F: FnMut(&T) -> bool>(&self, f: F) -> Option<T> {
+        self.buffer.remove_first(f)
+    }
+
+    fn split(&mut self) -> (ChannelSender<'_, T, C>, ChannelReceiver<'_, T, C>) {
+        (ChannelSender::new(self), ChannelReceiver::new(self))
     }
 }
 
-pub struct Channel<T, N>
+pub struct Channel<T, C>
 where
-    N: ArrayLength<T>,
+    C: Capacity,
 {
-    inner: ChannelInner<T, N>,
+    inner: ChannelInner<T, C>,
 }
 
-impl<T, N> Default for Channel<T, N>
+impl<T, C> Default for Channel<T, C>
 where
-    N: ArrayLength<T>,
+    C: Capacity,
 {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl<T, N> Channel<T, N>
+impl<T, C> Channel<T, C>
 where
-    N: ArrayLength<T>,
+    C: Capacity,
 {
     pub fn new() -> Self {
         let inner = ChannelInner::new();
         Self { inner }
     }
 
-    pub fn split(&mut self) -> (ChannelSender<'_, T, N>, ChannelReceiver<'_, T, N>) {
+    pub fn split(&mut self) -> (ChannelSender<'_, T, C>, ChannelReceiver<'_, T, C>) {
         self.inner.split()
     }
 }
 
-pub struct ChannelSender<'a, T, N>
+/// The sending half of a channel. The sender may be shared between multiple
+/// producers, including interrupt handlers, as every access to the underlying
+/// buffer happens within a critical section. The same goes for the receiver,
+/// which allows a producer to dequeue the oldest value to make room.
+pub struct ChannelSender<'a, T, C>
 where
-    N: ArrayLength<T>,
+    C: Capacity,
 {
-    inner: &'a ChannelInner<T, N>,
-    producer: RefCell<Producer<'a, T, N>>,
+    inner: &'a ChannelInner<T, C>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum ChannelError {
     ChannelFull,
     ChannelEmpty,
 }
 
-impl<'a, T, N> ChannelSender<'a, T, N>
+impl<'a, T, C> ChannelSender<'a, T, C>
 where
-    N: ArrayLength<T>,
+    C: Capacity,
 {
-    fn new(producer: Producer<'a, T, N>, inner: &'a ChannelInner<T, N>) -> Self {
-        Self {
-            producer: RefCell::new(producer),
-            inner,
-        }
+    fn new(inner: &'a ChannelInner<T, C>) -> Self {
+        Self { inner }
     }
 
     fn poll_enqueue(&self, cx: &mut Context<'_>, element: &mut Option<T>) -> Poll<()> {
-        let mut producer = self.producer.borrow_mut();
-        if producer.ready() {
-            let value = element.take().unwrap();
-            producer.enqueue(value).ok().unwrap();
-            self.inner.wake_receiver();
-            Poll::Ready(())
-        } else {
-            self.inner.register_sender(cx.waker());
-            Poll::Pending
-        }
+        self.poll_enqueue_with(cx, || element.take().unwrap())
+    }
+
+    /// Poll for space in the channel, enqueueing the value produced by `f` once there is.
+    pub fn poll_enqueue_with<F: FnOnce() -> T>(&self, cx: &mut Context<'_>, f: F) -> Poll<()> {
+        // Register before trying in order to not miss a dequeue in between
+        self.inner.register_sender(cx.waker());
+        critical_section::with(|_| {
+            if self.inner.is_full() {
+                Poll::Pending
+            } else {
+                self.inner.enqueue(f()).ok().unwrap();
+                self.inner.wake_receiver();
+                Poll::Ready(())
+            }
+        })
+    }
+
+    /// Returns true if there is no space left in the channel.
+    pub fn is_full(&self) -> bool {
+        critical_section::with(|_| self.inner.is_full())
     }
 
     pub fn try_send(&self, value: T) -> Result<(), ChannelError> {
         critical_section::with(|_| {
-            let mut producer = self.producer.borrow_mut();
-            producer
+            self.inner
                 .enqueue(value)
                 .map_err(|_| ChannelError::ChannelFull)
                 .map(|_| self.inner.wake_receiver())
         })
     }
 
-    pub fn send<'m>(&'m self, value: T) -> ChannelSend<'m, 'a, T, N> {
+    pub fn send<'m>(&'m self, value: T) -> ChannelSend<'m, 'a, T, C> {
         ChannelSend {
             sender: &self,
             element: Some(value),
@@ -152,19 +183,19 @@ where
     }
 }
 
-pub struct ChannelSend<'m, 'a, T, N>
+pub struct ChannelSend<'m, 'a, T, C>
 where
-    N: ArrayLength<T>,
+    C: Capacity,
 {
-    sender: &'m ChannelSender<'a, T, N>,
+    sender: &'m ChannelSender<'a, T, C>,
     element: Option<T>,
 }
 
-impl<'m, 'a, T, N> Unpin for ChannelSend<'m, 'a, T, N> where N: ArrayLength<T> {}
+impl<'m, 'a, T, C> Unpin for ChannelSend<'m, 'a, T, C> where C: Capacity {}
 
-impl<'m, 'a, T, N> Future for ChannelSend<'m, 'a, T, N>
+impl<'m, 'a, T, C> Future for ChannelSend<'m, 'a, T, C>
 where
-    N: ArrayLength<T>,
+    C: Capacity,
 {
     type Output = ();
 
@@ -173,60 +204,72 @@ where
     }
 }
 
-pub struct ChannelReceiver<'a, T, N>
+pub struct ChannelReceiver<'a, T, C>
 where
-    N: ArrayLength<T>,
+    C: Capacity,
 {
-    inner: &'a ChannelInner<T, N>,
-    consumer: RefCell<Consumer<'a, T, N>>,
+    inner: &'a ChannelInner<T, C>,
 }
 
-impl<'a, T, N> ChannelReceiver<'a, T, N>
+impl<'a, T, C> ChannelReceiver<'a, T, C>
 where
-    N: ArrayLength<T>,
+    C: Capacity,
 {
-    fn new(consumer: Consumer<'a, T, N>, inner: &'a ChannelInner<T, N>) -> Self {
-        Self {
-            consumer: RefCell::new(consumer),
-            inner,
-        }
+    fn new(inner: &'a ChannelInner<T, C>) -> Self {
+        Self { inner }
     }
 
-    fn poll_dequeue(&self, cx: &mut Context<'_>) -> Poll<T> {
-        if let Some(value) = self.consumer.borrow_mut().dequeue() {
-            self.inner.wake_sender();
-            Poll::Ready(value)
-        } else {
-            self.inner.register_receiver(cx.waker());
-            Poll::Pending
-        }
+    pub(crate) fn poll_dequeue(&self, cx: &mut Context<'_>) -> Poll<T> {
+        // Register before trying in order to not miss an enqueue in between
+        self.inner.register_receiver(cx.waker());
+        // Senders may dequeue to make room from interrupt context, see `try_receive`
+        critical_section::with(|_| {
+            if let Some(value) = self.inner.dequeue() {
+                self.inner.wake_sender();
+                Poll::Ready(value)
+            } else {
+                Poll::Pending
+            }
+        })
     }
 
-    pub fn receive<'m>(&'m self) -> ChannelReceive<'m, 'a, T, N> {
+    pub fn receive<'m>(&'m self) -> ChannelReceive<'m, 'a, T, C> {
         ChannelReceive { receiver: &self }
     }
     pub fn try_receive(&self) -> Result<T, ChannelError> {
         critical_section::with(|_| {
-            let mut consumer = self.consumer.borrow_mut();
-            if let Some(value) = consumer.dequeue() {
+            if let Some(value) = self.inner.dequeue() {
+                self.inner.wake_sender();
                 Ok(value)
             } else {
                 Err(ChannelError::ChannelEmpty)
             }
         })
     }
+
+    /// Take the oldest value for which `f` returns true, leaving the other values in
+    /// the order they were sent.
+    pub fn try_remove_first<F: FnMut(&T) -> bool>(&self, f: F) -> Option<T> {
+        critical_section::with(|_| {
+            let value = self.inner.remove_first(f);
+            if value.is_some() {
+                self.inner.wake_sender();
+            }
+            value
+        })
+    }
 }
 
-pub struct ChannelReceive<'m, 'a, T, N>
+pub struct ChannelReceive<'m, 'a, T, C>
 where
-    N: ArrayLength<T>,
+    C: Capacity,
 {
-    receiver: &'m ChannelReceiver<'a, T, N>,
+    receiver: &'m ChannelReceiver<'a, T, C>,
 }
 
-impl<'m, 'a, T, N> Future for ChannelReceive<'m, 'a, T, N>
+impl<'m, 'a, T, C> Future for ChannelReceive<'m, 'a, T, C>
 where
-    N: ArrayLength<T>,
+    C: Capacity,
 {
     type Output = T;
 
@@ -1,30 +1,43 @@
 use atomic_polyfill::{AtomicU8, Ordering};
+use core::cell::UnsafeCell;
 use core::future::Future;
-use embassy::util::Forever;
 
 const NEW: u8 = 0;
 const CONFIGURED: u8 = 1;
 const MOUNTED: u8 = 2;
+const UNMOUNTING: u8 = 3;
+
+/// A device whose actors can be shut down, allowing it to be unmounted from its
+/// `DeviceContext`. Devices declared with `device!` shut down their actors and packages
+/// in the reverse of the order they were mounted in.
+pub trait Device {
+    type ShutdownFuture: Future<Output = ()>;
+
+    /// Shut down every actor and package of the device.
+    fn shutdown(&'static self) -> Self::ShutdownFuture;
+}
 
 pub struct DeviceContext<D: 'static> {
-    device: Forever<D>,
+    device: UnsafeCell<Option<D>>,
     state: AtomicU8,
 }
 
+unsafe impl<D: 'static> Sync for DeviceContext<D> {}
+
 impl<D: 'static> DeviceContext<D> {
     pub const fn new() -> Self {
         Self {
-            device: Forever::new(),
+            device: UnsafeCell::new(None),
             state: AtomicU8::new(NEW),
         }
     }
 
     pub fn configure(&'static self, device: D) {
-        match self.state.fetch_add(1, Ordering::Relaxed) {
-            NEW => {
-                self.device.put(device);
+        match self.transition(NEW, CONFIGURED) {
+            Ok(_) => {
+                unsafe { &mut *self.device.get() }.replace(device);
             }
-            _ => {
+            Err(_) => {
                 panic!("Context already configured");
             }
         }
@@ -34,34 +47,70 @@ impl<D: 'static> DeviceContext<D> {
         &'static self,
         f: F,
     ) -> R {
-        match self.state.fetch_add(1, Ordering::Relaxed) {
-            CONFIGURED => {
-                let device = unsafe { self.device.steal() };
+        match self.transition(CONFIGURED, MOUNTED) {
+            Ok(_) => {
+                let device = unsafe { &*self.device.get() }.as_ref().unwrap();
                 let r = f(device).await;
 
                 r
             }
-            NEW => {
+            Err(NEW) => {
                 panic!("Context must be configured before mounted");
             }
-            MOUNTED => {
+            Err(MOUNTED) => {
                 panic!("Context already mounted");
             }
-            val => {
+            Err(val) => {
                 panic!("Unexpected state: {}", val);
             }
         }
     }
+
+    fn transition(&self, from: u8, to: u8) -> Result<u8, u8> {
+        self.state
+            .compare_exchange(from, to, Ordering::AcqRel, Ordering::Acquire)
+    }
+}
+
+impl<D: Device + 'static> DeviceContext<D> {
+    /// Unmount the device, shutting down all of its actors. The device may be mounted
+    /// again afterwards.
+    pub async fn unmount(&'static self) {
+        match self.transition(MOUNTED, UNMOUNTING) {
+            Ok(_) => {
+                let device = unsafe { &*self.device.get() }.as_ref().unwrap();
+                device.shutdown().await;
+                self.state.store(CONFIGURED, Ordering::Release);
+            }
+            Err(val) => {
+                panic!("Context must be mounted before unmounted, state: {}", val);
+            }
+        }
+    }
+
+    /// Shut down the device, unmounting it first if it is mounted, allowing the context
+    /// to be configured again.
+    ///
+    /// The device is only dropped once the context is configured again, so that the
+    /// addresses of its actors keep referring to actors that have been shut down.
+    pub async fn shutdown(&'static self) {
+        if self.state.load(Ordering::Acquire) == MOUNTED {
+            self.unmount().await;
+        }
+        match self.transition(CONFIGURED, NEW) {
+            Ok(_) | Err(NEW) => {}
+            Err(_) => {
+                panic!("Context must be unmounted before shut down");
+            }
+        }
+    }
 }
 
 impl<D: 'static> Drop for DeviceContext<D> {
     fn drop(&mut self) {
         match self.state.load(Ordering::Acquire) {
-            CONFIGURED => {
-                panic!("Context must be mounted before it is dropped");
-            }
-            MOUNTED => {
-                panic!("Context must be configured and mounted before it is dropped");
+            MOUNTED | UNMOUNTING => {
+                panic!("Context must be unmounted before it is dropped");
             }
             _ => {}
         }
@@ -0,0 +1,259 @@
+//! Runtime metrics of actors, enabled with the `metrics` feature.
+//!
+//! When the feature is disabled, the recorder kept in each `ActorContext` is zero-sized
+//! and all recording is a no-op.
+
+#[cfg(feature = "metrics")]
+use core::cell::{Cell, RefCell};
+#[cfg(feature = "metrics")]
+use embassy::time::{Duration, Instant};
+
+/// A snapshot of the counters of a single actor.
+#[cfg(feature = "metrics")]
+#[derive(Clone, Copy, Debug, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ActorMetrics {
+    /// Number of messages handled by the actor.
+    pub processed: u32,
+    /// Number of notifications dropped because the queue was full.
+    pub notify_drops: u32,
+    /// Number of requests dropped because the queue was full or no signal slot was free.
+    pub request_drops: u32,
+    /// Number of messages currently in the queue.
+    pub queue_depth: u32,
+    /// The highest number of messages that has been in the queue.
+    pub max_queue_depth: u32,
+    /// Cumulative time spent in `on_message` or `on_shared_message`, in ticks. Messages
+    /// handled at the same time are each timed in full.
+    pub handler_ticks: u64,
+    /// The longest time spent handling a single message, in ticks.
+    pub peak_handler_ticks: u64,
+}
+
+#[cfg(feature = "metrics")]
+impl ActorMetrics {
+    /// Cumulative time spent in `on_message`.
+    pub fn handler_time(&self) -> Duration {
+        Duration::from_ticks(self.handler_ticks)
+    }
+
+    /// The longest time spent handling a single message.
+    pub fn peak_handler_time(&self) -> Duration {
+        Duration::from_ticks(self.peak_handler_ticks)
+    }
+
+    /// Log the metrics with the provided name, as processed messages, notify/request drops,
+    /// current/max queue depth and total/peak handler time.
+    pub fn log(&self, name: &str) {
+        info!(
+            "[{}] processed {}, dropped {}/{}, queue {}/{}, handler {}/{} ms",
+            name,
+            self.processed,
+            self.notify_drops,
+            self.request_drops,
+            self.queue_depth,
+            self.max_queue_depth,
+            self.handler_time().as_millis(),
+            self.peak_handler_time().as_millis()
+        );
+    }
+}
+
+/// When a message handled concurrently with others started being handled.
+#[cfg(feature = "metrics")]
+#[derive(Clone, Copy)]
+pub(crate) struct HandlerStart(Instant);
+
+#[cfg(feature = "metrics")]
+pub(crate) struct MetricsRecorder {
+    metrics: RefCell<ActorMetrics>,
+    handler_started: Cell<Option<Instant>>,
+}
+
+#[cfg(feature = "metrics")]
+impl MetricsRecorder {
+    pub(crate) fn new() -> Self {
+        Self {
+            metrics: RefCell::new(ActorMetrics::default()),
+            handler_started: Cell::new(None),
+        }
+    }
+
+    fn update<F: FnOnce(&mut ActorMetrics)>(&self, f: F) {
+        critical_section::with(|_| f(&mut self.metrics.borrow_mut()))
+    }
+
+    pub(crate) fn snapshot(&self) -> ActorMetrics {
+        critical_section::with(|_| *self.metrics.borrow())
+    }
+
+    pub(crate) fn enqueued(&self) {
+        self.update(|m| {
+            m.queue_depth += 1;
+            m.max_queue_depth = core::cmp::max(m.max_queue_depth, m.queue_depth);
+        })
+    }
+
+    pub(crate) fn dequeued(&self) {
+        self.update(|m| m.queue_depth = m.queue_depth.saturating_sub(1))
+    }
+
+    pub(crate) fn notify_dropped(&self) {
+        self.update(|m| m.notify_drops += 1)
+    }
+
+    pub(crate) fn request_dropped(&self) {
+        self.update(|m| m.request_drops += 1)
+    }
+
+    pub(crate) fn handler_started(&self) {
+        self.handler_started.set(Some(Instant::now()));
+    }
+
+    pub(crate) fn handler_finished(&self) {
+        if let Some(started) = self.handler_started.take() {
+            self.processed(started);
+        }
+    }
+
+    pub(crate) fn handler_aborted(&self) {
+        self.handler_started.set(None);
+    }
+
+    /// Start timing a message handled concurrently with others.
+    pub(crate) fn shared_handler_started(&self) -> HandlerStart {
+        HandlerStart(Instant::now())
+    }
+
+    pub(crate) fn shared_handler_finished(&self, started: HandlerStart) {
+        self.processed(started.0);
+    }
+
+    fn processed(&self, started: Instant) {
+        let ticks = Instant::now().duration_since(started).as_ticks();
+        self.update(|m| {
+            m.processed += 1;
+            m.handler_ticks += ticks;
+            m.peak_handler_ticks = core::cmp::max(m.peak_handler_ticks, ticks);
+        })
+    }
+}
+
+#[cfg(not(feature = "metrics"))]
+#[derive(Clone, Copy)]
+pub(crate) struct HandlerStart;
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) struct MetricsRecorder;
+
+#[cfg(not(feature = "metrics"))]
+impl MetricsRecorder {
+    pub(crate) fn new() -> Self {
+        Self
+    }
+
+    pub(crate) fn enqueued(&self) {}
+
+    pub(crate) fn dequeued(&self) {}
+
+    pub(crate) fn notify_dropped(&self) {}
+
+    pub(crate) fn request_dropped(&self) {}
+
+    pub(crate) fn handler_started(&self) {}
+
+    pub(crate) fn handler_finished(&self) {}
+
+    pub(crate) fn handler_aborted(&self) {}
+
+    pub(crate) fn shared_handler_started(&self) -> HandlerStart {
+        HandlerStart
+    }
+
+    pub(crate) fn shared_handler_finished(&self, _: HandlerStart) {}
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use crate::kernel::{
+        actor::{Actor, ActorContext},
+        util::{ImmediateFuture, Slots},
+    };
+    use crate::testutil::*;
+    use core::future::Future;
+    use core::pin::Pin;
+
+    /// Has room for two messages, but only a single pending request
+    struct Sink;
+
+    impl Actor for Sink {
+        type MessageQueueSize = Slots<2>;
+        type PendingRequests = Slots<1>;
+        type Message<'m> = ();
+        type OnStartFuture<'m> = ImmediateFuture;
+        type OnMessageFuture<'m> = ImmediateFuture;
+
+        fn on_start(self: Pin<&'_ mut Self>) -> Self::OnStartFuture<'_> {
+            ImmediateFuture::new()
+        }
+
+        fn on_message<'m>(self: Pin<&'m mut Self>, _: ()) -> Self::OnMessageFuture<'m> {
+            ImmediateFuture::new()
+        }
+    }
+
+    #[test]
+    fn test_queue_depth() {
+        let spawner = TestSpawner::new();
+        let actor = Box::leak(Box::new(ActorContext::new(Sink)));
+
+        let address = actor.mount((), spawner);
+
+        assert!(address.notify(()).is_ok());
+        assert!(address.notify(()).is_ok());
+        let metrics = address.metrics();
+        assert_eq!(2, metrics.queue_depth);
+        assert_eq!(2, metrics.max_queue_depth);
+
+        step_actor(actor);
+        assert_eq!(1, address.metrics().queue_depth);
+        assert!(address.notify(()).is_ok());
+        step_actor(actor);
+        step_actor(actor);
+
+        let metrics = address.metrics();
+        assert_eq!(3, metrics.processed);
+        assert_eq!(0, metrics.queue_depth);
+        assert_eq!(2, metrics.max_queue_depth);
+    }
+
+    #[test]
+    fn test_drops() {
+        let spawner = TestSpawner::new();
+        let actor = Box::leak(Box::new(ActorContext::new(Sink)));
+
+        let address = actor.mount((), spawner);
+
+        assert!(address.notify(()).is_ok());
+        let mut request = address.request(()).unwrap();
+
+        // No signal slot is left for another request, and no room for another message
+        assert!(address.request(()).is_err());
+        assert!(address.notify(()).is_err());
+
+        let metrics = address.metrics();
+        assert_eq!(1, metrics.notify_drops);
+        assert_eq!(1, metrics.request_drops);
+        assert_eq!(2, metrics.queue_depth);
+
+        let waker = futures::task::noop_waker_ref();
+        let mut cx = std::task::Context::from_waker(waker);
+        while Pin::new(&mut request).poll(&mut cx).is_pending() {
+            step_actor(actor);
+        }
+
+        let metrics = address.metrics();
+        assert_eq!(2, metrics.processed);
+        assert_eq!(0, metrics.queue_depth);
+    }
+}
@@ -1,6 +1,15 @@
 pub mod actor;
+pub mod actor_pool;
 pub mod channel;
 pub mod device;
+pub mod metrics;
 pub mod package;
+pub mod pool;
+#[cfg(feature = "remote")]
+pub mod remote;
 pub mod signal;
+pub mod supervisor;
+pub mod sync;
+pub mod trace;
 pub mod util;
+pub mod watch;
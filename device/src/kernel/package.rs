@@ -1,10 +1,12 @@
 use super::actor::{Actor, ActorSpawner, Address};
+use core::future::Future;
 
 /// The package trait provides a way to bundle one or more actors and
 /// additional state in a package that can be used by other components.
 ///
 /// A Package is mounted with its desired configuration, and has a primary
-/// Actor that it provides the Address of when mounted.
+/// Actor that it provides the Address of when mounted. Shutting it down
+/// stops all of its actors.
 pub trait Package {
     /// The primary Actor for this package.
     type Primary: Actor;
@@ -12,6 +14,8 @@ pub trait Package {
     /// The expected configuration when mounting.
     type Configuration = ();
 
+    type ShutdownFuture: Future<Output = ()>;
+
     /// Mount this package, providing the configuration and a reference
     /// to a spawner used when mounting internal actors of the Package.
     fn mount<S: ActorSpawner>(
@@ -19,4 +23,8 @@ pub trait Package {
         config: Self::Configuration,
         spawner: S,
     ) -> Address<Self::Primary>;
+
+    /// Shut down the actors of this package, in the reverse of the order they
+    /// were mounted in.
+    fn shutdown(&'static self) -> Self::ShutdownFuture;
 }
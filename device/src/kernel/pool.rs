@@ -0,0 +1,106 @@
+//! Fixed size buffers in static storage, for passing large payloads between actors
+//! and drivers without copying them or keeping them on the stack.
+
+use atomic_polyfill::{AtomicBool, Ordering};
+use core::cell::UnsafeCell;
+use core::ops::{Deref, DerefMut};
+
+/// `N` buffers of `SIZE` bytes, meant to be placed in a `static`.
+pub struct Pool<const SIZE: usize, const N: usize> {
+    buffers: [UnsafeCell<[u8; SIZE]>; N],
+    used: [AtomicBool; N],
+}
+
+impl<const SIZE: usize, const N: usize> Pool<SIZE, N> {
+    const EMPTY: UnsafeCell<[u8; SIZE]> = UnsafeCell::new([0; SIZE]);
+    const FREE: AtomicBool = AtomicBool::new(false);
+
+    pub const fn new() -> Self {
+        Self {
+            buffers: [Self::EMPTY; N],
+            used: [Self::FREE; N],
+        }
+    }
+
+    /// Take a buffer from the pool, or `None` if all buffers are in use. The buffer
+    /// is returned to the pool when the `PoolBox` is dropped.
+    ///
+    /// The contents of the buffer are left as they were when it was last used.
+    pub fn alloc(&'static self) -> Option<PoolBox<SIZE>> {
+        self.used
+            .iter()
+            .zip(self.buffers.iter())
+            .find_map(|(used, buffer)| {
+                used.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                    .ok()
+                    .map(|_| PoolBox {
+                        // Safety: The buffer is only handed out once until it is freed
+                        buffer: unsafe { &mut *buffer.get() },
+                        used,
+                    })
+            })
+    }
+
+    /// The number of buffers currently available.
+    pub fn available(&self) -> usize {
+        self.used
+            .iter()
+            .filter(|used| !used.load(Ordering::Relaxed))
+            .count()
+    }
+}
+
+// Safety: Each buffer is only accessed through the single `PoolBox` it was handed out as.
+unsafe impl<const SIZE: usize, const N: usize> Sync for Pool<SIZE, N> {}
+
+/// An owned buffer from a `Pool`, which can be moved between actors and is returned
+/// to the pool when dropped.
+pub struct PoolBox<const SIZE: usize> {
+    buffer: &'static mut [u8; SIZE],
+    used: &'static AtomicBool,
+}
+
+impl<const SIZE: usize> Deref for PoolBox<SIZE> {
+    type Target = [u8; SIZE];
+    fn deref(&self) -> &[u8; SIZE] {
+        self.buffer
+    }
+}
+
+impl<const SIZE: usize> DerefMut for PoolBox<SIZE> {
+    fn deref_mut(&mut self) -> &mut [u8; SIZE] {
+        self.buffer
+    }
+}
+
+impl<const SIZE: usize> Drop for PoolBox<SIZE> {
+    fn drop(&mut self) {
+        self.used.store(false, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static POOL: Pool<4, 2> = Pool::new();
+
+    #[test]
+    fn test_alloc_until_empty() {
+        let mut first = POOL.alloc().unwrap();
+        let second = POOL.alloc().unwrap();
+        assert!(POOL.alloc().is_none());
+        assert_eq!(0, POOL.available());
+
+        first.copy_from_slice(&[1, 2, 3, 4]);
+        let moved = first;
+        assert_eq!(&[1, 2, 3, 4], &moved[..]);
+
+        drop(moved);
+        assert_eq!(1, POOL.available());
+        assert!(POOL.alloc().is_some());
+
+        drop(second);
+        assert_eq!(2, POOL.available());
+    }
+}
@@ -0,0 +1,588 @@
+//! Messaging between actors on different devices, such as a board talking to a gateway
+//! over a serial line or a network socket.
+//!
+//! A `RemoteEndpoint` on each side of the link carries the messages of a single actor type.
+//! Messages are serialized with postcard and framed with COBS, so that every frame ends with
+//! a zero byte. Requests carry an id that is echoed in the response, so that a request made
+//! through a `RemoteAddress` completes with the response of the actor on the other side.
+
+use super::{
+    actor::{Actor, ActorContext, ActorSpawner, Address, MessageChannel},
+    package::Package,
+    signal::{SignalFuture, SignalSlot},
+    util::{Capacity, ImmediateFuture, Slots},
+};
+use crate::traits::tcp::TcpSocket;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::Poll;
+use embassy::{
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt},
+    time::{Duration, Timer},
+};
+use futures::{
+    future::{poll_fn, select, Either},
+    pin_mut,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// A message or response that can be sent to another device.
+pub trait RemoteMessage: Serialize + DeserializeOwned {}
+
+impl<T> RemoteMessage for T where T: Serialize + DeserializeOwned {}
+
+/// Max number of requests through a link awaiting a response at the same time.
+type MaxPendingRequests = Slots<4>;
+
+/// Max number of frames waiting to be written to the transport.
+type MaxOutboundFrames = Slots<4>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RemoteError {
+    /// Reading from or writing to the transport failed.
+    Transport,
+    /// All requests that may be pending at the same time are in progress.
+    NoAvailableSignal,
+    /// The queue of frames to be written to the transport is full.
+    QueueFull,
+    /// The request could not be delivered, or there is no actor to handle it on the
+    /// other side of the link.
+    Abandoned,
+}
+
+/// A byte stream that remote messages are framed over.
+pub trait Transport {
+    type ReadableFuture<'m>: Future<Output = ()>
+    where
+        Self: 'm;
+    /// Wait until there may be data to read. The endpoint drops the returned future when it
+    /// has a frame to write, so it must not consume any data.
+    fn readable<'m>(&'m mut self) -> Self::ReadableFuture<'m>;
+
+    type ReadFuture<'m>: Future<Output = Result<usize, RemoteError>>
+    where
+        Self: 'm;
+    /// Read the data that is available, which may be none.
+    fn read<'m>(&'m mut self, buf: &'m mut [u8]) -> Self::ReadFuture<'m>;
+
+    type WriteFuture<'m>: Future<Output = Result<(), RemoteError>>
+    where
+        Self: 'm;
+    /// Write all of the provided data.
+    fn write<'m>(&'m mut self, buf: &'m [u8]) -> Self::WriteFuture<'m>;
+}
+
+/// A transport over a buffered `embassy::io` stream, such as a buffered UART.
+pub struct StreamTransport<S>
+where
+    S: AsyncBufRead + AsyncWrite + Unpin + 'static,
+{
+    stream: S,
+}
+
+impl<S> StreamTransport<S>
+where
+    S: AsyncBufRead + AsyncWrite + Unpin + 'static,
+{
+    pub fn new(stream: S) -> Self {
+        Self { stream }
+    }
+}
+
+impl<S> Transport for StreamTransport<S>
+where
+    S: AsyncBufRead + AsyncWrite + Unpin + 'static,
+{
+    #[rustfmt::skip]
+    type ReadableFuture<'m> where S: 'm = impl Future<Output = ()> + 'm;
+    fn readable<'m>(&'m mut self) -> Self::ReadableFuture<'m> {
+        // Filling the buffer does not consume any data
+        poll_fn(move |cx| Pin::new(&mut self.stream).poll_fill_buf(cx).map(|_| ()))
+    }
+
+    #[rustfmt::skip]
+    type ReadFuture<'m> where S: 'm = impl Future<Output = Result<usize, RemoteError>> + 'm;
+    fn read<'m>(&'m mut self, buf: &'m mut [u8]) -> Self::ReadFuture<'m> {
+        async move {
+            self.stream
+                .read(buf)
+                .await
+                .map_err(|_| RemoteError::Transport)
+        }
+    }
+
+    #[rustfmt::skip]
+    type WriteFuture<'m> where S: 'm = impl Future<Output = Result<(), RemoteError>> + 'm;
+    fn write<'m>(&'m mut self, buf: &'m [u8]) -> Self::WriteFuture<'m> {
+        async move {
+            self.stream
+                .write_all(buf)
+                .await
+                .map_err(|_| RemoteError::Transport)
+        }
+    }
+}
+
+/// A transport over a connected `TcpSocket`.
+///
+/// Sockets have no way of waiting for incoming data, so the socket is read every
+/// `poll_interval` while there are no frames to write.
+pub struct TcpTransport<S>
+where
+    S: TcpSocket + 'static,
+{
+    socket: S,
+    poll_interval: Duration,
+}
+
+impl<S> TcpTransport<S>
+where
+    S: TcpSocket + 'static,
+{
+    pub fn new(socket: S, poll_interval: Duration) -> Self {
+        Self {
+            socket,
+            poll_interval,
+        }
+    }
+}
+
+impl<S> Transport for TcpTransport<S>
+where
+    S: TcpSocket + 'static,
+{
+    #[rustfmt::skip]
+    type ReadableFuture<'m> where S: 'm = Timer;
+    fn readable<'m>(&'m mut self) -> Self::ReadableFuture<'m> {
+        Timer::after(self.poll_interval)
+    }
+
+    #[rustfmt::skip]
+    type ReadFuture<'m> where S: 'm = impl Future<Output = Result<usize, RemoteError>> + 'm;
+    fn read<'m>(&'m mut self, buf: &'m mut [u8]) -> Self::ReadFuture<'m> {
+        async move {
+            self.socket
+                .read(buf)
+                .await
+                .map_err(|_| RemoteError::Transport)
+        }
+    }
+
+    #[rustfmt::skip]
+    type WriteFuture<'m> where S: 'm = impl Future<Output = Result<(), RemoteError>> + 'm;
+    fn write<'m>(&'m mut self, buf: &'m [u8]) -> Self::WriteFuture<'m> {
+        async move {
+            let mut written = 0;
+            while written < buf.len() {
+                match self.socket.write(&buf[written..]).await {
+                    Ok(len) if len > 0 => written += len,
+                    _ => return Err(RemoteError::Transport),
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+/// A frame sent over the link.
+#[derive(Serialize, Deserialize)]
+enum Frame<M, R> {
+    Request(u8, M),
+    Notify(M),
+    Response(u8, R),
+    Abandoned(u8),
+}
+
+type ActorFrame<A> = Frame<<A as Actor>::Message<'static>, <A as Actor>::Response>;
+
+/// A request from the other side of the link that the local actor is handling.
+struct InboundRequest<A: Actor + 'static> {
+    // The id to respond with, which is cleared when the other side gives up on the request
+    id: Option<u8>,
+    response: SignalFuture<'static, A::Response>,
+}
+
+/// Wait for the local actor to respond to one of the requests from the other side, completing
+/// with the frame to reply with. Responses to requests the other side gave up on are discarded.
+fn replied<A: Actor + 'static>(
+    inbound: &mut [Option<InboundRequest<A>>],
+) -> impl Future<Output = Option<ActorFrame<A>>> + '_ {
+    poll_fn(move |cx| {
+        for slot in inbound.iter_mut() {
+            if let Some(request) = slot {
+                if let Poll::Ready(response) = Pin::new(&mut request.response).poll(cx) {
+                    let id = request.id;
+                    *slot = None;
+                    return Poll::Ready(id.map(|id| match response {
+                        Some(response) => Frame::Response(id, response),
+                        None => Frame::Abandoned(id),
+                    }));
+                }
+            }
+        }
+        Poll::Pending
+    })
+}
+
+/// The state of a link shared between its endpoint and remote addresses.
+struct Link<A: Actor + 'static> {
+    outbound: MessageChannel<'static, ActorFrame<A>, MaxOutboundFrames>,
+    // Indexed by the id of the request
+    pending: <MaxPendingRequests as Capacity>::Array<SignalSlot<A::Response>>,
+}
+
+impl<A: Actor + 'static> Link<A> {
+    fn new() -> Self {
+        Self {
+            outbound: MessageChannel::new(),
+            pending: MaxPendingRequests::array(SignalSlot::default),
+        }
+    }
+
+    /// Acquire a signal slot for a request, along with the id of the request.
+    fn acquire_signal(&self) -> Result<(u8, &SignalSlot<A::Response>), RemoteError> {
+        self.pending
+            .as_ref()
+            .iter()
+            .enumerate()
+            .find(|(_, signal)| signal.acquire())
+            .map(|(id, signal)| (id as u8, signal))
+            .ok_or(RemoteError::NoAvailableSignal)
+    }
+
+    /// The signal slot of a request awaiting a response, ignoring responses to unknown requests.
+    fn pending(&self, id: u8) -> Option<&SignalSlot<A::Response>> {
+        self.pending
+            .as_ref()
+            .get(id as usize)
+            .filter(|signal| signal.is_pending())
+    }
+
+    /// Drop the frames waiting to be written and abandon every request awaiting a response,
+    /// as its response may never arrive. This also frees the slots of requests that were
+    /// dropped by their requester, which would otherwise wait for the response forever.
+    fn reset(&self) {
+        while self.outbound.try_receive().is_ok() {}
+        for signal in self.pending.as_ref().iter() {
+            if signal.is_pending() {
+                signal.abandon();
+            }
+        }
+    }
+}
+
+/// A handle to an actor on the other side of a link, obtained with `RemoteEndpoint::address`.
+pub struct RemoteAddress<A: Actor + 'static> {
+    link: &'static Link<A>,
+}
+
+impl<A: Actor + 'static> RemoteAddress<A> {
+    /// Perform a request to the actor on the other side of the link, completing with its
+    /// response. An error is returned if the request cannot be enqueued for the link, or
+    /// if it could not be handled on the other side.
+    ///
+    /// Unlike `Address::request`, the returned future may be dropped at any time, and the
+    /// response is then dropped when it arrives. Requests are abandoned if the transport
+    /// fails or the endpoint is restarted, as their responses may have been lost.
+    pub async fn request(&self, message: A::Message<'static>) -> Result<A::Response, RemoteError> {
+        let (id, signal) = self.link.acquire_signal()?;
+        if self
+            .link
+            .outbound
+            .send(Frame::Request(id, message))
+            .is_err()
+        {
+            signal.release();
+            return Err(RemoteError::QueueFull);
+        }
+        SignalFuture::new(signal)
+            .await
+            .ok_or(RemoteError::Abandoned)
+    }
+
+    /// Perform a notification of the actor on the other side of the link. An error is
+    /// returned if the notification cannot be enqueued for the link.
+    ///
+    /// Notifications that cannot be delivered on the other side are dropped.
+    pub fn notify(&self, message: A::Message<'static>) -> Result<(), RemoteError> {
+        self.link
+            .outbound
+            .send(Frame::Notify(message))
+            .map_err(|_| RemoteError::QueueFull)
+    }
+}
+
+impl<A: Actor + 'static> Copy for RemoteAddress<A> {}
+
+impl<A: Actor + 'static> Clone for RemoteAddress<A> {
+    fn clone(&self) -> Self {
+        Self { link: self.link }
+    }
+}
+
+/// One side of a link carrying the messages of actor `A` over a transport, in frames of at
+/// most `FRAME` bytes.
+///
+/// The endpoint is configured with the local actor handling the requests and notifications
+/// from the other side, if any. Messages for the actor on the other side are sent through
+/// the `RemoteAddress` of the endpoint.
+// rustfmt drops the default of the const parameter
+#[rustfmt::skip]
+pub struct RemoteEndpoint<A, T, const FRAME: usize = 128>
+where
+    A: Actor + 'static,
+    A::Message<'static>: RemoteMessage,
+    A::Response: RemoteMessage,
+    T: Transport + 'static,
+{
+    link: Link<A>,
+    endpoint: ActorContext<'static, RemoteEndpointActor<A, T, FRAME>>,
+}
+
+impl<A, T, const FRAME: usize> RemoteEndpoint<A, T, FRAME>
+where
+    A: Actor + 'static,
+    A::Message<'static>: RemoteMessage,
+    A::Response: RemoteMessage,
+    T: Transport + 'static,
+{
+    pub fn new(transport: T) -> Self {
+        Self {
+            link: Link::new(),
+            endpoint: ActorContext::new(RemoteEndpointActor::new(transport)),
+        }
+    }
+
+    /// A handle to the actor on the other side of the link, which may only be used once
+    /// the endpoint has been mounted.
+    pub fn address(&'static self) -> RemoteAddress<A> {
+        RemoteAddress { link: &self.link }
+    }
+}
+
+impl<A, T, const FRAME: usize> Package for RemoteEndpoint<A, T, FRAME>
+where
+    A: Actor + 'static,
+    A::Message<'static>: RemoteMessage,
+    A::Response: RemoteMessage,
+    T: Transport + 'static,
+{
+    type Primary = RemoteEndpointActor<A, T, FRAME>;
+    type Configuration = Option<Address<'static, A>>;
+    type ShutdownFuture = impl Future<Output = ()>;
+
+    fn mount<S: ActorSpawner>(
+        &'static self,
+        config: Self::Configuration,
+        spawner: S,
+    ) -> Address<Self::Primary> {
+        self.link.outbound.initialize();
+        self.endpoint.mount((self.address(), config), spawner)
+    }
+
+    fn shutdown(&'static self) -> Self::ShutdownFuture {
+        self.endpoint.shutdown()
+    }
+}
+
+/// The actor of a `RemoteEndpoint`, writing outbound frames and handling inbound frames.
+///
+/// Requests from the other side are passed on to the local actor without waiting for its
+/// response, so the link keeps carrying frames while they are handled, and the local actor
+/// may make requests through the same link while handling one. Requests arriving while as
+/// many as may be pending at the same time are in progress, or while the queue of the
+/// local actor is full, are abandoned.
+pub struct RemoteEndpointActor<A, T, const FRAME: usize>
+where
+    A: Actor + 'static,
+    T: Transport + 'static,
+{
+    transport: T,
+    remote: Option<RemoteAddress<A>>,
+    local: Option<Address<'static, A>>,
+    // Requests from the other side awaiting the response of the local actor
+    inbound: <MaxPendingRequests as Capacity>::Array<Option<InboundRequest<A>>>,
+    rx: [u8; FRAME],
+    // Number of bytes received that are not yet part of a complete frame
+    rx_len: usize,
+    tx: [u8; FRAME],
+}
+
+impl<A, T, const FRAME: usize> RemoteEndpointActor<A, T, FRAME>
+where
+    A: Actor + 'static,
+    A::Message<'static>: RemoteMessage,
+    A::Response: RemoteMessage,
+    T: Transport + 'static,
+{
+    fn new(transport: T) -> Self {
+        Self {
+            transport,
+            remote: None,
+            local: None,
+            inbound: MaxPendingRequests::array(|| None),
+            rx: [0; FRAME],
+            rx_len: 0,
+            tx: [0; FRAME],
+        }
+    }
+
+    fn link(&self) -> &'static Link<A> {
+        self.remote.unwrap().link
+    }
+
+    /// Serialize a frame and write it to the transport. A request that cannot be serialized
+    /// is abandoned, and the link is reset if the transport fails.
+    async fn send(&mut self, frame: ActorFrame<A>) {
+        let request = match &frame {
+            Frame::Request(id, _) => Some(*id),
+            _ => None,
+        };
+        match postcard::to_slice_cobs(&frame, &mut self.tx) {
+            Ok(encoded) => {
+                if self.transport.write(encoded).await.is_err() {
+                    warn!("Error writing frame to transport");
+                    self.link().reset();
+                }
+            }
+            Err(_) => {
+                warn!("Error serializing frame larger than the frame size");
+                if let Some(signal) = request.and_then(|id| self.link().pending(id)) {
+                    signal.abandon();
+                }
+            }
+        }
+    }
+
+    /// Read the data available from the transport, handling every complete frame.
+    async fn receive(&mut self) {
+        match self.transport.read(&mut self.rx[self.rx_len..]).await {
+            Ok(len) => self.rx_len += len,
+            Err(_) => {
+                warn!("Error reading from transport");
+                self.rx_len = 0;
+                self.link().reset();
+                return;
+            }
+        }
+        while let Some(end) = self.rx[..self.rx_len].iter().position(|b| *b == 0) {
+            let frame: Option<ActorFrame<A>> = postcard::from_bytes_cobs(&mut self.rx[..=end]).ok();
+            self.rx.copy_within(end + 1..self.rx_len, 0);
+            self.rx_len -= end + 1;
+            match frame {
+                Some(frame) => self.dispatch(frame).await,
+                None => warn!("Discarding malformed frame"),
+            }
+        }
+        if self.rx_len == FRAME {
+            warn!("Discarding frame larger than the frame size");
+            self.rx_len = 0;
+        }
+    }
+
+    /// Pass a request from the other side on to the local actor, returning the id of the
+    /// request if it cannot be handled.
+    fn forward(&mut self, id: u8, message: A::Message<'static>) -> Result<(), u8> {
+        let local = self.local.ok_or(id)?;
+        let slot = self
+            .inbound
+            .as_mut()
+            .iter_mut()
+            .find(|slot| slot.is_none())
+            .ok_or(id)?;
+        let response = local.try_request_cancellable(message).map_err(|_| id)?;
+        slot.replace(InboundRequest {
+            id: Some(id),
+            response,
+        });
+        Ok(())
+    }
+
+    async fn dispatch(&mut self, frame: ActorFrame<A>) {
+        match frame {
+            Frame::Request(id, message) => {
+                if let Err(id) = self.forward(id, message) {
+                    self.send(Frame::Abandoned(id)).await;
+                }
+            }
+            Frame::Notify(message) => {
+                if let Some(local) = self.local {
+                    if local.notify(message).is_err() {
+                        warn!("Dropping notification from remote endpoint");
+                    }
+                }
+            }
+            Frame::Response(id, response) => {
+                if let Some(signal) = self.link().pending(id) {
+                    signal.signal(response);
+                }
+            }
+            Frame::Abandoned(id) => {
+                if let Some(signal) = self.link().pending(id) {
+                    signal.abandon();
+                }
+            }
+        }
+    }
+}
+
+impl<A, T, const FRAME: usize> Actor for RemoteEndpointActor<A, T, FRAME>
+where
+    A: Actor + 'static,
+    A::Message<'static>: RemoteMessage,
+    A::Response: RemoteMessage,
+    T: Transport + 'static,
+{
+    type Configuration = (RemoteAddress<A>, Option<Address<'static, A>>);
+    type Message<'m> = ();
+    type OnStartFuture<'m> = impl Future<Output = ()> + 'm;
+    type OnMessageFuture<'m> = ImmediateFuture;
+
+    fn on_mount(&mut self, _: Address<'static, Self>, config: Self::Configuration) {
+        self.remote.replace(config.0);
+        self.local = config.1;
+    }
+
+    fn on_restart(&mut self) {
+        // Anything in flight may have been lost along with the partially received frame
+        self.rx_len = 0;
+        self.link().reset();
+        // The local actor still responds to requests in progress, but the other side has
+        // given up on them, and their ids may be reused.
+        for request in self.inbound.as_mut().iter_mut().flatten() {
+            request.id.take();
+        }
+    }
+
+    fn on_start(self: Pin<&'_ mut Self>) -> Self::OnStartFuture<'_> {
+        async move {
+            let this = unsafe { self.get_unchecked_mut() };
+            let link = this.link();
+            loop {
+                let event = {
+                    let frame = link.outbound.receive();
+                    let readable = this.transport.readable();
+                    pin_mut!(readable);
+                    let replied = replied(this.inbound.as_mut());
+                    pin_mut!(replied);
+                    match select(select(frame, replied), readable).await {
+                        Either::Left((Either::Left((frame, _)), _)) => Some(Some(frame)),
+                        Either::Left((Either::Right((reply, _)), _)) => Some(reply),
+                        Either::Right(_) => None,
+                    }
+                };
+                match event {
+                    Some(Some(frame)) => this.send(frame).await,
+                    // A response to a request the other side gave up on
+                    Some(None) => {}
+                    None => this.receive().await,
+                }
+            }
+        }
+    }
+
+    fn on_message<'m>(self: Pin<&'m mut Self>, _: ()) -> Self::OnMessageFuture<'m> {
+        ImmediateFuture::new()
+    }
+}
@@ -1,47 +1,74 @@
-use atomic_polyfill::{AtomicBool, Ordering};
+use super::util::WakerSet;
+use atomic_polyfill::{AtomicU8, Ordering};
 use core::future::Future;
 use core::pin::Pin;
-use core::task::{Context, Poll};
+use core::task::{Context, Poll, Waker};
 use embassy::util::Signal;
+use heapless::consts;
+
+// States of a signal slot
+const FREE: u8 = 0;
+const ACQUIRED: u8 = 1;
+const SIGNALED: u8 = 2;
+const CANCELLED: u8 = 3;
+
+/// The number of tasks that can wait for a slot to be released before they fall back to polling.
+///
+/// A task using `request_async` beyond this number is woken again as soon as it registers,
+/// so it busy-polls, keeping the executor awake, until one of the slots is released.
+type MaxWaitingRequesters = consts::U4;
 
 pub struct SignalFuture<'s, T: Send> {
     signal: &'s SignalSlot<T>,
+    completed: bool,
 }
 
 impl<'s, T: Send> SignalFuture<'s, T> {
     pub fn new(signal: &'s SignalSlot<T>) -> Self {
-        Self { signal }
-    }
-
-    pub fn release(&self) {
-        self.signal.release();
+        Self {
+            signal,
+            completed: false,
+        }
     }
 }
 
-// impl Unpin for SignalFuture<'_, '_> {}
-
 impl<T: Send> Future for SignalFuture<'_, T> {
-    type Output = T;
+    /// The signaled value, or `None` if the signaling side abandoned the slot.
+    type Output = Option<T>;
 
-    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        self.signal.poll_wait(cx)
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let result = self.signal.poll_wait(cx);
+        if result.is_ready() {
+            self.completed = true;
+            self.signal.release();
+        }
+        result
     }
 }
 
 impl<T: Send> Drop for SignalFuture<'_, T> {
     fn drop(&mut self) {
-        self.signal.release();
+        // If the value never arrived, the slot must not be handed out again until
+        // the signaling side is done with it.
+        if !self.completed {
+            self.signal.cancel();
+        }
     }
 }
 
 pub struct SignalSlot<T: Send> {
-    free: AtomicBool,
-    signal: Signal<T>,
+    state: AtomicU8,
+    signal: Signal<Option<T>>,
+    release_wakers: WakerSet<MaxWaitingRequesters>,
 }
 
 impl<T: Send> SignalSlot<T> {
     pub fn acquire(&self) -> bool {
-        if self.free.swap(false, Ordering::AcqRel) {
+        if self
+            .state
+            .compare_exchange(FREE, ACQUIRED, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+        {
             self.signal.reset();
             true
         } else {
@@ -49,24 +76,88 @@ impl<T: Send> SignalSlot<T> {
         }
     }
 
-    pub fn poll_wait(&self, cx: &mut Context<'_>) -> Poll<T> {
+    pub fn poll_wait(&self, cx: &mut Context<'_>) -> Poll<Option<T>> {
         self.signal.poll_wait(cx)
     }
 
+    /// Signal the value to the waiting side. If the waiting side has cancelled,
+    /// the value is dropped and the slot is released.
     pub fn signal(&self, value: T) {
-        self.signal.signal(value)
+        self.complete(Some(value))
+    }
+
+    /// Let the waiting side know that no value will ever be signaled.
+    pub fn abandon(&self) {
+        self.complete(None)
+    }
+
+    fn complete(&self, value: Option<T>) {
+        critical_section::with(|_| {
+            if self.state.load(Ordering::Acquire) == CANCELLED {
+                self.release();
+            } else {
+                self.state.store(SIGNALED, Ordering::Release);
+                self.signal.signal(value)
+            }
+        })
+    }
+
+    /// Cancel waiting for the value. If the value has already been signaled, the
+    /// slot is released immediately, otherwise it is released by the signaling side.
+    pub fn cancel(&self) {
+        critical_section::with(|_| match self.state.load(Ordering::Acquire) {
+            SIGNALED => {
+                self.signal.reset();
+                self.release();
+            }
+            ACQUIRED => {
+                self.state.store(CANCELLED, Ordering::Release);
+            }
+            _ => {}
+        })
+    }
+
+    /// Returns true if the waiting side has cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.state.load(Ordering::Acquire) == CANCELLED
+    }
+
+    /// Returns true if the slot has been acquired and no value has been signaled yet.
+    pub fn is_pending(&self) -> bool {
+        matches!(self.state.load(Ordering::Acquire), ACQUIRED | CANCELLED)
+    }
+
+    /// Release the slot if the waiting side has cancelled, returning true if it did.
+    pub fn release_if_cancelled(&self) -> bool {
+        if self
+            .state
+            .compare_exchange(CANCELLED, FREE, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+        {
+            self.release_wakers.wake();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Register a waker to be woken the next time this slot is released.
+    pub fn register_release(&self, waker: &Waker) {
+        self.release_wakers.register(waker);
     }
 
     pub fn release(&self) {
-        self.free.store(true, Ordering::Release)
+        self.state.store(FREE, Ordering::Release);
+        self.release_wakers.wake();
     }
 }
 
 impl<T: Send> Default for SignalSlot<T> {
     fn default() -> Self {
         Self {
-            free: AtomicBool::new(true),
+            state: AtomicU8::new(FREE),
             signal: Signal::new(),
+            release_wakers: WakerSet::new(),
         }
     }
 }
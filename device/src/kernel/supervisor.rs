@@ -0,0 +1,135 @@
+use super::{
+    actor::{Actor, ActorContext, ActorSpawner, Address},
+    package::Package,
+};
+use core::future::Future;
+use core::pin::Pin;
+use embassy::time::{Duration, Instant, Timer};
+
+/// Decides what a `Supervisor` does when its actor reports a failure.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RestartPolicy {
+    /// Stop the actor.
+    Never,
+    /// Restart the actor immediately.
+    Always,
+    /// Restart the actor after a delay, starting at `initial` and doubling for each
+    /// consecutive failure up to `max`. The delay is reset once the actor has been
+    /// running for `max` without failing.
+    Backoff { initial: Duration, max: Duration },
+}
+
+/// A package that supervises an actor, applying a `RestartPolicy` whenever the
+/// actor reports a failure using `Address::fail`.
+///
+/// Restarting drops any message processing in progress and calls `on_stop` and
+/// `on_restart` on the actor, before starting it again with `on_start`. Messages
+/// already in the queue are kept. Requests being processed at the time of a restart
+/// are abandoned, and their requesters receive `ActorError::Abandoned`.
+///
+/// Panics can not be caught in a no_std environment, so only failures reported by
+/// the actor itself are supervised.
+pub struct Supervisor<A: Actor + 'static> {
+    actor: ActorContext<'static, A>,
+    supervisor: ActorContext<'static, SupervisorActor<A>>,
+}
+
+impl<A: Actor + 'static> Supervisor<A> {
+    pub fn new(actor: A, policy: RestartPolicy) -> Self {
+        Self {
+            actor: ActorContext::new(actor),
+            supervisor: ActorContext::new(SupervisorActor::new(policy)),
+        }
+    }
+}
+
+impl<A: Actor + 'static> Package for Supervisor<A> {
+    type Primary = A;
+    type Configuration = A::Configuration;
+    type ShutdownFuture = impl Future<Output = ()>;
+
+    fn mount<S: ActorSpawner>(
+        &'static self,
+        config: Self::Configuration,
+        spawner: S,
+    ) -> Address<Self::Primary> {
+        let address = self.actor.mount(config, spawner);
+        self.supervisor.mount(&self.actor, spawner);
+        address
+    }
+
+    fn shutdown(&'static self) -> Self::ShutdownFuture {
+        async move {
+            self.supervisor.shutdown().await;
+            self.actor.shutdown().await;
+        }
+    }
+}
+
+/// Actor waiting for failures of the supervised actor.
+pub struct SupervisorActor<A: Actor + 'static> {
+    policy: RestartPolicy,
+    actor: Option<&'static ActorContext<'static, A>>,
+}
+
+impl<A: Actor + 'static> SupervisorActor<A> {
+    pub fn new(policy: RestartPolicy) -> Self {
+        Self {
+            policy,
+            actor: None,
+        }
+    }
+}
+
+impl<A: Actor + 'static> Unpin for SupervisorActor<A> {}
+
+impl<A: Actor + 'static> Actor for SupervisorActor<A> {
+    type Configuration = &'static ActorContext<'static, A>;
+    type OnStartFuture<'m> = impl Future<Output = ()> + 'm;
+    type OnMessageFuture<'m> = impl Future<Output = ()> + 'm;
+
+    fn on_mount(&mut self, _: Address<'static, Self>, config: Self::Configuration) {
+        self.actor.replace(config);
+    }
+
+    fn on_start(self: Pin<&'_ mut Self>) -> Self::OnStartFuture<'_> {
+        async move {
+            let actor = self.actor.unwrap();
+            let mut delay = None;
+            let mut started = Instant::now();
+            loop {
+                actor.wait_failure().await;
+                match self.policy {
+                    RestartPolicy::Never => {
+                        warn!("Actor failed, stopping");
+                        actor.stop();
+                        break;
+                    }
+                    RestartPolicy::Always => {
+                        warn!("Actor failed, restarting");
+                        actor.restart();
+                    }
+                    RestartPolicy::Backoff { initial, max } => {
+                        if Instant::now().duration_since(started) >= max {
+                            delay = None;
+                        }
+                        let wait = match delay {
+                            None => initial,
+                            Some(previous) => core::cmp::min(previous * 2, max),
+                        };
+                        delay.replace(wait);
+                        warn!("Actor failed, restarting in {} ms", wait.as_millis());
+                        Timer::after(wait).await;
+                        actor.restart();
+                    }
+                }
+                started = Instant::now();
+            }
+        }
+    }
+
+    fn on_message<'m>(self: Pin<&'m mut Self>, _: Self::Message<'m>) -> Self::OnMessageFuture<'m> {
+        async move {}
+    }
+}
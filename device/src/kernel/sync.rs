@@ -0,0 +1,430 @@
+//! Primitives for sharing resources between actors without wrapping them in an actor.
+//!
+//! Waiters are served in the order they started waiting, as long as no more than eight
+//! tasks are waiting at a time. Tasks beyond that busy-poll, keeping the executor awake,
+//! until there is room in the queue, and are served after the tasks queued before them.
+
+use core::{
+    cell::{Cell, RefCell, UnsafeCell},
+    future::Future,
+    ops::{Deref, DerefMut},
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+use heapless::{consts, Vec};
+
+/// The number of tasks that can wait on a primitive in FIFO order.
+///
+/// A task waiting beyond this number is woken again as soon as it polls, so it busy-polls
+/// until there is room in the queue.
+type MaxWaiters = consts::U8;
+
+struct Waiter {
+    id: u32,
+    waker: Waker,
+}
+
+/// A FIFO queue of up to `MaxWaiters` tasks waiting to acquire a resource. Tasks finding
+/// the queue full are not queued, but woken right away to poll again.
+struct WaitQueue {
+    waiters: RefCell<Vec<Waiter, MaxWaiters>>,
+    next_id: Cell<u32>,
+}
+
+impl WaitQueue {
+    fn new() -> Self {
+        Self {
+            waiters: RefCell::new(Vec::new()),
+            next_id: Cell::new(0),
+        }
+    }
+
+    /// Wait until `try_acquire` succeeds while this task is first in line.
+    fn acquire<F: FnMut() -> bool>(&self, try_acquire: F) -> Acquire<'_, F> {
+        Acquire {
+            queue: self,
+            try_acquire,
+            id: None,
+        }
+    }
+
+    /// Returns true if no task is waiting.
+    fn is_empty(&self) -> bool {
+        critical_section::with(|_| self.waiters.borrow().is_empty())
+    }
+
+    /// Wake the first task in line, letting it try to acquire the resource.
+    fn wake(&self) {
+        critical_section::with(|_| {
+            if let Some(waiter) = self.waiters.borrow().first() {
+                waiter.waker.wake_by_ref();
+            }
+        })
+    }
+
+    fn poll_acquire<F: FnMut() -> bool>(
+        &self,
+        cx: &mut Context<'_>,
+        id: &mut Option<u32>,
+        try_acquire: &mut F,
+    ) -> Poll<()> {
+        critical_section::with(|_| {
+            let mut waiters = self.waiters.borrow_mut();
+            match *id {
+                None => {
+                    if waiters.is_empty() && try_acquire() {
+                        return Poll::Ready(());
+                    }
+                    let waiter = Waiter {
+                        id: self.next_id.get(),
+                        waker: cx.waker().clone(),
+                    };
+                    if waiters.push(waiter).is_ok() {
+                        id.replace(self.next_id.get());
+                        self.next_id.set(self.next_id.get().wrapping_add(1));
+                    } else {
+                        cx.waker().wake_by_ref();
+                    }
+                    Poll::Pending
+                }
+                Some(current) => {
+                    if waiters[0].id == current && try_acquire() {
+                        remove(&mut waiters, 0);
+                        id.take();
+                        // The next task in line may be able to acquire the resource as well
+                        if let Some(next) = waiters.first() {
+                            next.waker.wake_by_ref();
+                        }
+                        Poll::Ready(())
+                    } else {
+                        let waiter = waiters.iter_mut().find(|w| w.id == current).unwrap();
+                        if !waiter.waker.will_wake(cx.waker()) {
+                            waiter.waker = cx.waker().clone();
+                        }
+                        Poll::Pending
+                    }
+                }
+            }
+        })
+    }
+
+    /// Leave the queue without acquiring the resource.
+    fn cancel(&self, id: u32) {
+        critical_section::with(|_| {
+            let mut waiters = self.waiters.borrow_mut();
+            if let Some(index) = waiters.iter().position(|w| w.id == id) {
+                remove(&mut waiters, index);
+                if index == 0 {
+                    if let Some(next) = waiters.first() {
+                        next.waker.wake_by_ref();
+                    }
+                }
+            }
+        })
+    }
+}
+
+/// Remove a waiter, keeping the order of the waiters after it.
+fn remove(waiters: &mut Vec<Waiter, MaxWaiters>, index: usize) {
+    waiters[index..].rotate_left(1);
+    waiters.pop();
+}
+
+struct Acquire<'a, F: FnMut() -> bool> {
+    queue: &'a WaitQueue,
+    try_acquire: F,
+    id: Option<u32>,
+}
+
+// The closure is never pinned
+impl<'a, F: FnMut() -> bool> Unpin for Acquire<'a, F> {}
+
+impl<'a, F: FnMut() -> bool> Future for Acquire<'a, F> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        this.queue
+            .poll_acquire(cx, &mut this.id, &mut this.try_acquire)
+    }
+}
+
+impl<'a, F: FnMut() -> bool> Drop for Acquire<'a, F> {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            self.queue.cancel(id);
+        }
+    }
+}
+
+/// A counting semaphore, limiting the number of tasks using a resource at the same time.
+///
+/// Up to eight tasks waiting for a permit are served in FIFO order, any further tasks
+/// busy-poll until there is room in the queue.
+pub struct Semaphore {
+    permits: Cell<usize>,
+    waiters: WaitQueue,
+}
+
+impl Semaphore {
+    pub fn new(permits: usize) -> Self {
+        Self {
+            permits: Cell::new(permits),
+            waiters: WaitQueue::new(),
+        }
+    }
+
+    /// Wait for a permit, which is returned when the permit is dropped.
+    pub async fn acquire(&self) -> SemaphorePermit<'_> {
+        self.waiters.acquire(|| self.try_take()).await;
+        SemaphorePermit { semaphore: self }
+    }
+
+    /// Take a permit if one is available and no task is waiting for one.
+    pub fn try_acquire(&self) -> Option<SemaphorePermit<'_>> {
+        critical_section::with(|_| {
+            if self.waiters.is_empty() && self.try_take() {
+                Some(SemaphorePermit { semaphore: self })
+            } else {
+                None
+            }
+        })
+    }
+
+    /// The number of permits currently available.
+    pub fn available(&self) -> usize {
+        critical_section::with(|_| self.permits.get())
+    }
+
+    fn try_take(&self) -> bool {
+        let permits = self.permits.get();
+        if permits > 0 {
+            self.permits.set(permits - 1);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn release(&self) {
+        critical_section::with(|_| self.permits.set(self.permits.get() + 1));
+        self.waiters.wake();
+    }
+}
+
+// Safety: All state is accessed within critical sections.
+unsafe impl Send for Semaphore {}
+unsafe impl Sync for Semaphore {}
+
+/// A permit acquired from a `Semaphore`, returned to it when dropped.
+pub struct SemaphorePermit<'a> {
+    semaphore: &'a Semaphore,
+}
+
+impl<'a> Drop for SemaphorePermit<'a> {
+    fn drop(&mut self) {
+        self.semaphore.release();
+    }
+}
+
+/// A lock providing exclusive access to a value, for tasks taking turns in using
+/// a resource such as a peripheral.
+///
+/// Up to eight tasks waiting for the lock are served in FIFO order, any further tasks
+/// busy-poll until there is room in the queue.
+pub struct Mutex<T> {
+    semaphore: Semaphore,
+    value: UnsafeCell<T>,
+}
+
+impl<T> Mutex<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            semaphore: Semaphore::new(1),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Wait for the lock, which is released when the returned guard is dropped.
+    pub async fn lock(&self) -> MutexGuard<'_, T> {
+        let permit = self.semaphore.acquire().await;
+        MutexGuard {
+            mutex: self,
+            _permit: permit,
+        }
+    }
+
+    /// Take the lock if it is free and no task is waiting for it.
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        self.semaphore.try_acquire().map(|permit| MutexGuard {
+            mutex: self,
+            _permit: permit,
+        })
+    }
+
+    /// Get the value without locking, which is safe as the mutex is borrowed mutably.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+}
+
+// Safety: The value is only accessed by the holder of the lock.
+unsafe impl<T: Send> Send for Mutex<T> {}
+unsafe impl<T: Send> Sync for Mutex<T> {}
+
+/// Exclusive access to the value of a `Mutex`, releasing the lock when dropped.
+pub struct MutexGuard<'a, T> {
+    mutex: &'a Mutex<T>,
+    _permit: SemaphorePermit<'a>,
+}
+
+impl<'a, T> Deref for MutexGuard<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.value.get() }
+    }
+}
+
+impl<'a, T> DerefMut for MutexGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.value.get() }
+    }
+}
+
+/// A lock providing shared access to a value for any number of readers, or exclusive
+/// access for a single writer. A writer waiting for the lock blocks readers that
+/// arrive after it.
+///
+/// Up to eight tasks waiting for the lock are served in FIFO order, any further tasks
+/// busy-poll until there is room in the queue.
+pub struct RwLock<T> {
+    readers: Cell<usize>,
+    writer: Cell<bool>,
+    waiters: WaitQueue,
+    value: UnsafeCell<T>,
+}
+
+impl<T> RwLock<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            readers: Cell::new(0),
+            writer: Cell::new(false),
+            waiters: WaitQueue::new(),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Wait for shared access, which is released when the returned guard is dropped.
+    pub async fn read(&self) -> RwLockReadGuard<'_, T> {
+        self.waiters.acquire(|| self.try_take_read()).await;
+        RwLockReadGuard { lock: self }
+    }
+
+    /// Wait for exclusive access, which is released when the returned guard is dropped.
+    pub async fn write(&self) -> RwLockWriteGuard<'_, T> {
+        self.waiters.acquire(|| self.try_take_write()).await;
+        RwLockWriteGuard { lock: self }
+    }
+
+    /// Take shared access if the lock is not written and no task is waiting for it.
+    pub fn try_read(&self) -> Option<RwLockReadGuard<'_, T>> {
+        critical_section::with(|_| {
+            if self.waiters.is_empty() && self.try_take_read() {
+                Some(RwLockReadGuard { lock: self })
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Take exclusive access if the lock is free and no task is waiting for it.
+    pub fn try_write(&self) -> Option<RwLockWriteGuard<'_, T>> {
+        critical_section::with(|_| {
+            if self.waiters.is_empty() && self.try_take_write() {
+                Some(RwLockWriteGuard { lock: self })
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Get the value without locking, which is safe as the lock is borrowed mutably.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+
+    fn try_take_read(&self) -> bool {
+        if self.writer.get() {
+            false
+        } else {
+            self.readers.set(self.readers.get() + 1);
+            true
+        }
+    }
+
+    fn try_take_write(&self) -> bool {
+        if self.writer.get() || self.readers.get() > 0 {
+            false
+        } else {
+            self.writer.set(true);
+            true
+        }
+    }
+
+    fn release_read(&self) {
+        critical_section::with(|_| self.readers.set(self.readers.get() - 1));
+        self.waiters.wake();
+    }
+
+    fn release_write(&self) {
+        critical_section::with(|_| self.writer.set(false));
+        self.waiters.wake();
+    }
+}
+
+// Safety: The value is only accessed by the holders of the lock, and readers
+// only get shared references.
+unsafe impl<T: Send> Send for RwLock<T> {}
+unsafe impl<T: Send + Sync> Sync for RwLock<T> {}
+
+/// Shared access to the value of a `RwLock`, released when dropped.
+pub struct RwLockReadGuard<'a, T> {
+    lock: &'a RwLock<T>,
+}
+
+impl<'a, T> Deref for RwLockReadGuard<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<'a, T> Drop for RwLockReadGuard<'a, T> {
+    fn drop(&mut self) {
+        self.lock.release_read();
+    }
+}
+
+/// Exclusive access to the value of a `RwLock`, released when dropped.
+pub struct RwLockWriteGuard<'a, T> {
+    lock: &'a RwLock<T>,
+}
+
+impl<'a, T> Deref for RwLockWriteGuard<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<'a, T> DerefMut for RwLockWriteGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.value.get() }
+    }
+}
+
+impl<'a, T> Drop for RwLockWriteGuard<'a, T> {
+    fn drop(&mut self) {
+        self.lock.release_write();
+    }
+}
@@ -0,0 +1,390 @@
+//! Tracing of the messages flowing between actors, enabled with the `trace` feature.
+//!
+//! Every request and notification enqueued for an actor, and the start and finish of
+//! handling each message, is recorded with a timestamp in a buffer keeping the most recent
+//! `MAX_EVENTS` events. Actors are told apart by the address of their context, so that
+//! several instances of the same actor type can be followed. Messages enqueued while an
+//! actor is being polled are recorded as sent by that actor, including notifications from
+//! interrupts that preempt it. The events can be taken with `pop` and logged with `log`, or
+//! on `std` rendered as a Mermaid sequence diagram with `to_mermaid`.
+//!
+//! When the feature is disabled, all recording is a no-op.
+
+use super::actor::{Actor, ActorContext};
+#[cfg(feature = "trace")]
+use core::cell::{Cell, UnsafeCell};
+#[cfg(feature = "trace")]
+use embassy::time::Instant;
+
+/// Max number of events kept, after which the oldest events are overwritten.
+#[cfg(feature = "trace")]
+pub const MAX_EVENTS: usize = 64;
+
+#[cfg(feature = "trace")]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TraceEventKind {
+    /// A request was enqueued for the actor.
+    Request,
+    /// A notification was enqueued for the actor.
+    Notify,
+    /// The actor started handling a message.
+    Start,
+    /// The actor finished handling a message.
+    Finish,
+}
+
+/// An actor instance an event is about.
+#[cfg(feature = "trace")]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TraceActor {
+    /// Address of the context of the actor, which tells instances of the same type apart.
+    pub id: usize,
+    /// Type name of the actor.
+    pub name: &'static str,
+}
+
+#[cfg(feature = "trace")]
+impl TraceActor {
+    fn of<A: Actor>(context: &ActorContext<'_, A>) -> Self {
+        Self {
+            id: context as *const _ as usize,
+            name: core::any::type_name::<A>(),
+        }
+    }
+}
+
+#[cfg(feature = "trace")]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TraceEvent {
+    /// Time of the event, in ticks.
+    pub ticks: u64,
+    /// The actor the event happened to.
+    pub actor: TraceActor,
+    /// The actor that enqueued the message, or `None` if it was enqueued outside of an
+    /// actor or the event is not about enqueueing.
+    pub sender: Option<TraceActor>,
+    pub kind: TraceEventKind,
+}
+
+#[cfg(feature = "trace")]
+impl TraceEvent {
+    const EMPTY: TraceEvent = TraceEvent {
+        ticks: 0,
+        actor: TraceActor { id: 0, name: "" },
+        sender: None,
+        kind: TraceEventKind::Finish,
+    };
+
+    /// Log the event, as the time in microseconds, kind, actor and sender, each actor
+    /// followed by the address of its context.
+    pub fn log(&self) {
+        let micros = Instant::from_ticks(self.ticks).as_micros();
+        let kind = match self.kind {
+            TraceEventKind::Request => "request",
+            TraceEventKind::Notify => "notify",
+            TraceEventKind::Start => "start",
+            TraceEventKind::Finish => "finish",
+        };
+        let actor = short_name(self.actor.name);
+        match self.sender {
+            Some(sender) => info!(
+                "[trace] {} us {} {}@{} <- {}@{}",
+                micros,
+                kind,
+                actor,
+                self.actor.id,
+                short_name(sender.name),
+                sender.id
+            ),
+            None => info!("[trace] {} us {} {}@{}", micros, kind, actor, self.actor.id),
+        }
+    }
+}
+
+#[cfg(feature = "trace")]
+struct TraceBuffer {
+    events: UnsafeCell<[TraceEvent; MAX_EVENTS]>,
+    // Index of the oldest event and number of events, only accessed within critical sections.
+    head: Cell<usize>,
+    len: Cell<usize>,
+    // The actor currently being polled
+    current: Cell<Option<TraceActor>>,
+}
+
+// Safety: All state is only accessed within critical sections.
+#[cfg(feature = "trace")]
+unsafe impl Sync for TraceBuffer {}
+
+#[cfg(feature = "trace")]
+static BUFFER: TraceBuffer = TraceBuffer {
+    events: UnsafeCell::new([TraceEvent::EMPTY; MAX_EVENTS]),
+    head: Cell::new(0),
+    len: Cell::new(0),
+    current: Cell::new(None),
+};
+
+#[cfg(feature = "trace")]
+impl TraceBuffer {
+    fn push(&self, actor: TraceActor, kind: TraceEventKind) {
+        let ticks = Instant::now().as_ticks();
+        critical_section::with(|_| {
+            let sender = match kind {
+                TraceEventKind::Request | TraceEventKind::Notify => self.current.get(),
+                _ => None,
+            };
+            let event = TraceEvent {
+                ticks,
+                actor,
+                sender,
+                kind,
+            };
+            let (head, len) = (self.head.get(), self.len.get());
+            let events = unsafe { &mut *self.events.get() };
+            if len == MAX_EVENTS {
+                events[head] = event;
+                self.head.set((head + 1) % MAX_EVENTS);
+            } else {
+                events[(head + len) % MAX_EVENTS] = event;
+                self.len.set(len + 1);
+            }
+        })
+    }
+
+    fn pop(&self) -> Option<TraceEvent> {
+        critical_section::with(|_| {
+            let (head, len) = (self.head.get(), self.len.get());
+            if len == 0 {
+                return None;
+            }
+            self.head.set((head + 1) % MAX_EVENTS);
+            self.len.set(len - 1);
+            Some(unsafe { &*self.events.get() }[head])
+        })
+    }
+}
+
+/// Take the oldest recorded event.
+#[cfg(feature = "trace")]
+pub fn pop() -> Option<TraceEvent> {
+    BUFFER.pop()
+}
+
+/// Take and log every recorded event, oldest first.
+#[cfg(feature = "trace")]
+pub fn log() {
+    while let Some(event) = pop() {
+        event.log();
+    }
+}
+
+/// Take every recorded event, oldest first.
+#[cfg(all(feature = "trace", feature = "std"))]
+pub fn take() -> std::vec::Vec<TraceEvent> {
+    core::iter::from_fn(pop).collect()
+}
+
+/// Render events as a Mermaid sequence diagram, with messages drawn from sender to actor
+/// and actors activated while they handle a message.
+///
+/// Participants are named after the type of the actor, followed by a number for every
+/// further instance of the same type, such as `Led` and `Led2`.
+#[cfg(all(feature = "trace", feature = "std"))]
+pub fn to_mermaid(events: &[TraceEvent]) -> std::string::String {
+    use core::fmt::Write;
+    use std::{string::String, vec::Vec};
+    let mut diagram = String::from("sequenceDiagram\n");
+    // The buffer may have dropped the start of a message being handled
+    let mut active = Vec::new();
+    // Instances of the same actor type are numbered in the order they first appear
+    let mut instances: Vec<(usize, &str)> = Vec::new();
+    let mut participant = |actor: TraceActor| -> String {
+        let name = short_name(actor.name);
+        if !instances.iter().any(|(id, _)| *id == actor.id) {
+            instances.push((actor.id, name));
+        }
+        let number = instances
+            .iter()
+            .filter(|(_, n)| *n == name)
+            .position(|(id, _)| *id == actor.id)
+            .unwrap()
+            + 1;
+        match number {
+            1 => String::from(name),
+            _ => std::format!("{}{}", name, number),
+        }
+    };
+    for event in events {
+        let actor = participant(event.actor);
+        let sender = match event.sender {
+            Some(sender) => participant(sender),
+            None => String::from("External"),
+        };
+        let micros = Instant::from_ticks(event.ticks).as_micros();
+        let _ = match event.kind {
+            TraceEventKind::Request => {
+                writeln!(
+                    diagram,
+                    "    {}->>{}: request at {} us",
+                    sender, actor, micros
+                )
+            }
+            TraceEventKind::Notify => {
+                writeln!(
+                    diagram,
+                    "    {}-){}: notify at {} us",
+                    sender, actor, micros
+                )
+            }
+            TraceEventKind::Start => {
+                active.push(event.actor.id);
+                writeln!(diagram, "    activate {}", actor)
+            }
+            TraceEventKind::Finish => match active.iter().position(|id| *id == event.actor.id) {
+                Some(index) => {
+                    active.remove(index);
+                    writeln!(diagram, "    deactivate {}", actor)
+                }
+                None => Ok(()),
+            },
+        };
+    }
+    diagram
+}
+
+/// The name of a type without its module path and generic parameters.
+#[cfg(feature = "trace")]
+fn short_name(name: &'static str) -> &'static str {
+    let name = name.split('<').next().unwrap_or(name);
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+/// Marks an actor as being polled until dropped, see `enter`.
+#[cfg(feature = "trace")]
+pub(crate) struct Scope {
+    previous: Option<TraceActor>,
+}
+
+#[cfg(feature = "trace")]
+impl Drop for Scope {
+    fn drop(&mut self) {
+        let previous = self.previous;
+        critical_section::with(|_| BUFFER.current.set(previous));
+    }
+}
+
+/// Mark the actor of the context as being polled, until the returned scope is dropped.
+#[cfg(feature = "trace")]
+pub(crate) fn enter<A: Actor>(context: &ActorContext<'_, A>) -> Scope {
+    let actor = TraceActor::of(context);
+    let previous = critical_section::with(|_| BUFFER.current.replace(Some(actor)));
+    Scope { previous }
+}
+
+#[cfg(feature = "trace")]
+pub(crate) fn request<A: Actor>(context: &ActorContext<'_, A>) {
+    BUFFER.push(TraceActor::of(context), TraceEventKind::Request)
+}
+
+#[cfg(feature = "trace")]
+pub(crate) fn notify<A: Actor>(context: &ActorContext<'_, A>) {
+    BUFFER.push(TraceActor::of(context), TraceEventKind::Notify)
+}
+
+#[cfg(feature = "trace")]
+pub(crate) fn started<A: Actor>(context: &ActorContext<'_, A>) {
+    BUFFER.push(TraceActor::of(context), TraceEventKind::Start)
+}
+
+#[cfg(feature = "trace")]
+pub(crate) fn finished<A: Actor>(context: &ActorContext<'_, A>) {
+    BUFFER.push(TraceActor::of(context), TraceEventKind::Finish)
+}
+
+#[cfg(not(feature = "trace"))]
+pub(crate) struct Scope;
+
+#[cfg(not(feature = "trace"))]
+pub(crate) fn enter<A: Actor>(_: &ActorContext<'_, A>) -> Scope {
+    Scope
+}
+
+#[cfg(not(feature = "trace"))]
+pub(crate) fn request<A: Actor>(_: &ActorContext<'_, A>) {}
+
+#[cfg(not(feature = "trace"))]
+pub(crate) fn notify<A: Actor>(_: &ActorContext<'_, A>) {}
+
+#[cfg(not(feature = "trace"))]
+pub(crate) fn started<A: Actor>(_: &ActorContext<'_, A>) {}
+
+#[cfg(not(feature = "trace"))]
+pub(crate) fn finished<A: Actor>(_: &ActorContext<'_, A>) {}
+
+#[cfg(all(test, feature = "trace", feature = "std"))]
+mod tests {
+    use super::*;
+
+    const BOARD: TraceActor = TraceActor {
+        id: 1,
+        name: "app::Board",
+    };
+    const GATEWAY: TraceActor = TraceActor {
+        id: 2,
+        name: "app::Gateway",
+    };
+    const LED: TraceActor = TraceActor {
+        id: 3,
+        name: "app::Led",
+    };
+    const OTHER_LED: TraceActor = TraceActor {
+        id: 4,
+        name: "app::Led",
+    };
+
+    fn event(
+        ticks: u64,
+        actor: TraceActor,
+        sender: Option<TraceActor>,
+        kind: TraceEventKind,
+    ) -> TraceEvent {
+        TraceEvent {
+            ticks,
+            actor,
+            sender,
+            kind,
+        }
+    }
+
+    #[test]
+    fn test_short_name() {
+        assert_eq!(
+            "Button",
+            short_name("drogue_device::actors::button::Button<P, H>")
+        );
+        assert_eq!("Counter", short_name("Counter"));
+    }
+
+    #[test]
+    fn test_mermaid() {
+        let events = [
+            event(0, BOARD, Some(GATEWAY), TraceEventKind::Finish),
+            event(0, BOARD, None, TraceEventKind::Request),
+            event(0, BOARD, None, TraceEventKind::Start),
+            event(0, LED, Some(BOARD), TraceEventKind::Notify),
+            event(0, OTHER_LED, Some(BOARD), TraceEventKind::Notify),
+            event(0, BOARD, None, TraceEventKind::Finish),
+        ];
+        assert_eq!(
+            "sequenceDiagram\n    \
+             External->>Board: request at 0 us\n    \
+             activate Board\n    \
+             Board-)Led: notify at 0 us\n    \
+             Board-)Led2: notify at 0 us\n    \
+             deactivate Board\n",
+            to_mermaid(&events)
+        );
+    }
+}
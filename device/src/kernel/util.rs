@@ -1,8 +1,11 @@
 use core::{
+    cell::{Cell, RefCell, UnsafeCell},
     future::Future,
+    mem::MaybeUninit,
     pin::Pin,
-    task::{Context, Poll},
+    task::{Context, Poll, Waker},
 };
+use heapless::{ArrayLength, Vec};
 
 pub struct ImmediateFuture;
 
@@ -24,3 +27,242 @@ impl Future for ImmediateFuture {
         Poll::Ready(())
     }
 }
+
+/// A set of wakers for tasks waiting on the same condition, for use where
+/// a single `AtomicWaker` would lose all but the last registered task.
+///
+/// If the set is full, the registering task is woken immediately so that
+/// it polls again rather than missing the wake up. Tasks beyond the capacity
+/// of the set therefore busy-poll until the condition is met, so it should be
+/// sized for the number of tasks expected to wait at the same time.
+pub struct WakerSet<N>
+where
+    N: ArrayLength<Waker>,
+{
+    wakers: RefCell<Vec<Waker, N>>,
+}
+
+impl<N> WakerSet<N>
+where
+    N: ArrayLength<Waker>,
+{
+    pub fn new() -> Self {
+        Self {
+            wakers: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub fn register(&self, waker: &Waker) {
+        critical_section::with(|_| {
+            let mut wakers = self.wakers.borrow_mut();
+            if wakers.iter().any(|w| w.will_wake(waker)) {
+                return;
+            }
+            if wakers.push(waker.clone()).is_err() {
+                waker.wake_by_ref();
+            }
+        })
+    }
+
+    pub fn wake(&self) {
+        let wakers = critical_section::with(|_| {
+            core::mem::replace(&mut *self.wakers.borrow_mut(), Vec::new())
+        });
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
+
+impl<N> Default for WakerSet<N>
+where
+    N: ArrayLength<Waker>,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A capacity given as a const generic, used to size the message queues and pending
+/// requests of an actor, such as `Slots<4>`.
+pub struct Slots<const N: usize>;
+
+/// A fixed number of slots for values of any type, implemented by `Slots`.
+pub trait Capacity {
+    /// The number of slots.
+    const SIZE: usize;
+
+    /// An array of `SIZE` values of type `T`.
+    type Array<T>: AsRef<[T]> + AsMut<[T]>;
+
+    /// Create an array with each value produced by `f`.
+    fn array<T, F: FnMut() -> T>(f: F) -> Self::Array<T>;
+}
+
+impl<const N: usize> Capacity for Slots<N> {
+    const SIZE: usize = N;
+
+    type Array<T> = [T; N];
+
+    fn array<T, F: FnMut() -> T>(mut f: F) -> [T; N] {
+        [(); N].map(|_| f())
+    }
+}
+
+/// A queue of up to `C::SIZE` values, kept in place without allocating.
+///
+/// The buffer may be shared, but is not synchronized itself, so its methods must be
+/// called within a critical section.
+pub struct RingBuffer<T, C>
+where
+    C: Capacity,
+{
+    buffer: C::Array<UnsafeCell<MaybeUninit<T>>>,
+    // Index of the oldest value and number of values in the buffer
+    head: Cell<usize>,
+    len: Cell<usize>,
+}
+
+impl<T, C> RingBuffer<T, C>
+where
+    C: Capacity,
+{
+    pub fn new() -> Self {
+        Self {
+            buffer: C::array(|| UnsafeCell::new(MaybeUninit::uninit())),
+            head: Cell::new(0),
+            len: Cell::new(0),
+        }
+    }
+
+    /// The number of values in the buffer.
+    pub fn len(&self) -> usize {
+        self.len.get()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len.get() == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len.get() == C::SIZE
+    }
+
+    /// Append a value to the buffer, returning it if the buffer is full.
+    pub fn enqueue(&self, value: T) -> Result<(), T> {
+        if self.is_full() {
+            return Err(value);
+        }
+        let index = (self.head.get() + self.len.get()) % C::SIZE;
+        let slot = self.buffer.as_ref()[index].get();
+        // Safety: The slot after the last value is not initialized.
+        unsafe { (*slot).as_mut_ptr().write(value) };
+        self.len.set(self.len.get() + 1);
+        Ok(())
+    }
+
+    /// Take the oldest value from the buffer.
+    pub fn dequeue(&self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+        let index = self.head.get();
+        let slot = self.buffer.as_ref()[index].get();
+        // Safety: The slot at the head is initialized, and is no longer considered so
+        // once the head has moved past it.
+        let value = unsafe { (*slot).as_ptr().read() };
+        self.head.set((index + 1) % C::SIZE);
+        self.len.set(self.len.get() - 1);
+        Some(value)
+    }
+
+    /// Take the oldest value for which `f` returns true from the buffer, moving the values
+    /// after it one slot towards the head.
+    pub fn remove_first<F: FnMut(&T) -> bool>(&self, mut f: F) -> Option<T> {
+        let len = self.len.get();
+        // Safety: The slots from the head up to `len` are initialized.
+        let position = (0..len).find(|&offset| f(unsafe { &*(*self.slot(offset)).as_ptr() }))?;
+        let value = unsafe { (*self.slot(position)).as_ptr().read() };
+        for offset in position..len - 1 {
+            // Safety: The value is moved into the slot just vacated ahead of it, leaving
+            // the last slot uninitialized once the length is reduced.
+            unsafe {
+                let next = (*self.slot(offset + 1)).as_ptr().read();
+                (*self.slot(offset)).as_mut_ptr().write(next);
+            }
+        }
+        self.len.set(len - 1);
+        Some(value)
+    }
+
+    /// The slot at `offset` from the head.
+    fn slot(&self, offset: usize) -> *mut MaybeUninit<T> {
+        self.buffer.as_ref()[(self.head.get() + offset) % C::SIZE].get()
+    }
+}
+
+impl<T, C> Default for RingBuffer<T, C>
+where
+    C: Capacity,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, C> Drop for RingBuffer<T, C>
+where
+    C: Capacity,
+{
+    fn drop(&mut self) {
+        while self.dequeue().is_some() {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring_buffer_wraps() {
+        let buffer: RingBuffer<u32, Slots<2>> = RingBuffer::new();
+        assert_eq!(Ok(()), buffer.enqueue(1));
+        assert_eq!(Ok(()), buffer.enqueue(2));
+        assert_eq!(Err(3), buffer.enqueue(3));
+
+        assert_eq!(Some(1), buffer.dequeue());
+        assert_eq!(Ok(()), buffer.enqueue(3));
+        assert_eq!(2, buffer.len());
+        assert_eq!(Some(2), buffer.dequeue());
+        assert_eq!(Some(3), buffer.dequeue());
+        assert_eq!(None, buffer.dequeue());
+    }
+
+    #[test]
+    fn test_ring_buffer_remove_first() {
+        let buffer: RingBuffer<u32, Slots<3>> = RingBuffer::new();
+        // Wrap around the end of the buffer
+        assert!(buffer.enqueue(0).is_ok());
+        assert_eq!(Some(0), buffer.dequeue());
+        for value in 1..4 {
+            assert!(buffer.enqueue(value).is_ok());
+        }
+
+        assert_eq!(Some(2), buffer.remove_first(|v| v % 2 == 0));
+        assert_eq!(None, buffer.remove_first(|v| *v > 3));
+        assert_eq!(Ok(()), buffer.enqueue(4));
+        assert_eq!(Some(1), buffer.dequeue());
+        assert_eq!(Some(3), buffer.dequeue());
+        assert_eq!(Some(4), buffer.dequeue());
+    }
+
+    #[test]
+    fn test_ring_buffer_drops_values() {
+        let value = std::rc::Rc::new(());
+        let buffer: RingBuffer<_, Slots<2>> = RingBuffer::new();
+        assert!(buffer.enqueue(value.clone()).is_ok());
+        assert_eq!(2, std::rc::Rc::strong_count(&value));
+        drop(buffer);
+        assert_eq!(1, std::rc::Rc::strong_count(&value));
+    }
+}
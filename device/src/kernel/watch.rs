@@ -0,0 +1,177 @@
+//! Distribution of the latest value of some state, such as whether a network has been
+//! joined, to a fixed number of receivers.
+//!
+//! Unlike a channel, a watch only keeps the latest value. Receivers wait for the value
+//! to change since they last looked at it, skipping any values sent in between.
+
+use core::cell::{Cell, RefCell};
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use embassy::util::AtomicWaker;
+
+/// A value that `N` receivers can watch for changes.
+pub struct Watch<T: Clone, const N: usize> {
+    value: RefCell<Option<T>>,
+    // Incremented for every value sent, with 0 meaning that no value has been sent
+    version: Cell<u32>,
+    wakers: [AtomicWaker; N],
+    receivers: Cell<usize>,
+}
+
+impl<T: Clone, const N: usize> Watch<T, N> {
+    const WAKER: AtomicWaker = AtomicWaker::new();
+
+    pub fn new() -> Self {
+        Self {
+            value: RefCell::new(None),
+            version: Cell::new(0),
+            wakers: [Self::WAKER; N],
+            receivers: Cell::new(0),
+        }
+    }
+
+    /// Replace the value, waking every receiver waiting for a change.
+    pub fn send(&self, value: T) {
+        critical_section::with(|_| {
+            self.value.borrow_mut().replace(value);
+            self.version.set(self.version.get().wrapping_add(1).max(1));
+        });
+        for waker in self.wakers.iter() {
+            waker.wake();
+        }
+    }
+
+    /// The current value, if any value has been sent.
+    pub fn get(&self) -> Option<T> {
+        critical_section::with(|_| self.value.borrow().clone())
+    }
+
+    /// Create a receiver of changes, or `None` if all `N` receivers have been created.
+    ///
+    /// If a value has already been sent, it counts as a change for the new receiver.
+    pub fn receiver(&self) -> Option<WatchReceiver<'_, T, N>> {
+        critical_section::with(|_| {
+            let index = self.receivers.get();
+            if index < N {
+                self.receivers.set(index + 1);
+                Some(WatchReceiver {
+                    watch: self,
+                    index,
+                    seen: 0,
+                })
+            } else {
+                None
+            }
+        })
+    }
+
+    /// The value and its version, if the version differs from `seen`.
+    fn changed_since(&self, seen: u32) -> Option<(T, u32)> {
+        critical_section::with(|_| {
+            let version = self.version.get();
+            if version != seen {
+                self.value.borrow().clone().map(|value| (value, version))
+            } else {
+                None
+            }
+        })
+    }
+}
+
+impl<T: Clone, const N: usize> Default for Watch<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Safety: The value is only accessed within critical sections.
+unsafe impl<T: Clone + Send, const N: usize> Send for Watch<T, N> {}
+unsafe impl<T: Clone + Send, const N: usize> Sync for Watch<T, N> {}
+
+/// One of the receivers of a `Watch`, keeping track of the last value it has seen.
+pub struct WatchReceiver<'a, T: Clone, const N: usize> {
+    watch: &'a Watch<T, N>,
+    index: usize,
+    seen: u32,
+}
+
+impl<'a, T: Clone, const N: usize> WatchReceiver<'a, T, N> {
+    /// Wait for the value to change since this receiver last looked at it.
+    pub fn changed<'m>(&'m mut self) -> WatchChanged<'m, 'a, T, N> {
+        WatchChanged { receiver: self }
+    }
+
+    /// The value, if it has changed since this receiver last looked at it.
+    pub fn try_changed(&mut self) -> Option<T> {
+        self.watch.changed_since(self.seen).map(|(value, version)| {
+            self.seen = version;
+            value
+        })
+    }
+
+    /// The current value, if any value has been sent, marking it as seen.
+    pub fn get(&mut self) -> Option<T> {
+        critical_section::with(|_| {
+            self.seen = self.watch.version.get();
+            self.watch.value.borrow().clone()
+        })
+    }
+
+    fn poll_changed(&mut self, cx: &mut Context<'_>) -> Poll<T> {
+        // Register before checking in order to not miss a change in between
+        self.watch.wakers[self.index].register(cx.waker());
+        match self.try_changed() {
+            Some(value) => Poll::Ready(value),
+            None => Poll::Pending,
+        }
+    }
+}
+
+pub struct WatchChanged<'m, 'a, T: Clone, const N: usize> {
+    receiver: &'m mut WatchReceiver<'a, T, N>,
+}
+
+impl<'m, 'a, T: Clone, const N: usize> Future for WatchChanged<'m, 'a, T, N> {
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.receiver.poll_changed(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_receivers_see_latest_value() {
+        let watch: Watch<u32, 2> = Watch::new();
+        let mut first = watch.receiver().unwrap();
+        let mut second = watch.receiver().unwrap();
+        assert!(watch.receiver().is_none());
+
+        let waker = futures::task::noop_waker_ref();
+        let mut cx = std::task::Context::from_waker(waker);
+
+        {
+            let changed = first.changed();
+            futures::pin_mut!(changed);
+            assert!(changed.as_mut().poll(&mut cx).is_pending());
+            watch.send(1);
+            assert_eq!(Poll::Ready(1), changed.as_mut().poll(&mut cx));
+        }
+
+        // Values sent in between are skipped
+        watch.send(2);
+        watch.send(3);
+        assert_eq!(Some(3), second.try_changed());
+        assert_eq!(None, second.try_changed());
+        assert_eq!(Some(3), first.try_changed());
+
+        let changed = second.changed();
+        futures::pin_mut!(changed);
+        assert!(changed.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(Some(3), watch.get());
+    }
+}
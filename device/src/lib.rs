@@ -10,6 +10,7 @@
 #![feature(generic_associated_types)]
 #![feature(associated_type_defaults)]
 #![feature(type_alias_impl_trait)]
+#![feature(array_map)]
 //! An async, no-alloc actor framework for embedded devices.
 //!
 //! See [the book](https://book.drogue.io/drogue-device/dev/index.html) for more about the architecture, how to write device drivers, and running some examples.
@@ -78,20 +79,31 @@
 //!     let a_addr = DEVICE.mount(|device| {
 //!         device.a.mount((), spawner.into())
 //!     });
-//!     a_addr.request(SayHello("World")).await;
+//!     a_addr.request(SayHello("World")).unwrap().await.unwrap();
 //! }
 //!```
 //!
 
 pub(crate) mod fmt;
 
+// Allow the macros to refer to `::drogue_device` from within this crate
+extern crate self as drogue_device;
+pub use drogue_device_macros::{actor, device};
+
 pub mod kernel;
 pub use kernel::{
-    actor::{Actor, ActorContext, ActorSpawner, Address},
+    actor::{
+        Actor, ActorContext, ActorSpawner, Address, Concurrency, ConcurrentActor, Exclusive,
+        ExecutorPriority, HighPriority, HighPrioritySpawner, IsrAddress, MessageAdapter,
+        NormalPriority, OverflowPolicy, Priority, PrioritySpawner, Recipient, Responder,
+        SendActorSpawner, Shared,
+    },
+    actor_pool::{ActorPool, PoolAddress},
     channel::Channel,
-    device::DeviceContext,
+    device::{Device, DeviceContext},
     package::Package,
-    util::ImmediateFuture,
+    supervisor::{RestartPolicy, Supervisor},
+    util::{ImmediateFuture, Slots},
 };
 
 pub mod actors;
@@ -1,24 +1,27 @@
 use crate::actors::button::{ButtonEvent, FromButtonEvent};
 use crate::kernel::{
     actor::{Actor, ActorContext, ActorSpawner},
-    device::DeviceContext,
+    device::{Device, DeviceContext},
     util::ImmediateFuture,
 };
-use core::cell::RefCell;
+use core::cell::{Cell, RefCell};
 use core::future::Future;
 use core::pin::Pin;
 use core::sync::atomic::{AtomicBool, Ordering};
-use core::task::{Context, Poll};
+use core::task::{Context, Poll, Waker};
 use embassy::executor::{raw, SpawnError, Spawner};
-use embassy::time::TICKS_PER_SECOND;
-use embassy::time::{Alarm, Clock};
+use embassy::time::{Alarm, Clock, Duration, TICKS_PER_SECOND};
 use embassy::traits::gpio::WaitForAnyEdge;
 use embassy::util::Signal;
-use embedded_hal::digital::v2::InputPin;
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+use std::boxed::Box;
 use std::cell::UnsafeCell;
 use std::marker::PhantomData;
 use std::mem::MaybeUninit;
 use std::ptr;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::task::Wake;
 // use std::time::{Duration as StdDuration, Instant as StdInstant};
 use std::time::Instant as StdInstant;
 use std::vec::Vec;
@@ -28,6 +31,8 @@ pub struct TestSpawner;
 
 impl TestSpawner {
     pub fn new() -> Self {
+        // Actors may read the time while handling messages, such as for metrics
+        init_clock();
         Self {}
     }
 }
@@ -38,6 +43,210 @@ impl ActorSpawner for TestSpawner {
     }
 }
 
+/// Polls mounted actors and spawned futures on the current thread, one at a time, picking
+/// the next one among those that have been woken with a random number generator. Runs with
+/// the same seed poll in the same order, while different seeds explore other interleavings,
+/// see `explore`.
+///
+/// Unlike `TestSpawner`, mounting an actor with the scheduler starts it, so its `on_start`
+/// runs as well. Actors and futures must not use embassy timers, which need an embassy
+/// executor.
+pub struct TestScheduler {
+    seed: u64,
+    rng: Cell<u64>,
+    tasks: RefCell<Vec<Rc<ScheduledTask>>>,
+}
+
+impl TestScheduler {
+    pub fn new(seed: u64) -> Self {
+        init_clock();
+        Self {
+            seed,
+            rng: Cell::new(seed),
+            tasks: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// The seed the scheduler was created with.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Spawn a future that is polled along with the actors, such as a test client
+    /// making requests.
+    pub fn spawn<F: Future<Output = ()> + 'static>(&self, future: F) {
+        self.schedule(Task::Future(RefCell::new(Box::pin(future))));
+    }
+
+    fn schedule(&self, task: Task) {
+        self.tasks.borrow_mut().push(Rc::new(ScheduledTask {
+            task,
+            waker: Arc::new(TaskWaker {
+                woken: AtomicBool::new(true),
+            }),
+            done: Cell::new(false),
+        }));
+    }
+
+    /// Poll one of the actors and futures that have been woken, returning false if none
+    /// have been woken.
+    pub fn step(&self) -> bool {
+        let woken: Vec<Rc<ScheduledTask>> = self
+            .tasks
+            .borrow()
+            .iter()
+            .filter(|task| task.is_woken())
+            .cloned()
+            .collect();
+        if woken.is_empty() {
+            return false;
+        }
+        let index = self.next_index(woken.len());
+        woken[index].poll();
+        true
+    }
+
+    /// Poll actors and futures until none have been woken.
+    pub fn run_until_idle(&self) {
+        while self.step() {}
+    }
+
+    /// Poll actors and futures until every spawned future has completed.
+    ///
+    /// # Panics
+    /// If the spawned futures are left waiting for something that never happens.
+    pub fn run(&self) {
+        self.run_until_idle();
+        let pending = self
+            .tasks
+            .borrow()
+            .iter()
+            .filter(|task| matches!(task.task, Task::Future(_)) && !task.done.get())
+            .count();
+        assert!(
+            pending == 0,
+            "{} futures never completed with seed {}",
+            pending,
+            self.seed
+        );
+    }
+
+    // A random index below `len`, using SplitMix64 which works with any seed.
+    fn next_index(&self, len: usize) -> usize {
+        let state = self.rng.get().wrapping_add(0x9E37_79B9_7F4A_7C15);
+        self.rng.set(state);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        (z % len as u64) as usize
+    }
+}
+
+impl ActorSpawner for &'static TestScheduler {
+    fn start<A: Actor>(&self, actor: &'static ActorContext<'static, A>) -> Result<(), SpawnError> {
+        self.schedule(Task::Actor(actor));
+        Ok(())
+    }
+}
+
+/// Run a test with schedulers seeded from 0 up to `runs`, exploring as many interleavings
+/// of its actors and futures. A failing run panics with its seed in the message, so that
+/// it can be reproduced with `TestScheduler::new`.
+pub fn explore<F: Fn(&'static TestScheduler)>(runs: u64, test: F) {
+    for seed in 0..runs {
+        let scheduler = Box::leak(Box::new(TestScheduler::new(seed)));
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| test(scheduler)));
+        if let Err(e) = result {
+            let message = e
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| e.downcast_ref::<std::string::String>().map(|s| s.as_str()))
+                .unwrap_or("unknown panic");
+            panic!("Test failed with scheduler seed {}: {}", seed, message);
+        }
+    }
+}
+
+/// Let the scheduler poll other actors and futures before continuing.
+pub async fn yield_now() {
+    YieldNow { yielded: false }.await
+}
+
+struct YieldNow {
+    yielded: bool,
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.yielded {
+            Poll::Ready(())
+        } else {
+            self.yielded = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
+
+/// An actor that can be polled by the scheduler regardless of its type.
+trait ScheduledActor {
+    fn poll_actor(&'static self, cx: &mut Context<'_>) -> Poll<()>;
+}
+
+impl<A: Actor> ScheduledActor for ActorContext<'static, A> {
+    fn poll_actor(&'static self, cx: &mut Context<'_>) -> Poll<()> {
+        self.poll(cx)
+    }
+}
+
+enum Task {
+    Actor(&'static dyn ScheduledActor),
+    Future(RefCell<Pin<Box<dyn Future<Output = ()>>>>),
+}
+
+struct ScheduledTask {
+    task: Task,
+    waker: Arc<TaskWaker>,
+    // Set once the future has completed or the actor has stopped
+    done: Cell<bool>,
+}
+
+impl ScheduledTask {
+    fn is_woken(&self) -> bool {
+        !self.done.get() && self.waker.woken.load(Ordering::SeqCst)
+    }
+
+    fn poll(&self) {
+        self.waker.woken.store(false, Ordering::SeqCst);
+        let waker = Waker::from(self.waker.clone());
+        let mut cx = Context::from_waker(&waker);
+        let result = match &self.task {
+            Task::Actor(actor) => actor.poll_actor(&mut cx),
+            Task::Future(future) => future.borrow_mut().as_mut().poll(&mut cx),
+        };
+        if result.is_ready() {
+            self.done.set(true);
+        }
+    }
+}
+
+struct TaskWaker {
+    woken: AtomicBool,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.woken.store(true, Ordering::SeqCst);
+    }
+}
+
 /// A test context that can execute test for a given device
 pub struct TestContext<D: 'static> {
     runner: &'static TestRunner,
@@ -64,6 +273,11 @@ impl<D> TestContext<D> {
         self.runner.signal()
     }
 
+    /// Advance the virtual clock of the test, see `TestRunner::advance`.
+    pub fn advance(&mut self, duration: Duration) {
+        self.runner.advance(duration)
+    }
+
     /// Mount the device, running the provided callback function.
     pub async fn mount<FUT: Future<Output = R>, F: FnOnce(&'static D) -> FUT, R>(
         &mut self,
@@ -73,6 +287,18 @@ impl<D> TestContext<D> {
     }
 }
 
+impl<D: Device> TestContext<D> {
+    /// Unmount the device, shutting down its actors.
+    pub async fn unmount(&mut self) {
+        self.device.unmount().await
+    }
+
+    /// Shut down the device, allowing the context to be configured again.
+    pub async fn shutdown(&mut self) {
+        self.device.shutdown().await
+    }
+}
+
 impl<D> Drop for TestContext<D> {
     fn drop(&mut self) {
         self.runner.done()
@@ -218,6 +444,18 @@ impl WaitForAnyEdge for TestPin {
     }
 }
 
+impl OutputPin for TestPin {
+    type Error = ();
+    fn set_high(&mut self) -> Result<(), ()> {
+        self.inner.set_value(true);
+        Ok(())
+    }
+    fn set_low(&mut self) -> Result<(), ()> {
+        self.inner.set_value(false);
+        Ok(())
+    }
+}
+
 impl InputPin for TestPin {
     type Error = ();
     fn is_high(&self) -> Result<bool, ()> {
@@ -270,10 +508,9 @@ pub struct TestRunner {
 
 impl TestRunner {
     pub fn new() -> Self {
-        unsafe {
-            CLOCK_ZERO.as_mut_ptr().write(StdInstant::now());
-            embassy::time::set_clock(&StdClock);
-        }
+        init_clock();
+        VIRTUAL_NOW.with(|now| now.set(None));
+        ALARM_AT.with(|at| at.set(u64::MAX));
 
         Self {
             inner: UnsafeCell::new(raw::Executor::new(Signaler::signal, ptr::null_mut())),
@@ -285,6 +522,17 @@ impl TestRunner {
         }
     }
 
+    /// Create a runner with a virtual clock starting at zero, which only advances when
+    /// calling `advance` or when the executor is idle waiting for a timer, so that schedules
+    /// of hours run in no time.
+    ///
+    /// The clock is kept per thread, so the runner must be created and run on the same thread.
+    pub fn new_virtual() -> Self {
+        let runner = Self::new();
+        VIRTUAL_NOW.with(|now| now.set(Some(0)));
+        runner
+    }
+
     pub fn initialize(&'static self, init: impl FnOnce(Spawner)) {
         let inner = unsafe { &mut *self.inner.get() };
         inner.set_signal_ctx(&self.signaler as *const _ as _);
@@ -297,6 +545,31 @@ impl TestRunner {
         while self.signaler.should_run() {
             unsafe { (&*self.inner.get()).run_queued() };
         }
+        // Nothing left to run, so skip ahead to the next timer
+        if !self.is_done() {
+            VIRTUAL_NOW.with(|now| {
+                let alarm_at = ALARM_AT.with(|at| at.get());
+                if let Some(t) = now.get() {
+                    if alarm_at != u64::MAX && alarm_at > t {
+                        now.set(Some(alarm_at));
+                    }
+                }
+            });
+        }
+    }
+
+    /// Advance the virtual clock by the provided duration. Timers expiring in the meantime
+    /// complete the next time the executor runs.
+    ///
+    /// # Panics
+    /// If the runner was not created with `new_virtual`.
+    pub fn advance(&self, duration: Duration) {
+        VIRTUAL_NOW.with(|now| {
+            let t = now
+                .get()
+                .expect("advance requires a runner with virtual time");
+            now.set(Some(t + duration.as_ticks()));
+        })
     }
 
     /// Create a test pin that can be used in tests
@@ -325,9 +598,29 @@ impl TestRunner {
 }
 
 static mut CLOCK_ZERO: MaybeUninit<StdInstant> = MaybeUninit::uninit();
+
+/// Install the clock used for embassy timers, once for all tests in the process.
+fn init_clock() {
+    static INIT: std::sync::Once = std::sync::Once::new();
+    INIT.call_once(|| unsafe {
+        CLOCK_ZERO.as_mut_ptr().write(StdInstant::now());
+        embassy::time::set_clock(&StdClock);
+    });
+}
+
+thread_local! {
+    // The time of the virtual clock of the runner on this thread, if it has one
+    static VIRTUAL_NOW: Cell<Option<u64>> = Cell::new(None);
+    // The time of the next timer expiration of the executor on this thread
+    static ALARM_AT: Cell<u64> = Cell::new(u64::MAX);
+}
+
 struct StdClock;
 impl Clock for StdClock {
     fn now(&self) -> u64 {
+        if let Some(now) = VIRTUAL_NOW.with(|now| now.get()) {
+            return now;
+        }
         let zero = unsafe { CLOCK_ZERO.as_ptr().read() };
         let dur = StdInstant::now().duration_since(zero);
         dur.as_secs() * (TICKS_PER_SECOND as u64)
@@ -335,18 +628,16 @@ impl Clock for StdClock {
     }
 }
 
-static mut ALARM_AT: u64 = u64::MAX;
-
 pub struct StdAlarm;
 impl Alarm for StdAlarm {
     fn set_callback(&self, _callback: fn(*mut ()), _ctx: *mut ()) {}
 
     fn set(&self, timestamp: u64) {
-        unsafe { ALARM_AT = timestamp }
+        ALARM_AT.with(|at| at.set(timestamp))
     }
 
     fn clear(&self) {
-        unsafe { ALARM_AT = u64::MAX }
+        ALARM_AT.with(|at| at.set(u64::MAX))
     }
 }
 
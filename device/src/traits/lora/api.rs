@@ -49,4 +49,6 @@ pub enum LoraError {
     NotImplemented,
     UnsupportedRegion,
     OtherError,
+    /// The LoRa actor was restarted or stopped before it responded.
+    Abandoned,
 }
@@ -10,6 +10,11 @@ pub enum TcpError {
     CloseError,
     IoError,
     SocketClosed,
+    /// Data received on the socket was dropped by the adapter, leaving a gap of this many
+    /// bytes in the stream.
+    DataDropped(usize),
+    /// The network adapter actor was restarted or stopped before it responded.
+    Abandoned,
 }
 
 pub trait TcpSocket {
@@ -37,7 +42,7 @@ pub trait TcpSocket {
 pub trait TcpStack {
     type SocketHandle: Copy;
 
-    type OpenFuture<'m>: Future<Output = Self::SocketHandle>
+    type OpenFuture<'m>: Future<Output = Result<Self::SocketHandle, TcpError>>
     where
         Self: 'm;
     fn open<'m>(&'m mut self) -> Self::OpenFuture<'m>;
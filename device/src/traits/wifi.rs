@@ -15,6 +15,8 @@ pub enum JoinError {
     InvalidSsid,
     InvalidPassword,
     UnableToAssociate,
+    /// The network adapter actor was restarted or stopped before it responded.
+    Abandoned,
 }
 
 pub trait WifiSupplicant {
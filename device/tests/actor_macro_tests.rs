@@ -0,0 +1,83 @@
+#![macro_use]
+#![allow(incomplete_features)]
+#![feature(min_type_alias_impl_trait)]
+#![feature(impl_trait_in_bindings)]
+#![feature(generic_associated_types)]
+#![feature(type_alias_impl_trait)]
+
+#[cfg(feature = "std")]
+mod tests {
+    extern crate std;
+    use drogue_device::{testutil::*, *};
+    use drogue_device_macros::test as drogue_test;
+    use embassy::executor::Spawner;
+
+    /// Keeps a running total of the values it is given
+    pub struct Accumulator {
+        total: u32,
+        started: bool,
+    }
+
+    #[actor]
+    impl Accumulator {
+        async fn on_start(&mut self) {
+            self.started = true;
+        }
+
+        /// Add a value, returning the new total
+        pub async fn add(&mut self, value: u32) -> u32 {
+            self.total += value;
+            self.total
+        }
+
+        /// Add all values of a borrowed slice
+        pub async fn add_all(&mut self, values: &'m [u32]) -> u32 {
+            for value in values {
+                self.total += value;
+            }
+            self.total
+        }
+
+        pub async fn reset(&mut self) {
+            self.total = 0;
+        }
+
+        pub async fn started(&mut self) -> bool {
+            self.started
+        }
+    }
+
+    struct AccumulatorDevice {
+        accumulator: ActorContext<'static, Accumulator>,
+    }
+
+    #[drogue_test]
+    async fn test_typed_requests(spawner: Spawner, mut context: TestContext<AccumulatorDevice>) {
+        context.configure(AccumulatorDevice {
+            accumulator: ActorContext::new(Accumulator {
+                total: 0,
+                started: false,
+            }),
+        });
+
+        let accumulator = context
+            .mount(|device| async move { device.accumulator.mount((), spawner) })
+            .await;
+
+        assert_eq!(Ok(true), accumulator.call_started().await);
+        assert_eq!(Ok(1), accumulator.call_add(1).await);
+        assert_eq!(Ok(3), accumulator.call_add(2).await);
+
+        let values = [1, 2, 3];
+        assert_eq!(Ok(9), accumulator.call_add_all(&values).await);
+
+        assert_eq!(Ok(()), accumulator.call_reset().await);
+        assert_eq!(Ok(4), accumulator.call_add(4).await);
+
+        // The generated message enum can be used directly as well
+        match accumulator.request_async(AccumulatorMessage::Add(1)).await {
+            Ok(AccumulatorResponse::Add(total)) => assert_eq!(5, total),
+            _ => panic!("unexpected response"),
+        }
+    }
+}
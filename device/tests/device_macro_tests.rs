@@ -0,0 +1,87 @@
+#![macro_use]
+#![allow(incomplete_features)]
+#![feature(min_type_alias_impl_trait)]
+#![feature(impl_trait_in_bindings)]
+#![feature(generic_associated_types)]
+#![feature(type_alias_impl_trait)]
+
+#[cfg(feature = "std")]
+mod tests {
+    use drogue_device::{actors::button::*, testutil::*, *};
+    use drogue_device_macros::test as drogue_test;
+    use embassy::executor::Spawner;
+
+    device! {
+        struct WiredDevice: WIRED {
+            button: Button<'static, TestPin, TestHandler>,
+            handler: TestHandler,
+        }
+
+        button -> handler;
+    }
+
+    device! {
+        struct RemountedDevice: REMOUNTED {
+            button: Button<'static, TestPin, TestHandler>,
+            handler: TestHandler,
+        }
+
+        button -> handler;
+    }
+
+    struct Unused;
+
+    #[drogue_test]
+    async fn test_wired(spawner: Spawner, mut context: TestContext<Unused>) {
+        let pin = context.pin(true);
+        let notified = context.signal();
+
+        WiredDevice::configure(WiredDeviceConfig {
+            button: Button::new(pin),
+            handler: TestHandler::new(notified),
+        });
+
+        // The handler is declared after the button, but must be mounted first
+        let addresses = WiredDevice::mount(spawner).await;
+
+        pin.set_low();
+        notified.wait_signaled().await;
+        assert_eq!(0, notified.message().unwrap().0);
+
+        addresses.handler.notify(TestMessage(2)).unwrap();
+        notified.wait_signaled().await;
+        assert_eq!(2, notified.message().unwrap().0);
+    }
+    #[drogue_test]
+    async fn test_unmount(spawner: Spawner, mut context: TestContext<Unused>) {
+        let pin = context.pin(true);
+        let notified = context.signal();
+
+        RemountedDevice::configure(RemountedDeviceConfig {
+            button: Button::new(pin),
+            handler: TestHandler::new(notified),
+        });
+
+        // Mounting again fails to spawn the actors unless unmounting stopped them
+        for i in 0..2 {
+            let addresses = RemountedDevice::mount(spawner).await;
+            addresses.handler.notify(TestMessage(i)).unwrap();
+            notified.wait_signaled().await;
+            assert_eq!(i, notified.message().unwrap().0);
+            RemountedDevice::unmount().await;
+        }
+
+        RemountedDevice::shutdown().await;
+        RemountedDevice::configure(RemountedDeviceConfig {
+            button: Button::new(pin),
+            handler: TestHandler::new(notified),
+        });
+        let addresses = RemountedDevice::mount(spawner).await;
+        pin.set_low();
+        notified.wait_signaled().await;
+        assert_eq!(0, notified.message().unwrap().0);
+        addresses.handler.notify(TestMessage(2)).unwrap();
+        notified.wait_signaled().await;
+        assert_eq!(2, notified.message().unwrap().0);
+    }
+}
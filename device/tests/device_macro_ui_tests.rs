@@ -0,0 +1,9 @@
+#[cfg(feature = "std")]
+mod tests {
+    /// Wiring errors of `device!` are reported at compile time
+    #[test]
+    fn test_device_macro_errors() {
+        let t = trybuild::TestCases::new();
+        t.compile_fail("tests/ui/device_*.rs");
+    }
+}
@@ -0,0 +1,107 @@
+#![macro_use]
+#![allow(incomplete_features)]
+#![feature(min_type_alias_impl_trait)]
+#![feature(impl_trait_in_bindings)]
+#![feature(generic_associated_types)]
+#![feature(type_alias_impl_trait)]
+
+#[cfg(feature = "std")]
+mod tests {
+    extern crate std;
+    use core::pin::Pin;
+    use drogue_device::{
+        actors::event_bus::{EventBus, FromEvent},
+        testutil::*,
+        *,
+    };
+    use drogue_device_macros::test as drogue_test;
+    use embassy::executor::Spawner;
+
+    #[derive(Clone, Copy)]
+    pub enum Temperature {
+        Low,
+        High,
+    }
+
+    /// Subscriber only interested in high temperatures
+    pub struct Alarm {
+        alarms: &'static TestSignal,
+    }
+
+    impl FromEvent<Temperature, ()> for Alarm {
+        fn from(event: Temperature) -> Option<()> {
+            match event {
+                Temperature::High => Some(()),
+                Temperature::Low => None,
+            }
+        }
+    }
+
+    /// Adapter forwarding every temperature as a reading
+    pub struct Reading;
+
+    impl FromEvent<Temperature, TestMessage> for Reading {
+        fn from(event: Temperature) -> Option<TestMessage> {
+            Some(TestMessage(event as u32))
+        }
+    }
+
+    impl Actor for Alarm {
+        type OnStartFuture<'m> = ImmediateFuture;
+        type OnMessageFuture<'m> = ImmediateFuture;
+
+        fn on_start(self: Pin<&'_ mut Self>) -> Self::OnStartFuture<'_> {
+            ImmediateFuture::new()
+        }
+
+        fn on_message<'m>(
+            self: Pin<&'m mut Self>,
+            _: Self::Message<'m>,
+        ) -> Self::OnMessageFuture<'m> {
+            self.alarms.signal(TestMessage(1));
+            ImmediateFuture::new()
+        }
+    }
+
+    struct BusDevice {
+        bus: ActorContext<'static, EventBus<Temperature, 2>>,
+        alarm: ActorContext<'static, Alarm>,
+        handler: ActorContext<'static, TestHandler>,
+    }
+
+    #[drogue_test]
+    async fn test_fan_out(spawner: Spawner, mut context: TestContext<BusDevice>) {
+        let alarms = context.signal();
+        let readings = context.signal();
+        context.configure(BusDevice {
+            bus: ActorContext::new(EventBus::new()),
+            alarm: ActorContext::new(Alarm { alarms }),
+            handler: ActorContext::new(TestHandler::new(readings)),
+        });
+
+        let (bus, alarm, handler) = context
+            .mount(|device| async move {
+                (
+                    device.bus.mount((), spawner),
+                    device.alarm.mount((), spawner),
+                    device.handler.mount((), spawner),
+                )
+            })
+            .await;
+
+        assert!(bus.subscribe(alarm).await.is_ok());
+        assert!(bus.subscribe_with::<_, Reading>(handler).await.is_ok());
+        assert!(bus.subscribe(alarm).await.is_err());
+
+        bus.publish(Temperature::Low).await;
+        readings.wait_signaled().await;
+        assert_eq!(Temperature::Low as u32, readings.message().unwrap().0);
+        assert!(alarms.message().is_none());
+
+        bus.publish(Temperature::High).await;
+        readings.wait_signaled().await;
+        assert_eq!(Temperature::High as u32, readings.message().unwrap().0);
+        alarms.wait_signaled().await;
+        assert_eq!(1, alarms.message().unwrap().0);
+    }
+}
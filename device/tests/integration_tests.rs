@@ -58,7 +58,7 @@ mod tests {
                 .mount(|device| async move { device.a.mount((), spawner) })
                 .await;
 
-            a_addr.request(Add(10)).unwrap().await;
+            a_addr.request(Add(10)).unwrap().await.unwrap();
         }
 
         std::thread::spawn(move || {
@@ -0,0 +1,91 @@
+#![macro_use]
+#![allow(incomplete_features)]
+#![feature(min_type_alias_impl_trait)]
+#![feature(impl_trait_in_bindings)]
+#![feature(generic_associated_types)]
+#![feature(type_alias_impl_trait)]
+
+#[cfg(feature = "std")]
+mod tests {
+    extern crate std;
+    use core::future::Future;
+    use core::pin::Pin;
+    use drogue_device::{testutil::*, *};
+    use drogue_device_macros::test as drogue_test;
+    use embassy::executor::Spawner;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    static STOPS: AtomicU32 = AtomicU32::new(0);
+
+    /// Signals received messages and counts how many times it has been stopped
+    pub struct Stoppable {
+        received: &'static TestSignal,
+    }
+
+    impl Actor for Stoppable {
+        type Message<'m> = TestMessage;
+        type OnStartFuture<'m> = ImmediateFuture;
+        type OnMessageFuture<'m> = ImmediateFuture;
+
+        fn on_start(self: Pin<&'_ mut Self>) -> Self::OnStartFuture<'_> {
+            ImmediateFuture::new()
+        }
+
+        fn on_message<'m>(
+            self: Pin<&'m mut Self>,
+            message: Self::Message<'m>,
+        ) -> Self::OnMessageFuture<'m> {
+            self.received.signal(message);
+            ImmediateFuture::new()
+        }
+
+        fn on_stop(&mut self) {
+            STOPS.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    struct LifecycleDevice {
+        actor: ActorContext<'static, Stoppable>,
+    }
+
+    impl Device for LifecycleDevice {
+        type ShutdownFuture = impl Future<Output = ()>;
+
+        fn shutdown(&'static self) -> Self::ShutdownFuture {
+            self.actor.shutdown()
+        }
+    }
+
+    #[drogue_test]
+    async fn test_remount(spawner: Spawner, mut context: TestContext<LifecycleDevice>) {
+        let received = context.signal();
+        context.configure(LifecycleDevice {
+            actor: ActorContext::new(Stoppable { received }),
+        });
+
+        for i in 0..2 {
+            let actor = context
+                .mount(|device| async move { device.actor.mount((), spawner) })
+                .await;
+
+            actor.notify(TestMessage(i)).unwrap();
+            received.wait_signaled().await;
+            assert_eq!(i, received.message().unwrap().0);
+
+            context.unmount().await;
+            assert_eq!(i + 1, STOPS.load(Ordering::SeqCst));
+        }
+
+        // Once shut down, the device can be configured again
+        context.shutdown().await;
+        context.configure(LifecycleDevice {
+            actor: ActorContext::new(Stoppable { received }),
+        });
+        let actor = context
+            .mount(|device| async move { device.actor.mount((), spawner) })
+            .await;
+        actor.notify(TestMessage(2)).unwrap();
+        received.wait_signaled().await;
+        assert_eq!(2, received.message().unwrap().0);
+    }
+}
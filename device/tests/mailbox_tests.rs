@@ -0,0 +1,185 @@
+#![macro_use]
+#![allow(incomplete_features)]
+#![feature(min_type_alias_impl_trait)]
+#![feature(impl_trait_in_bindings)]
+#![feature(generic_associated_types)]
+#![feature(type_alias_impl_trait)]
+
+#[cfg(feature = "std")]
+mod tests {
+    extern crate std;
+    use core::future::Future;
+    use core::pin::Pin;
+    use drogue_device::{testutil::*, *};
+    use drogue_device_macros::test as drogue_test;
+    use embassy::executor::Spawner;
+    use embassy::time::{Duration, Timer};
+
+    const PRODUCER_MESSAGES: u32 = 10;
+    const INTERRUPT_MESSAGES: u32 = 5;
+    const THREAD_MESSAGES: u32 = 100;
+
+    /// Counts all messages it receives, signalling once the expected amount has arrived
+    pub struct Counter {
+        done: &'static TestSignal,
+        expected: u32,
+        total: u32,
+    }
+
+    impl Counter {
+        fn new(done: &'static TestSignal, expected: u32) -> Self {
+            Self {
+                done,
+                expected,
+                total: 0,
+            }
+        }
+    }
+
+    impl Actor for Counter {
+        type Message<'m> = TestMessage;
+        type OnStartFuture<'m> = ImmediateFuture;
+        type OnMessageFuture<'m> = ImmediateFuture;
+
+        fn on_start(self: Pin<&'_ mut Self>) -> Self::OnStartFuture<'_> {
+            ImmediateFuture::new()
+        }
+
+        fn on_message<'m>(
+            self: Pin<&'m mut Self>,
+            message: Self::Message<'m>,
+        ) -> Self::OnMessageFuture<'m> {
+            let this = unsafe { self.get_unchecked_mut() };
+            this.total += message.0;
+            if this.total == this.expected {
+                this.done.signal(TestMessage(this.total));
+            }
+            ImmediateFuture::new()
+        }
+    }
+
+    /// Sends a burst of messages to the counter when started
+    pub struct Producer {
+        counter: Option<Address<'static, Counter>>,
+    }
+
+    impl Actor for Producer {
+        type Configuration = Address<'static, Counter>;
+        type OnStartFuture<'m> = impl Future<Output = ()> + 'm;
+        type OnMessageFuture<'m> = ImmediateFuture;
+
+        fn on_mount(&mut self, _: Address<'static, Self>, config: Self::Configuration) {
+            self.counter.replace(config);
+        }
+
+        fn on_start(self: Pin<&'_ mut Self>) -> Self::OnStartFuture<'_> {
+            async move {
+                let counter = self.counter.unwrap();
+                for _ in 0..PRODUCER_MESSAGES {
+                    counter.notify_async(TestMessage(1)).await;
+                }
+            }
+        }
+
+        fn on_message<'m>(
+            self: Pin<&'m mut Self>,
+            _: Self::Message<'m>,
+        ) -> Self::OnMessageFuture<'m> {
+            ImmediateFuture::new()
+        }
+    }
+
+    struct MailboxDevice {
+        counter: ActorContext<'static, Counter>,
+        p1: ActorContext<'static, Producer>,
+        p2: ActorContext<'static, Producer>,
+        p3: ActorContext<'static, Producer>,
+    }
+
+    /// Simulates an interrupt handler posting to the actor, which can't await
+    /// and may preempt any of the producers.
+    fn interrupt(counter: Address<'static, Counter>) -> bool {
+        critical_section::with(|_| counter.notify(TestMessage(1)).is_ok())
+    }
+
+    #[drogue_test]
+    async fn test_multiple_producers(spawner: Spawner, mut context: TestContext<MailboxDevice>) {
+        let done = context.signal();
+        let expected = 3 * PRODUCER_MESSAGES + INTERRUPT_MESSAGES;
+        context.configure(MailboxDevice {
+            counter: ActorContext::new(Counter::new(done, expected)),
+            p1: ActorContext::new(Producer { counter: None }),
+            p2: ActorContext::new(Producer { counter: None }),
+            p3: ActorContext::new(Producer { counter: None }),
+        });
+
+        let counter = context
+            .mount(|device| async move {
+                let counter = device.counter.mount((), spawner);
+                device.p1.mount(counter, spawner);
+                device.p2.mount(counter, spawner);
+                device.p3.mount(counter, spawner);
+                counter
+            })
+            .await;
+
+        let mut fired = 0;
+        while fired < INTERRUPT_MESSAGES {
+            if interrupt(counter) {
+                fired += 1;
+            }
+            Timer::after(Duration::from_millis(1)).await;
+        }
+
+        done.wait_signaled().await;
+        assert_eq!(expected, done.message().unwrap().0);
+    }
+
+    /// Sends messages to the counter from its own OS thread, retrying while the queue is full
+    fn spawn_producer_thread(counter: Address<'static, Counter>) -> std::thread::JoinHandle<()> {
+        std::thread::spawn(move || {
+            let mut sent = 0;
+            while sent < THREAD_MESSAGES {
+                if counter.notify(TestMessage(1)).is_ok() {
+                    sent += 1;
+                } else {
+                    std::thread::yield_now();
+                }
+            }
+        })
+    }
+
+    #[drogue_test]
+    async fn test_producer_threads(spawner: Spawner, mut context: TestContext<MailboxDevice>) {
+        let done = context.signal();
+        let expected = 3 * PRODUCER_MESSAGES + 2 * THREAD_MESSAGES;
+        context.configure(MailboxDevice {
+            counter: ActorContext::new(Counter::new(done, expected)),
+            p1: ActorContext::new(Producer { counter: None }),
+            p2: ActorContext::new(Producer { counter: None }),
+            p3: ActorContext::new(Producer { counter: None }),
+        });
+
+        let counter = context
+            .mount(|device| async move {
+                let counter = device.counter.mount((), spawner);
+                device.p1.mount(counter, spawner);
+                device.p2.mount(counter, spawner);
+                device.p3.mount(counter, spawner);
+                counter
+            })
+            .await;
+
+        // The threads send while the producer actors are sending on the executor thread
+        let threads = std::vec![
+            spawn_producer_thread(counter),
+            spawn_producer_thread(counter),
+        ];
+
+        done.wait_signaled().await;
+        assert_eq!(expected, done.message().unwrap().0);
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    }
+}
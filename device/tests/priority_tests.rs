@@ -0,0 +1,103 @@
+#![macro_use]
+#![allow(incomplete_features)]
+#![feature(min_type_alias_impl_trait)]
+#![feature(impl_trait_in_bindings)]
+#![feature(generic_associated_types)]
+#![feature(type_alias_impl_trait)]
+
+#[cfg(feature = "std")]
+mod tests {
+    extern crate std;
+    use drogue_device::{
+        actors::led::matrix::{LEDMatrix, MatrixCommand},
+        testutil::*,
+        *,
+    };
+    use drogue_device_macros::test as drogue_test;
+    use embassy::executor::{SendSpawner, Spawner};
+    use embedded_hal::digital::v2::OutputPin;
+    use std::sync::Mutex;
+    use std::thread::ThreadId;
+
+    /// Pin recording the thread it was last driven from
+    #[derive(Clone, Copy)]
+    pub struct ThreadPin(&'static Mutex<Option<ThreadId>>);
+
+    impl ThreadPin {
+        fn driven(&self) -> Result<(), ()> {
+            self.0.lock().unwrap().replace(std::thread::current().id());
+            Ok(())
+        }
+    }
+
+    impl OutputPin for ThreadPin {
+        type Error = ();
+
+        fn set_low(&mut self) -> Result<(), ()> {
+            self.driven()
+        }
+
+        fn set_high(&mut self) -> Result<(), ()> {
+            self.driven()
+        }
+    }
+
+    /// Start a runner on another thread, standing in for an interrupt executor, and
+    /// return a spawner for it.
+    fn start_high_priority_executor() -> SendSpawner {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let runner = Box::leak(Box::new(TestRunner::new()));
+            runner.initialize(|spawner| sender.send(spawner.make_send()).unwrap());
+            loop {
+                runner.run_until_idle();
+                std::thread::yield_now();
+            }
+        });
+        receiver.recv().unwrap()
+    }
+
+    struct PriorityDevice {
+        matrix: ActorContext<'static, LEDMatrix<ThreadPin, 1, 1>>,
+        handler: ActorContext<'static, TestHandler>,
+    }
+
+    #[drogue_test]
+    async fn test_high_priority_executor(
+        spawner: Spawner,
+        mut context: TestContext<PriorityDevice>,
+    ) {
+        let driven = Box::leak(Box::new(Mutex::new(None)));
+        let pin = ThreadPin(driven);
+        let notified = context.signal();
+        context.configure(PriorityDevice {
+            matrix: ActorContext::new(LEDMatrix::new([pin], [pin])),
+            handler: ActorContext::new(TestHandler::new(notified)),
+        });
+
+        let high = SendActorSpawner::new(start_high_priority_executor());
+        let spawner = PrioritySpawner::new(spawner, high);
+        let (matrix, handler) = context
+            .mount(|device| async move {
+                (
+                    device.matrix.mount((), spawner),
+                    device.handler.mount((), spawner),
+                )
+            })
+            .await;
+
+        // The matrix renders on the other executor
+        matrix
+            .request(MatrixCommand::Render)
+            .unwrap()
+            .await
+            .unwrap();
+        let driven = driven.lock().unwrap().unwrap();
+        assert_ne!(std::thread::current().id(), driven);
+
+        // Other actors keep running on the executor mounting them
+        handler.notify(TestMessage(1)).unwrap();
+        notified.wait_signaled().await;
+        assert_eq!(1, notified.message().unwrap().0);
+    }
+}
@@ -0,0 +1,306 @@
+#![macro_use]
+#![allow(incomplete_features)]
+#![feature(min_type_alias_impl_trait)]
+#![feature(impl_trait_in_bindings)]
+#![feature(generic_associated_types)]
+#![feature(type_alias_impl_trait)]
+
+#[cfg(all(feature = "std", feature = "remote"))]
+mod tests {
+    extern crate std;
+    use core::cell::{Cell, RefCell};
+    use core::future::{ready, Future, Ready};
+    use core::pin::Pin;
+    use core::task::Poll;
+    use drogue_device::{kernel::remote::*, testutil::*, *};
+    use drogue_device_macros::test as drogue_test;
+    use embassy::executor::Spawner;
+    use embassy::time::{Duration, Timer};
+    use embassy::util::AtomicWaker;
+    use futures::future::poll_fn;
+    use serde::{Deserialize, Serialize};
+    use std::collections::VecDeque;
+
+    /// One direction of an in-memory link
+    struct Pipe {
+        data: RefCell<VecDeque<u8>>,
+        // Fail the next read, as if the link was broken
+        broken: Cell<bool>,
+        waker: AtomicWaker,
+    }
+
+    impl Pipe {
+        fn new() -> &'static Self {
+            Box::leak(Box::new(Self {
+                data: RefCell::new(VecDeque::new()),
+                broken: Cell::new(false),
+                waker: AtomicWaker::new(),
+            }))
+        }
+
+        fn break_once(&self) {
+            self.broken.set(true);
+            self.waker.wake();
+        }
+    }
+
+    /// One side of an in-memory link
+    struct PipeTransport {
+        rx: &'static Pipe,
+        tx: &'static Pipe,
+    }
+
+    impl Transport for PipeTransport {
+        type ReadableFuture<'m> = impl Future<Output = ()> + 'm;
+        fn readable<'m>(&'m mut self) -> Self::ReadableFuture<'m> {
+            poll_fn(move |cx| {
+                self.rx.waker.register(cx.waker());
+                if self.rx.data.borrow().is_empty() && !self.rx.broken.get() {
+                    Poll::Pending
+                } else {
+                    Poll::Ready(())
+                }
+            })
+        }
+
+        type ReadFuture<'m> = Ready<Result<usize, RemoteError>>;
+        fn read<'m>(&'m mut self, buf: &'m mut [u8]) -> Self::ReadFuture<'m> {
+            if self.rx.broken.replace(false) {
+                return ready(Err(RemoteError::Transport));
+            }
+            let mut data = self.rx.data.borrow_mut();
+            let len = buf.len().min(data.len());
+            for (b, d) in buf.iter_mut().zip(data.drain(..len)) {
+                *b = d;
+            }
+            ready(Ok(len))
+        }
+
+        type WriteFuture<'m> = Ready<Result<(), RemoteError>>;
+        fn write<'m>(&'m mut self, buf: &'m [u8]) -> Self::WriteFuture<'m> {
+            self.tx.data.borrow_mut().extend(buf);
+            self.tx.waker.wake();
+            ready(Ok(()))
+        }
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub enum CounterMessage {
+        Add(u32),
+        Get,
+    }
+
+    /// Keeps a running total, responding to every message with the total
+    pub struct Counter {
+        total: u32,
+    }
+
+    impl Actor for Counter {
+        type Message<'m> = CounterMessage;
+        type Response = u32;
+        type OnStartFuture<'m> = ImmediateFuture;
+        type OnMessageFuture<'m> = Ready<u32>;
+
+        fn on_start(self: Pin<&'_ mut Self>) -> Self::OnStartFuture<'_> {
+            ImmediateFuture::new()
+        }
+
+        fn on_message<'m>(
+            self: Pin<&'m mut Self>,
+            message: Self::Message<'m>,
+        ) -> Self::OnMessageFuture<'m> {
+            let this = self.get_mut();
+            if let CounterMessage::Add(value) = message {
+                this.total += value;
+            }
+            ready(this.total)
+        }
+    }
+
+    struct LinkedDevice {
+        counter: ActorContext<'static, Counter>,
+        gateway: RemoteEndpoint<Counter, PipeTransport>,
+        board: RemoteEndpoint<Counter, PipeTransport>,
+    }
+
+    #[drogue_test]
+    async fn test_requests_over_link(spawner: Spawner, mut context: TestContext<LinkedDevice>) {
+        let uplink = Pipe::new();
+        let downlink = Pipe::new();
+        context.configure(LinkedDevice {
+            counter: ActorContext::new(Counter { total: 0 }),
+            gateway: RemoteEndpoint::new(PipeTransport {
+                rx: uplink,
+                tx: downlink,
+            }),
+            board: RemoteEndpoint::new(PipeTransport {
+                rx: downlink,
+                tx: uplink,
+            }),
+        });
+
+        let (board, gateway) = context
+            .mount(|device| async move {
+                let counter = device.counter.mount((), spawner);
+                device.gateway.mount(Some(counter), spawner);
+                device.board.mount(None, spawner);
+                (device.board.address(), device.gateway.address())
+            })
+            .await;
+
+        // Messages are handled by the counter behind the gateway in the order they were sent
+        assert!(board.notify(CounterMessage::Add(2)).is_ok());
+        assert_eq!(Ok(5), board.request(CounterMessage::Add(3)).await);
+        assert_eq!(Ok(5), board.request(CounterMessage::Get).await);
+
+        // There is no actor behind the board endpoint to handle requests
+        assert_eq!(
+            Err(RemoteError::Abandoned),
+            gateway.request(CounterMessage::Get).await
+        );
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub enum RelayMessage {
+        Ask,
+        Get,
+    }
+
+    /// Answers `Get` with its value, and `Ask` with the sum of its value and the value of
+    /// the relay on the other side of the link.
+    pub struct Relay {
+        value: u32,
+        remote: Option<RemoteAddress<Relay>>,
+    }
+
+    impl Actor for Relay {
+        type Configuration = RemoteAddress<Relay>;
+        type Message<'m> = RelayMessage;
+        type Response = u32;
+        type OnStartFuture<'m> = ImmediateFuture;
+        type OnMessageFuture<'m> = impl Future<Output = u32> + 'm;
+
+        fn on_mount(&mut self, _: Address<'static, Self>, remote: RemoteAddress<Relay>) {
+            self.remote.replace(remote);
+        }
+
+        fn on_start(self: Pin<&'_ mut Self>) -> Self::OnStartFuture<'_> {
+            ImmediateFuture::new()
+        }
+
+        fn on_message<'m>(
+            self: Pin<&'m mut Self>,
+            message: Self::Message<'m>,
+        ) -> Self::OnMessageFuture<'m> {
+            async move {
+                match message {
+                    RelayMessage::Ask => {
+                        let remote = self.remote.unwrap();
+                        self.value + remote.request(RelayMessage::Get).await.unwrap()
+                    }
+                    RelayMessage::Get => self.value,
+                }
+            }
+        }
+    }
+
+    struct RelayDevice {
+        gateway_relay: ActorContext<'static, Relay>,
+        board_relay: ActorContext<'static, Relay>,
+        gateway: RemoteEndpoint<Relay, PipeTransport>,
+        board: RemoteEndpoint<Relay, PipeTransport>,
+    }
+
+    #[drogue_test]
+    async fn test_requests_while_handling_request(
+        spawner: Spawner,
+        mut context: TestContext<RelayDevice>,
+    ) {
+        let uplink = Pipe::new();
+        let downlink = Pipe::new();
+        context.configure(RelayDevice {
+            gateway_relay: ActorContext::new(Relay {
+                value: 10,
+                remote: None,
+            }),
+            board_relay: ActorContext::new(Relay {
+                value: 1,
+                remote: None,
+            }),
+            gateway: RemoteEndpoint::new(PipeTransport {
+                rx: uplink,
+                tx: downlink,
+            }),
+            board: RemoteEndpoint::new(PipeTransport {
+                rx: downlink,
+                tx: uplink,
+            }),
+        });
+
+        let board = context
+            .mount(|device| async move {
+                let gateway_relay = device
+                    .gateway_relay
+                    .mount(device.gateway.address(), spawner);
+                let board_relay = device.board_relay.mount(device.board.address(), spawner);
+                device.gateway.mount(Some(gateway_relay), spawner);
+                device.board.mount(Some(board_relay), spawner);
+                device.board.address()
+            })
+            .await;
+
+        // The gateway relay asks the board relay back through the same link while the
+        // gateway endpoint is waiting for it to respond
+        assert_eq!(Ok(11), board.request(RelayMessage::Ask).await);
+    }
+
+    struct BoardDevice {
+        board: RemoteEndpoint<Counter, PipeTransport>,
+    }
+
+    #[drogue_test]
+    async fn test_dropped_requests_reclaimed(
+        spawner: Spawner,
+        mut context: TestContext<BoardDevice>,
+    ) {
+        // Nothing answers on the other side of the link
+        let uplink = Pipe::new();
+        let downlink = Pipe::new();
+        context.configure(BoardDevice {
+            board: RemoteEndpoint::new(PipeTransport {
+                rx: downlink,
+                tx: uplink,
+            }),
+        });
+
+        let board = context
+            .mount(|device| async move {
+                device.board.mount(None, spawner);
+                device.board.address()
+            })
+            .await;
+
+        // Give up on as many requests as may be pending at the same time
+        for _ in 0..4 {
+            let request = board.request(CounterMessage::Get);
+            futures::pin_mut!(request);
+            assert!(poll_fn(|cx| Poll::Ready(request.as_mut().poll(cx)))
+                .await
+                .is_pending());
+        }
+        assert_eq!(
+            Err(RemoteError::NoAvailableSignal),
+            board.request(CounterMessage::Get).await
+        );
+
+        // The requests are abandoned once the transport fails, freeing their slots
+        downlink.break_once();
+        Timer::after(Duration::from_millis(10)).await;
+
+        let request = board.request(CounterMessage::Get);
+        futures::pin_mut!(request);
+        assert!(poll_fn(|cx| Poll::Ready(request.as_mut().poll(cx)))
+            .await
+            .is_pending());
+    }
+}
@@ -0,0 +1,77 @@
+#![macro_use]
+#![allow(incomplete_features)]
+#![feature(min_type_alias_impl_trait)]
+#![feature(impl_trait_in_bindings)]
+#![feature(generic_associated_types)]
+#![feature(type_alias_impl_trait)]
+
+#[cfg(feature = "std")]
+mod tests {
+    extern crate std;
+    use core::future::Future;
+    use core::pin::Pin;
+    use drogue_device::{
+        kernel::actor::{ActorError, SignalError},
+        testutil::*,
+        *,
+    };
+    use drogue_device_macros::test as drogue_test;
+    use embassy::executor::Spawner;
+    use embassy::time::{Duration, Instant, Timer};
+
+    /// Responds to each request after sleeping for the requested duration
+    pub struct Sleeper;
+
+    impl Actor for Sleeper {
+        type Message<'m> = Duration;
+        type OnStartFuture<'m> = ImmediateFuture;
+        type OnMessageFuture<'m> = impl Future<Output = ()> + 'm;
+
+        fn on_start(self: Pin<&'_ mut Self>) -> Self::OnStartFuture<'_> {
+            ImmediateFuture::new()
+        }
+
+        fn on_message<'m>(
+            self: Pin<&'m mut Self>,
+            message: Self::Message<'m>,
+        ) -> Self::OnMessageFuture<'m> {
+            async move { Timer::after(message).await }
+        }
+    }
+
+    struct SleeperDevice {
+        sleeper: ActorContext<'static, Sleeper>,
+    }
+
+    #[drogue_test(virtual_time)]
+    async fn test_request_timeout(spawner: Spawner, mut context: TestContext<SleeperDevice>) {
+        context.configure(SleeperDevice {
+            sleeper: ActorContext::new(Sleeper),
+        });
+
+        let sleeper = context
+            .mount(|device| async move { device.sleeper.mount((), spawner) })
+            .await;
+
+        let before = Instant::now();
+        let result = sleeper
+            .request_with_timeout(Duration::from_secs(10), Duration::from_secs(1))
+            .unwrap()
+            .await;
+        assert!(matches!(result, Err(ActorError::Timeout)));
+        assert_eq!(1, Instant::now().duration_since(before).as_secs());
+
+        // The only signal slot is held until the actor is done with the request
+        assert!(matches!(
+            sleeper.request_with_timeout(Duration::from_secs(0), Duration::from_secs(1)),
+            Err(ActorError::Signal(SignalError::NoAvailableSignal))
+        ));
+
+        Timer::after(Duration::from_secs(10)).await;
+        let result = sleeper
+            .request_with_timeout(Duration::from_secs(0), Duration::from_secs(1))
+            .unwrap()
+            .await;
+        assert!(matches!(result, Ok(())));
+    }
+}
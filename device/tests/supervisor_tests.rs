@@ -0,0 +1,160 @@
+#![macro_use]
+#![allow(incomplete_features)]
+#![feature(min_type_alias_impl_trait)]
+#![feature(impl_trait_in_bindings)]
+#![feature(generic_associated_types)]
+#![feature(type_alias_impl_trait)]
+
+#[cfg(feature = "std")]
+mod tests {
+    extern crate std;
+    use core::pin::Pin;
+    use drogue_device::{testutil::*, *};
+    use drogue_device_macros::test as drogue_test;
+    use embassy::executor::Spawner;
+    use embassy::time::{Duration, Instant, Timer};
+
+    /// Reports a failure on every message, signalling the number of restarts when started
+    /// and the number of stops when stopped
+    pub struct Flaky {
+        started: &'static TestSignal,
+        stopped: &'static TestSignal,
+        me: Option<Address<'static, Flaky>>,
+        stops: u32,
+        restarts: u32,
+    }
+
+    impl Flaky {
+        fn new(started: &'static TestSignal, stopped: &'static TestSignal) -> Self {
+            Self {
+                started,
+                stopped,
+                me: None,
+                stops: 0,
+                restarts: 0,
+            }
+        }
+    }
+
+    impl Actor for Flaky {
+        type Message<'m> = TestMessage;
+        type OnStartFuture<'m> = ImmediateFuture;
+        type OnMessageFuture<'m> = ImmediateFuture;
+
+        fn on_mount(&mut self, me: Address<'static, Self>, _: Self::Configuration) {
+            self.me.replace(me);
+        }
+
+        fn on_start(self: Pin<&'_ mut Self>) -> Self::OnStartFuture<'_> {
+            assert_eq!(self.stops, self.restarts);
+            self.started.signal(TestMessage(self.restarts));
+            ImmediateFuture::new()
+        }
+
+        fn on_message<'m>(
+            self: Pin<&'m mut Self>,
+            _: Self::Message<'m>,
+        ) -> Self::OnMessageFuture<'m> {
+            self.me.unwrap().fail();
+            ImmediateFuture::new()
+        }
+
+        fn on_stop(&mut self) {
+            self.stops += 1;
+            self.stopped.signal(TestMessage(self.stops));
+        }
+
+        fn on_restart(&mut self) {
+            self.restarts += 1;
+        }
+    }
+
+    struct SupervisedDevice {
+        flaky: Supervisor<Flaky>,
+    }
+
+    #[drogue_test]
+    async fn test_restart(spawner: Spawner, mut context: TestContext<SupervisedDevice>) {
+        let started = context.signal();
+        let stopped = context.signal();
+        context.configure(SupervisedDevice {
+            flaky: Supervisor::new(Flaky::new(started, stopped), RestartPolicy::Always),
+        });
+
+        let flaky = context
+            .mount(|device| async move { device.flaky.mount((), spawner) })
+            .await;
+
+        started.wait_signaled().await;
+        assert_eq!(0, started.message().unwrap().0);
+
+        flaky.notify(TestMessage(0)).unwrap();
+        started.wait_signaled().await;
+        assert_eq!(1, started.message().unwrap().0);
+
+        flaky.notify(TestMessage(0)).unwrap();
+        started.wait_signaled().await;
+        assert_eq!(2, started.message().unwrap().0);
+    }
+
+    #[drogue_test(virtual_time)]
+    async fn test_never_restart(spawner: Spawner, mut context: TestContext<SupervisedDevice>) {
+        let started = context.signal();
+        let stopped = context.signal();
+        context.configure(SupervisedDevice {
+            flaky: Supervisor::new(Flaky::new(started, stopped), RestartPolicy::Never),
+        });
+
+        let flaky = context
+            .mount(|device| async move { device.flaky.mount((), spawner) })
+            .await;
+
+        started.wait_signaled().await;
+        assert_eq!(0, started.message().unwrap().0);
+
+        flaky.notify(TestMessage(0)).unwrap();
+        stopped.wait_signaled().await;
+        assert_eq!(1, stopped.message().unwrap().0);
+
+        // The actor stays stopped
+        Timer::after(Duration::from_secs(60)).await;
+        assert_eq!(0, started.message().unwrap().0);
+        assert_eq!(1, stopped.message().unwrap().0);
+    }
+
+    #[drogue_test(virtual_time)]
+    async fn test_backoff_restart(spawner: Spawner, mut context: TestContext<SupervisedDevice>) {
+        let started = context.signal();
+        let stopped = context.signal();
+        let policy = RestartPolicy::Backoff {
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(4),
+        };
+        context.configure(SupervisedDevice {
+            flaky: Supervisor::new(Flaky::new(started, stopped), policy),
+        });
+
+        let flaky = context
+            .mount(|device| async move { device.flaky.mount((), spawner) })
+            .await;
+
+        started.wait_signaled().await;
+
+        // The delay doubles with each consecutive failure, up to the maximum
+        for (restarts, delay) in [1, 2, 4, 4].iter().enumerate() {
+            let failed = Instant::now();
+            flaky.notify(TestMessage(0)).unwrap();
+            started.wait_signaled().await;
+            assert_eq!(restarts as u32 + 1, started.message().unwrap().0);
+            assert_eq!(*delay, Instant::now().duration_since(failed).as_secs());
+        }
+
+        // Having run for the maximum delay without failing, the delay starts over
+        Timer::after(Duration::from_secs(4)).await;
+        let failed = Instant::now();
+        flaky.notify(TestMessage(0)).unwrap();
+        started.wait_signaled().await;
+        assert_eq!(5, started.message().unwrap().0);
+        assert_eq!(1, Instant::now().duration_since(failed).as_secs());
+    }
+}
@@ -0,0 +1,152 @@
+#![macro_use]
+#![allow(incomplete_features)]
+#![feature(min_type_alias_impl_trait)]
+#![feature(impl_trait_in_bindings)]
+#![feature(generic_associated_types)]
+#![feature(type_alias_impl_trait)]
+
+#[cfg(feature = "std")]
+mod tests {
+    extern crate std;
+    use drogue_device::{kernel::sync::*, testutil::*};
+    use drogue_device_macros::test as drogue_test;
+    use embassy::executor::Spawner;
+    use embassy::time::{Duration, Timer};
+    use futures::future::{join, join3, join4, join_all};
+    use std::vec::Vec;
+
+    struct MutexDevice {
+        mutex: Mutex<Vec<u32>>,
+    }
+
+    struct SemaphoreDevice {
+        semaphore: Semaphore,
+        order: Mutex<Vec<u32>>,
+    }
+
+    struct RwLockDevice {
+        rwlock: RwLock<Vec<u32>>,
+    }
+
+    /// Hold the lock for a while, letting the other tasks queue up
+    async fn hold() {
+        Timer::after(Duration::from_millis(10)).await;
+    }
+
+    #[drogue_test]
+    async fn test_mutex_fifo(_spawner: Spawner, mut context: TestContext<MutexDevice>) {
+        context.configure(MutexDevice {
+            mutex: Mutex::new(Vec::new()),
+        });
+        let device = context.mount(|device| async move { device }).await;
+        let mutex = &device.mutex;
+
+        let append = |value| async move {
+            mutex.lock().await.push(value);
+        };
+        join4(
+            async {
+                let mut guard = mutex.lock().await;
+                hold().await;
+                guard.push(0);
+            },
+            append(1),
+            append(2),
+            append(3),
+        )
+        .await;
+
+        assert_eq!(&[0, 1, 2, 3], &mutex.lock().await[..]);
+        assert!(mutex.try_lock().is_some());
+    }
+
+    #[drogue_test]
+    async fn test_mutex_waiters_overflow(_spawner: Spawner, mut context: TestContext<MutexDevice>) {
+        context.configure(MutexDevice {
+            mutex: Mutex::new(Vec::new()),
+        });
+        let device = context.mount(|device| async move { device }).await;
+        let mutex = &device.mutex;
+
+        // More tasks than the eight the wait queue holds, the others busy-poll
+        const TASKS: u32 = 12;
+        let guard = mutex.lock().await;
+        join(
+            async move {
+                hold().await;
+                drop(guard);
+            },
+            join_all((0..TASKS).map(|value| async move {
+                mutex.lock().await.push(value);
+            })),
+        )
+        .await;
+
+        let values = mutex.lock().await.clone();
+        // The queued tasks are served in order, and the others once there is room
+        assert_eq!(&[0, 1, 2, 3, 4, 5, 6, 7], &values[..8]);
+        let mut sorted = values.clone();
+        sorted.sort();
+        assert_eq!((0..TASKS).collect::<Vec<_>>(), sorted);
+    }
+
+    #[drogue_test]
+    async fn test_semaphore(_spawner: Spawner, mut context: TestContext<SemaphoreDevice>) {
+        context.configure(SemaphoreDevice {
+            semaphore: Semaphore::new(2),
+            order: Mutex::new(Vec::new()),
+        });
+        let device = context.mount(|device| async move { device }).await;
+        let semaphore = &device.semaphore;
+        let order = &device.order;
+
+        let use_permit = |value| async move {
+            let _permit = semaphore.acquire().await;
+            order.lock().await.push(value);
+            hold().await;
+        };
+        join3(use_permit(0), use_permit(1), async {
+            // Both permits are taken until one of the first tasks is done
+            assert!(semaphore.try_acquire().is_none());
+            let _permit = semaphore.acquire().await;
+            order.lock().await.push(2);
+        })
+        .await;
+
+        assert_eq!(&[0, 1, 2], &order.lock().await[..]);
+        assert_eq!(2, semaphore.available());
+    }
+
+    #[drogue_test]
+    async fn test_rwlock(_spawner: Spawner, mut context: TestContext<RwLockDevice>) {
+        context.configure(RwLockDevice {
+            rwlock: RwLock::new(Vec::new()),
+        });
+        let device = context.mount(|device| async move { device }).await;
+        let rwlock = &device.rwlock;
+
+        join4(
+            async {
+                // Readers share the lock
+                let first = rwlock.read().await;
+                let second = rwlock.read().await;
+                hold().await;
+                assert!(first.is_empty() && second.is_empty());
+            },
+            async {
+                rwlock.write().await.push(1);
+            },
+            async {
+                // Arrives after the writer, so sees its update
+                assert_eq!(&[1], &rwlock.read().await[..]);
+            },
+            async {
+                rwlock.write().await.push(2);
+            },
+        )
+        .await;
+
+        assert_eq!(&[1, 2], &rwlock.read().await[..]);
+        assert!(rwlock.try_write().is_some());
+    }
+}
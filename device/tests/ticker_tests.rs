@@ -15,10 +15,10 @@ mod tests {
 
     struct TickerDevice {
         handler: ActorContext<'static, TestHandler>,
-        ticker: ActorContext<'static, Ticker<'static, TestHandler>>,
+        ticker: ActorContext<'static, Ticker<'static, TestMessage>>,
     }
 
-    #[drogue_test]
+    #[drogue_test(virtual_time)]
     async fn test_ticker(spawner: Spawner, mut context: TestContext<TickerDevice>) {
         let notified = context.signal();
         context.configure(TickerDevice {
@@ -29,7 +29,10 @@ mod tests {
         context
             .mount(|device| async move {
                 let handler_addr = device.handler.mount((), spawner);
-                (device.ticker.mount(handler_addr, spawner), handler_addr)
+                (
+                    device.ticker.mount(handler_addr.recipient(), spawner),
+                    handler_addr,
+                )
             })
             .await;
 
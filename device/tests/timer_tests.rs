@@ -15,10 +15,10 @@ mod tests {
 
     struct ScheduleDevice {
         handler: ActorContext<'static, TestHandler>,
-        timer: ActorContext<'static, Timer<'static, TestHandler>>,
+        timer: ActorContext<'static, Timer<'static, TestMessage>>,
     }
 
-    #[drogue_test]
+    #[drogue_test(virtual_time)]
     async fn test_schedule(spawner: Spawner, mut context: TestContext<ScheduleDevice>) {
         let notified = context.signal();
         context.configure(ScheduleDevice {
@@ -37,7 +37,7 @@ mod tests {
         timer_addr
             .notify(TimerMessage::schedule(
                 time::Duration::from_secs(1),
-                handler_addr,
+                handler_addr.recipient(),
                 TestMessage(1),
             ))
             .unwrap();
@@ -48,10 +48,10 @@ mod tests {
     }
 
     struct DelayDevice {
-        timer: ActorContext<'static, Timer<'static, TestHandler>>,
+        timer: ActorContext<'static, Timer<'static, TestMessage>>,
     }
 
-    #[drogue_test]
+    #[drogue_test(virtual_time)]
     async fn test_delay(spawner: Spawner, mut context: TestContext<DelayDevice>) {
         context.configure(DelayDevice {
             timer: ActorContext::new(Timer::new()),
@@ -65,8 +65,37 @@ mod tests {
         timer
             .request(TimerMessage::Delay(time::Duration::from_secs(1)))
             .unwrap()
-            .await;
+            .await
+            .unwrap();
         let after = time::Instant::now();
         assert!(after.as_secs() >= before.as_secs() + 1);
     }
+
+    #[drogue_test(virtual_time)]
+    async fn test_long_delay(spawner: Spawner, mut context: TestContext<DelayDevice>) {
+        context.configure(DelayDevice {
+            timer: ActorContext::new(Timer::new()),
+        });
+
+        let timer = context
+            .mount(|device| async move { device.timer.mount((), spawner) })
+            .await;
+
+        let before = time::Instant::now();
+        timer
+            .request(TimerMessage::Delay(time::Duration::from_secs(12 * 3600)))
+            .unwrap()
+            .await
+            .unwrap();
+        let after = time::Instant::now();
+        assert_eq!(12 * 3600, after.duration_since(before).as_secs());
+    }
+
+    #[drogue_test(virtual_time)]
+    async fn test_advance(_spawner: Spawner, mut context: TestContext<DelayDevice>) {
+        let before = time::Instant::now();
+        context.advance(time::Duration::from_secs(60));
+        let after = time::Instant::now();
+        assert_eq!(60, after.duration_since(before).as_secs());
+    }
 }
@@ -0,0 +1,98 @@
+#![macro_use]
+#![allow(incomplete_features)]
+#![feature(min_type_alias_impl_trait)]
+#![feature(impl_trait_in_bindings)]
+#![feature(generic_associated_types)]
+#![feature(type_alias_impl_trait)]
+
+#[cfg(all(feature = "std", feature = "trace"))]
+mod tests {
+    extern crate std;
+    use core::pin::Pin;
+    use drogue_device::{
+        kernel::trace::{self, TraceEventKind},
+        testutil::*,
+        *,
+    };
+    use drogue_device_macros::test as drogue_test;
+    use embassy::executor::Spawner;
+    use embassy::time::{Duration, Timer};
+
+    /// Passes every message on to the next relay, if any
+    pub struct Relay {
+        next: Option<Address<'static, Relay>>,
+    }
+
+    impl Actor for Relay {
+        type Configuration = Option<Address<'static, Relay>>;
+        type Message<'m> = ();
+        type OnStartFuture<'m> = ImmediateFuture;
+        type OnMessageFuture<'m> = ImmediateFuture;
+
+        fn on_mount(&mut self, _: Address<'static, Self>, config: Self::Configuration) {
+            self.next = config;
+        }
+
+        fn on_start(self: Pin<&'_ mut Self>) -> Self::OnStartFuture<'_> {
+            ImmediateFuture::new()
+        }
+
+        fn on_message<'m>(self: Pin<&'m mut Self>, _: ()) -> Self::OnMessageFuture<'m> {
+            if let Some(next) = self.next {
+                next.notify(()).unwrap();
+            }
+            ImmediateFuture::new()
+        }
+    }
+
+    struct RelayDevice {
+        first: ActorContext<'static, Relay>,
+        second: ActorContext<'static, Relay>,
+    }
+
+    #[drogue_test]
+    async fn test_events_per_instance(spawner: Spawner, mut context: TestContext<RelayDevice>) {
+        context.configure(RelayDevice {
+            first: ActorContext::new(Relay { next: None }),
+            second: ActorContext::new(Relay { next: None }),
+        });
+
+        let address = context
+            .mount(|device| async move {
+                let second = device.second.mount(None, spawner);
+                device.first.mount(Some(second), spawner)
+            })
+            .await;
+
+        address.request(()).unwrap().await.unwrap();
+        // Let the second relay handle the notification
+        Timer::after(Duration::from_millis(10)).await;
+
+        let events = trace::take();
+        let kinds: std::vec::Vec<_> = events.iter().map(|event| event.kind).collect();
+        assert_eq!(
+            &[
+                TraceEventKind::Request,
+                TraceEventKind::Start,
+                TraceEventKind::Notify,
+                TraceEventKind::Finish,
+                TraceEventKind::Start,
+                TraceEventKind::Finish,
+            ],
+            &kinds[..]
+        );
+
+        // Both relays are of the same type, but are told apart by their context
+        let (first, second) = (events[0].actor, events[2].actor);
+        assert_eq!(first.name, second.name);
+        assert_ne!(first.id, second.id);
+        assert_eq!(first, events[1].actor);
+        assert_eq!(first, events[3].actor);
+        assert_eq!(second, events[4].actor);
+        assert_eq!(second, events[5].actor);
+
+        // The notification was sent while the first relay was being polled
+        assert_eq!(None, events[0].sender);
+        assert_eq!(Some(first), events[2].sender);
+    }
+}
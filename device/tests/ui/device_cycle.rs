@@ -0,0 +1,13 @@
+use drogue_device::device;
+
+device! {
+    struct CyclicDevice: CYCLIC {
+        ping: Ping,
+        pong: Pong,
+    }
+
+    ping -> pong;
+    pong -> ping;
+}
+
+fn main() {}
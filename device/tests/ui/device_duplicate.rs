@@ -0,0 +1,10 @@
+use drogue_device::device;
+
+device! {
+    struct DuplicateDevice: DUPLICATE {
+        handler: Handler,
+        handler: Handler,
+    }
+}
+
+fn main() {}
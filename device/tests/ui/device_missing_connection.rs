@@ -0,0 +1,11 @@
+use drogue_device::device;
+
+device! {
+    struct UnwiredDevice: UNWIRED {
+        button: Button,
+    }
+
+    button -> handler;
+}
+
+fn main() {}
@@ -40,7 +40,7 @@ pub struct MyDevice {
     button: ActorContext<'static, Button<'static, PortInput<'static, P0_14>, Statistics>>,
     statistics: ActorContext<'static, Statistics>,
     server: ActorContext<'static, EchoServer<'static, Uarte<'static, UARTE0>>>,
-    ticker: ActorContext<'static, Ticker<'static, LedMatrix>>,
+    ticker: ActorContext<'static, Ticker<'static, MatrixCommand<'static>>>,
     matrix: ActorContext<'static, LedMatrix>,
 }
 
@@ -97,7 +97,7 @@ async fn main(spawner: embassy::executor::Spawner, p: Peripherals) {
             let statistics = device.statistics.mount((), spawner);
             device.server.mount((matrix, statistics), spawner);
             device.button.mount(statistics, spawner);
-            let ticker = device.ticker.mount(matrix, spawner);
+            let ticker = device.ticker.mount(matrix.recipient(), spawner);
             ticker.notify(TickerCommand::Start).unwrap();
         })
         .await;
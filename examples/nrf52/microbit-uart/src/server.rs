@@ -48,7 +48,11 @@ impl<'a, U: Write + Read + 'a> Actor for EchoServer<'a, U> {
         let statistics = self.statistics.unwrap();
         async move {
             for c in r"Hello, World!".chars() {
-                matrix.request(MatrixCommand::ApplyFrame(&c)).unwrap().await;
+                matrix
+                    .request(MatrixCommand::ApplyFrame(&c))
+                    .unwrap()
+                    .await
+                    .unwrap();
                 Timer::after(Duration::from_millis(200)).await;
             }
             matrix.notify(MatrixCommand::Clear).unwrap();
@@ -65,11 +69,13 @@ impl<'a, U: Write + Read + 'a> Actor for EchoServer<'a, U> {
                 matrix
                     .request(MatrixCommand::ApplyFrame(&(buf[0] as char)))
                     .unwrap()
-                    .await;
+                    .await
+                    .unwrap();
                 statistics
                     .request(StatisticsCommand::IncrementCharacterCount)
                     .unwrap()
-                    .await;
+                    .await
+                    .unwrap();
             }
         }
     }
@@ -89,7 +89,7 @@ async fn main(spawner: embassy::executor::Spawner) {
             .expect("Error joining wifi");
             log::info!("WiFi network joined");
 
-            let socket = Socket::new(wifi, wifi.open().await);
+            let socket = Socket::new(wifi, wifi.open().await.expect("Error opening socket"));
             #[cfg(feature = "tls")]
             let socket = TlsSocket::wrap(
                 socket,
@@ -100,7 +100,7 @@ async fn main(spawner: embassy::executor::Spawner) {
         })
         .await;
 
-    app.request(Command::Send).unwrap().await;
+    app.request(Command::Send).unwrap().await.unwrap();
 }
 
 pub struct DummyPin {}
@@ -16,14 +16,17 @@ mod mypack;
 use myactor::*;
 use mypack::*;
 
-pub struct MyDevice {
-    counter: AtomicU32,
-    a: ActorContext<'static, MyActor>,
-    b: ActorContext<'static, MyActor>,
-    p: MyPack,
-}
+device! {
+    pub struct MyDevice: DEVICE {
+        counter: state AtomicU32,
+        a: MyActor,
+        b: MyActor,
+        p: package MyPack,
+    }
 
-static DEVICE: DeviceContext<MyDevice> = DeviceContext::new();
+    a => &device.counter;
+    b => &device.counter;
+}
 
 #[embassy::main]
 async fn main(spawner: embassy::executor::Spawner) {
@@ -32,28 +35,25 @@ async fn main(spawner: embassy::executor::Spawner) {
         .format_timestamp_nanos()
         .init();
 
-    DEVICE.configure(MyDevice {
+    MyDevice::configure(MyDeviceConfig {
         counter: AtomicU32::new(0),
-        a: ActorContext::new(MyActor::new("a")),
-        b: ActorContext::new(MyActor::new("b")),
+        a: MyActor::new("a"),
+        b: MyActor::new("b"),
         p: MyPack::new(),
     });
 
-    let (a_addr, b_addr, c_addr) = DEVICE
-        .mount(|device| async move {
-            let a_addr = device.a.mount(&device.counter, spawner);
-            let b_addr = device.b.mount(&device.counter, spawner);
-            let c_addr = device.p.mount((), spawner);
-            (a_addr, b_addr, c_addr)
-        })
-        .await;
+    let MyDeviceAddresses {
+        a: a_addr,
+        b: b_addr,
+        p: c_addr,
+    } = MyDevice::mount(spawner).await;
 
     loop {
         time::Timer::after(time::Duration::from_secs(1)).await;
         // Send that completes immediately when message is enqueued
         a_addr.notify(SayHello("World")).unwrap();
         // Send that waits until message is processed
-        b_addr.request(SayHello("You")).unwrap().await;
+        b_addr.request(SayHello("You")).unwrap().await.unwrap();
 
         // Actor uses a different counter
         c_addr.notify(SayHello("There")).unwrap();
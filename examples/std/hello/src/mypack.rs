@@ -22,6 +22,7 @@ impl MyPack {
 impl Package for MyPack {
     type Primary = MyActor;
     type Configuration = ();
+    type ShutdownFuture = impl core::future::Future<Output = ()>;
     fn mount<S: ActorSpawner>(
         &'static self,
         _: Self::Configuration,
@@ -29,4 +30,8 @@ impl Package for MyPack {
     ) -> Address<Self::Primary> {
         self.c.mount(&self.counter, spawner)
     }
+
+    fn shutdown(&'static self) -> Self::ShutdownFuture {
+        self.c.shutdown()
+    }
 }
@@ -73,15 +73,17 @@ type Led4Pin = Output<'static, PB7>;
 
 type MyApp = App<Address<'static, LoraActor<Sx127x<'static>>>, Led4Pin, Led2Pin, Led3Pin, Led1Pin>;
 
-pub struct MyDevice {
-    lora: ActorContext<'static, LoraActor<Sx127x<'static>>>,
+device! {
+    pub struct MyDevice: DEVICE {
+        lora: LoraActor<Sx127x<'static>>,
+        button: Button<'static, ExtiInput<'static, PB2>, MyApp>,
+        app: MyApp,
+    }
 
-    button: ActorContext<'static, Button<'static, ExtiInput<'static, PB2>, MyApp>>,
-    app: ActorContext<'static, MyApp>,
+    button -> app;
+    app -> lora => AppConfig { lora };
 }
 
-static DEVICE: DeviceContext<MyDevice> = DeviceContext::new();
-
 #[embassy::main(
     config = "embassy_stm32::Config::default().rcc(embassy_stm32::rcc::Config::default().clock_src(embassy_stm32::rcc::ClockSrc::HSI16))"
 )]
@@ -136,23 +138,17 @@ async fn main(spawner: embassy::executor::Spawner, mut p: Peripherals) {
 
     log::info!("Configuring with config {:?}", config);
 
-    DEVICE.configure(MyDevice {
-        app: ActorContext::new(App::new(AppInitConfig {
+    MyDevice::configure(MyDeviceConfig {
+        app: App::new(AppInitConfig {
             tx_led: led2,
             green_led: led1,
             init_led: led4,
             user_led: led3,
             lora: config,
-        })),
-        lora: ActorContext::new(LoraActor::new(lora)),
-        button: ActorContext::new(Button::new(pin)),
+        }),
+        lora: LoraActor::new(lora),
+        button: Button::new(pin),
     });
 
-    DEVICE
-        .mount(|device| async move {
-            let lora = device.lora.mount((), spawner);
-            let app = device.app.mount(AppConfig { lora }, spawner);
-            device.button.mount(app, spawner);
-        })
-        .await;
+    MyDevice::mount(spawner).await;
 }
@@ -0,0 +1,400 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+
+/// Methods that are moved into the `Actor` implementation rather than becoming handlers.
+const LIFECYCLE_METHODS: &[&str] = &["on_mount", "on_stop", "on_restart", "priority"];
+
+struct Handler {
+    method: syn::ImplItemMethod,
+    variant: syn::Ident,
+    args: Vec<(syn::Ident, syn::Type)>,
+    output: syn::Type,
+}
+
+pub fn expand(mut item: syn::ItemImpl) -> TokenStream {
+    let mut fail = false;
+    if let Some((_, path, _)) = &item.trait_ {
+        path.span()
+            .unwrap()
+            .error("#[actor] must be used on an inherent impl block")
+            .emit();
+        fail = true;
+    }
+
+    let self_ty = item.self_ty.clone();
+    let actor_name = match &*self_ty {
+        syn::Type::Path(tp) => tp.path.segments.last().unwrap().ident.clone(),
+        _ => {
+            self_ty
+                .span()
+                .unwrap()
+                .error("#[actor] must be used on an impl block of a named type")
+                .emit();
+            return TokenStream::new();
+        }
+    };
+
+    for param in item.generics.params.iter() {
+        if let syn::GenericParam::Const(c) = param {
+            c.span()
+                .unwrap()
+                .error("#[actor] does not support const generic parameters")
+                .emit();
+            fail = true;
+        }
+    }
+
+    let mut actor_types = Vec::new();
+    let mut actor_consts = Vec::new();
+    let mut actor_methods = Vec::new();
+    let mut on_start = false;
+    let mut handlers = Vec::new();
+    let mut inherent = Vec::new();
+
+    for impl_item in item.items.drain(..) {
+        match impl_item {
+            syn::ImplItem::Type(t) => actor_types.push(t),
+            syn::ImplItem::Const(c) => actor_consts.push(c),
+            syn::ImplItem::Method(m) => {
+                let name = m.sig.ident.to_string();
+                if m.sig.asyncness.is_none() {
+                    if LIFECYCLE_METHODS.contains(&name.as_str()) {
+                        actor_methods.push(m);
+                    } else {
+                        inherent.push(syn::ImplItem::Method(m));
+                    }
+                } else if name == "on_start" {
+                    on_start = true;
+                    inherent.push(syn::ImplItem::Method(m));
+                } else {
+                    match parse_handler(m) {
+                        Ok(handler) => handlers.push(handler),
+                        Err(_) => fail = true,
+                    }
+                }
+            }
+            other => inherent.push(other),
+        }
+    }
+
+    if fail {
+        return TokenStream::new();
+    }
+
+    let message_name = format_ident!("{}Message", actor_name);
+    let response_name = format_ident!("{}Response", actor_name);
+
+    let generics = &item.generics;
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let params: Vec<_> = generics.params.iter().collect();
+    let lifetimes: Vec<_> = generics.lifetimes().map(|l| &l.lifetime).collect();
+    let types: Vec<_> = generics.type_params().map(|t| &t.ident).collect();
+    let where_predicates = where_clause.map(|w| &w.predicates);
+
+    // Message enum, parameterized by the lifetime of the message contents
+    let message_variants = handlers.iter().map(|h| {
+        let variant = &h.variant;
+        let tys = h.args.iter().map(|(_, ty)| ty);
+        if h.args.is_empty() {
+            quote!(#variant)
+        } else {
+            quote!(#variant(#(#tys),*))
+        }
+    });
+    let message_enum = quote! {
+        #[allow(dead_code)]
+        pub enum #message_name<'m, #(#params),*>
+        where
+            #where_predicates
+        {
+            #(#message_variants,)*
+            #[doc(hidden)]
+            __Phantom(
+                ::core::marker::PhantomData<(&'m (), #(&#lifetimes (),)* #(fn() -> #types,)*)>,
+                ::core::convert::Infallible,
+            ),
+        }
+    };
+    let message_ty = quote!(#message_name<'m, #(#lifetimes,)* #(#types),*>);
+
+    // Response enum, only carrying a marker when the actor is generic
+    let response_variants = handlers.iter().map(|h| {
+        let variant = &h.variant;
+        let output = &h.output;
+        quote!(#variant(#output))
+    });
+    let generic = !params.is_empty();
+    let response_marker = if generic {
+        quote! {
+            #[doc(hidden)]
+            __Phantom(
+                ::core::marker::PhantomData<fn() -> (#(&#lifetimes (),)* #(#types,)*)>,
+                ::core::convert::Infallible,
+            ),
+        }
+    } else {
+        quote!()
+    };
+    let response_enum = quote! {
+        pub enum #response_name #impl_generics #where_clause {
+            #(#response_variants,)*
+            #response_marker
+        }
+    };
+    let response_ty = quote!(#response_name #ty_generics);
+
+    // Dispatch of messages to the handler methods
+    let dispatch = handlers.iter().map(|h| {
+        let variant = &h.variant;
+        let method = &h.method.sig.ident;
+        let args: Vec<_> = h.args.iter().map(|(arg, _)| arg).collect();
+        let pattern = if args.is_empty() {
+            quote!(#message_name::#variant)
+        } else {
+            quote!(#message_name::#variant(#(#args),*))
+        };
+        quote! {
+            #pattern => #response_name::#variant(this.#method(#(#args),*).await),
+        }
+    });
+
+    let start = if on_start {
+        quote! {
+            let this = unsafe { self.get_unchecked_mut() };
+            this.on_start().await
+        }
+    } else {
+        quote!()
+    };
+
+    let actor_impl = quote! {
+        impl #impl_generics ::drogue_device::Actor for #self_ty #where_clause {
+            #(#actor_types)*
+            #(#actor_consts)*
+
+            #[rustfmt::skip]
+            type Message<'m> where Self: 'm = #message_ty;
+            type Response = #response_ty;
+
+            #[rustfmt::skip]
+            type OnStartFuture<'m> where Self: 'm = impl ::core::future::Future<Output = ()> + 'm;
+            fn on_start(self: ::core::pin::Pin<&'_ mut Self>) -> Self::OnStartFuture<'_> {
+                async move {
+                    #start
+                }
+            }
+
+            #[rustfmt::skip]
+            type OnMessageFuture<'m> where Self: 'm =
+                impl ::core::future::Future<Output = Self::Response> + 'm;
+            fn on_message<'m>(
+                self: ::core::pin::Pin<&'m mut Self>,
+                message: Self::Message<'m>,
+            ) -> Self::OnMessageFuture<'m> {
+                async move {
+                    let this = unsafe { self.get_unchecked_mut() };
+                    match message {
+                        #(#dispatch)*
+                        #message_name::__Phantom(_, never) => match never {},
+                    }
+                }
+            }
+
+            #(#actor_methods)*
+        }
+    };
+
+    // Strongly typed methods on the address of the actor, and on the address of a pool of it
+    let typed_methods = |bound: TokenStream| {
+        handlers
+            .iter()
+            .map(|h| {
+                let docs = h.method.attrs.iter().filter(|a| a.path.is_ident("doc"));
+                let vis = &h.method.vis;
+                // Prefixed, so that traits implemented by the address keep their method names
+                let method = format_ident!("call_{}", h.method.sig.ident);
+                let variant = &h.variant;
+                let output = &h.output;
+                let args: Vec<_> = h.args.iter().map(|(arg, _)| arg).collect();
+                let tys = h.args.iter().map(|(_, ty)| ty);
+                let message = if args.is_empty() {
+                    quote!(#message_name::#variant)
+                } else {
+                    quote!(#message_name::#variant(#(#args),*))
+                };
+                // Requests that borrow from the caller must be awaited until they complete
+                let request = if h.args.iter().any(|(_, ty)| borrows(ty)) {
+                    quote!(request_async)
+                } else {
+                    quote!(request_cancellable)
+                };
+                quote! {
+                    #(#docs)*
+                    #vis async fn #method<'m>(
+                        &self,
+                        #(#args: #tys),*
+                    ) -> ::core::result::Result<#output, ::drogue_device::kernel::actor::ActorError>
+                    where
+                        #bound
+                    {
+                        #[allow(unreachable_patterns)]
+                        match self.#request(#message).await? {
+                            #response_name::#variant(value) => Ok(value),
+                            _ => unreachable!(),
+                        }
+                    }
+                }
+            })
+            .collect::<Vec<_>>()
+    };
+    let address_methods = typed_methods(quote!('__a: 'm,));
+    let address_impl = quote! {
+        impl<'__a, #(#params),*> ::drogue_device::Address<'__a, #self_ty>
+        where
+            #self_ty: 'static,
+            #where_predicates
+        {
+            #(#address_methods)*
+        }
+    };
+    let pool_methods = typed_methods(quote!());
+    let pool_impl = quote! {
+        impl<#(#params,)* const __N: usize> ::drogue_device::PoolAddress<#self_ty, __N>
+        where
+            #self_ty: 'static,
+            #where_predicates
+        {
+            #(#pool_methods)*
+        }
+    };
+
+    let handler_methods = handlers.into_iter().map(|h| h.method);
+    let attrs = &item.attrs;
+    quote! {
+        #(#attrs)*
+        impl #impl_generics #self_ty #where_clause {
+            #(#inherent)*
+            #(#handler_methods)*
+        }
+
+        #message_enum
+
+        #response_enum
+
+        #actor_impl
+
+        #address_impl
+
+        #pool_impl
+    }
+}
+
+/// Validate a handler method and add the `'m` message lifetime to its generics.
+fn parse_handler(mut method: syn::ImplItemMethod) -> Result<Handler, ()> {
+    let name = method.sig.ident.to_string();
+
+    if method.sig.generics.type_params().next().is_some()
+        || method.sig.generics.const_params().next().is_some()
+    {
+        method
+            .sig
+            .generics
+            .span()
+            .unwrap()
+            .error("handler methods must not be generic")
+            .emit();
+        return Err(());
+    }
+
+    let mut inputs = method.sig.inputs.iter();
+    match inputs.next() {
+        Some(syn::FnArg::Receiver(r)) if r.reference.is_some() && r.mutability.is_some() => {}
+        _ => {
+            method
+                .sig
+                .span()
+                .unwrap()
+                .error("handler methods must take `&mut self`")
+                .emit();
+            return Err(());
+        }
+    }
+
+    let mut args = Vec::new();
+    for (i, input) in inputs.enumerate() {
+        if let syn::FnArg::Typed(t) = input {
+            let arg = match &*t.pat {
+                syn::Pat::Ident(p) => p.ident.clone(),
+                _ => format_ident!("arg{}", i),
+            };
+            args.push((arg, (*t.ty).clone()));
+        }
+    }
+
+    // Rebind non-identifier patterns so that arguments can be passed by name
+    for (input, (arg, _)) in method.sig.inputs.iter_mut().skip(1).zip(args.iter()) {
+        if let syn::FnArg::Typed(t) = input {
+            if !matches!(&*t.pat, syn::Pat::Ident(_)) {
+                let pat = &t.pat;
+                let ty = &t.ty;
+                let block = &method.block;
+                method.block = syn::parse_quote!({
+                    let #pat: #ty = #arg;
+                    #block
+                });
+                t.pat = Box::new(syn::parse_quote!(#arg));
+            }
+        }
+    }
+
+    let output = match &method.sig.output {
+        syn::ReturnType::Default => syn::parse_quote!(()),
+        syn::ReturnType::Type(_, ty) => (**ty).clone(),
+    };
+
+    let lifetime = syn::Lifetime::new("'m", Span::call_site());
+    if !method
+        .sig
+        .generics
+        .lifetimes()
+        .any(|l| l.lifetime == lifetime)
+    {
+        method.sig.generics.params.insert(
+            0,
+            syn::GenericParam::Lifetime(syn::LifetimeDef::new(lifetime)),
+        );
+    }
+
+    Ok(Handler {
+        variant: format_ident!("{}", camel_case(&name)),
+        method,
+        args,
+        output,
+    })
+}
+
+/// Returns true if the type contains a reference or a lifetime.
+fn borrows(ty: &syn::Type) -> bool {
+    fn any_borrow(tokens: TokenStream) -> bool {
+        tokens.into_iter().any(|token| match token {
+            proc_macro2::TokenTree::Punct(p) => p.as_char() == '&' || p.as_char() == '\'',
+            proc_macro2::TokenTree::Group(g) => any_borrow(g.stream()),
+            _ => false,
+        })
+    }
+    any_borrow(quote!(#ty))
+}
+
+fn camel_case(name: &str) -> String {
+    name.split('_')
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            let mut chars = s.chars();
+            match chars.next() {
+                Some(c) => c.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
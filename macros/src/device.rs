@@ -0,0 +1,426 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+
+mod kw {
+    syn::custom_keyword!(package);
+    syn::custom_keyword!(state);
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Actor,
+    Package,
+    State,
+}
+
+struct Component {
+    name: syn::Ident,
+    kind: Kind,
+    ty: syn::Type,
+}
+
+impl Parse for Component {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name = input.parse()?;
+        input.parse::<syn::Token![:]>()?;
+        let kind = if input.peek(kw::package) {
+            input.parse::<kw::package>()?;
+            Kind::Package
+        } else if input.peek(kw::state) {
+            input.parse::<kw::state>()?;
+            Kind::State
+        } else {
+            Kind::Actor
+        };
+        let ty = input.parse()?;
+        Ok(Self { name, kind, ty })
+    }
+}
+
+/// A connection of the form `source -> target, ... => configuration;`
+struct Wire {
+    source: syn::Ident,
+    targets: Vec<syn::Ident>,
+    config: Option<syn::Expr>,
+}
+
+impl Parse for Wire {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let source = input.parse()?;
+        let mut targets = Vec::new();
+        if input.peek(syn::Token![->]) {
+            input.parse::<syn::Token![->]>()?;
+            targets.push(input.parse()?);
+            while input.peek(syn::Token![,]) {
+                input.parse::<syn::Token![,]>()?;
+                targets.push(input.parse()?);
+            }
+        }
+        let config = if input.peek(syn::Token![=>]) {
+            input.parse::<syn::Token![=>]>()?;
+            Some(input.parse()?)
+        } else {
+            None
+        };
+        if targets.is_empty() && config.is_none() {
+            return Err(input.error("expected `->` or `=>`"));
+        }
+        input.parse::<syn::Token![;]>()?;
+        Ok(Self {
+            source,
+            targets,
+            config,
+        })
+    }
+}
+
+pub struct Device {
+    vis: syn::Visibility,
+    name: syn::Ident,
+    context: syn::Ident,
+    components: Vec<Component>,
+    wires: Vec<Wire>,
+}
+
+impl Parse for Device {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let vis = input.parse()?;
+        input.parse::<syn::Token![struct]>()?;
+        let name = input.parse()?;
+        input.parse::<syn::Token![:]>()?;
+        let context = input.parse()?;
+
+        let content;
+        syn::braced!(content in input);
+        let components: Punctuated<Component, syn::Token![,]> =
+            content.parse_terminated(Component::parse)?;
+
+        let mut wires = Vec::new();
+        while !input.is_empty() {
+            wires.push(input.parse()?);
+        }
+
+        Ok(Self {
+            vis,
+            name,
+            context,
+            components: components.into_iter().collect(),
+            wires,
+        })
+    }
+}
+
+pub fn expand(device: Device) -> TokenStream {
+    let mut fail = false;
+    let find = |name: &syn::Ident| device.components.iter().position(|c| c.name == *name);
+
+    for (i, component) in device.components.iter().enumerate() {
+        if device.components[..i]
+            .iter()
+            .any(|c| c.name == component.name)
+        {
+            component
+                .name
+                .span()
+                .unwrap()
+                .error(format!("`{}` is declared more than once", component.name))
+                .emit();
+            fail = true;
+        }
+    }
+
+    // Outgoing connections of each component, by index
+    let mut wiring: Vec<Option<&Wire>> = device.components.iter().map(|_| None).collect();
+    for wire in device.wires.iter() {
+        let source = match find(&wire.source) {
+            Some(source) => source,
+            None => {
+                wire.source
+                    .span()
+                    .unwrap()
+                    .error(format!("no actor or package named `{}`", wire.source))
+                    .emit();
+                fail = true;
+                continue;
+            }
+        };
+        if device.components[source].kind == Kind::State {
+            wire.source
+                .span()
+                .unwrap()
+                .error(format!("`{}` is state and can not be mounted", wire.source))
+                .emit();
+            fail = true;
+        }
+        if wiring[source].is_some() {
+            wire.source
+                .span()
+                .unwrap()
+                .error(format!("`{}` is wired more than once", wire.source))
+                .emit();
+            fail = true;
+        }
+        wiring[source] = Some(wire);
+
+        for target in wire.targets.iter() {
+            match find(target).map(|t| device.components[t].kind) {
+                None => {
+                    target
+                        .span()
+                        .unwrap()
+                        .error(format!("no actor or package named `{}`", target))
+                        .emit();
+                    fail = true;
+                }
+                Some(Kind::State) => {
+                    target
+                        .span()
+                        .unwrap()
+                        .error(format!("`{}` is state and has no address", target))
+                        .emit();
+                    fail = true;
+                }
+                Some(_) => {}
+            }
+        }
+        if wire.targets.len() > 1 && wire.config.is_none() {
+            wire.source
+                .span()
+                .unwrap()
+                .error(format!(
+                    "`{}` is connected to more than one actor, its configuration must be provided with `=>`",
+                    wire.source
+                ))
+                .emit();
+            fail = true;
+        }
+    }
+
+    if fail {
+        return TokenStream::new();
+    }
+
+    let order = match mount_order(&device, &wiring) {
+        Ok(order) => order,
+        Err(cycle) => {
+            let names: Vec<_> = cycle
+                .iter()
+                .map(|i| device.components[*i].name.to_string())
+                .collect();
+            device.components[cycle[0]]
+                .name
+                .span()
+                .unwrap()
+                .error(format!("cyclic wiring: {}", names.join(" -> ")))
+                .emit();
+            return TokenStream::new();
+        }
+    };
+
+    let vis = &device.vis;
+    let name = &device.name;
+    let context = &device.context;
+    let config_name = format_ident!("{}Config", name);
+    let addresses_name = format_ident!("{}Addresses", name);
+
+    let fields = device.components.iter().map(|c| {
+        let field = &c.name;
+        let ty = &c.ty;
+        match c.kind {
+            Kind::Actor => quote!(#vis #field: ::drogue_device::ActorContext<'static, #ty>),
+            _ => quote!(#vis #field: #ty),
+        }
+    });
+
+    let config_fields = device.components.iter().map(|c| {
+        let field = &c.name;
+        let ty = &c.ty;
+        quote!(#vis #field: #ty)
+    });
+
+    let configure_fields = device.components.iter().map(|c| {
+        let field = &c.name;
+        match c.kind {
+            Kind::Actor => quote!(#field: ::drogue_device::ActorContext::new(config.#field)),
+            _ => quote!(#field: config.#field),
+        }
+    });
+
+    let mounted: Vec<_> = device
+        .components
+        .iter()
+        .filter(|c| c.kind != Kind::State)
+        .collect();
+
+    let address_fields = mounted.iter().map(|c| {
+        let field = &c.name;
+        let ty = &c.ty;
+        match c.kind {
+            Kind::Package => quote! {
+                #vis #field: ::drogue_device::Address<'static, <#ty as ::drogue_device::Package>::Primary>
+            },
+            _ => quote!(#vis #field: ::drogue_device::Address<'static, #ty>),
+        }
+    });
+
+    let mounts = order.iter().map(|i| {
+        let c = &device.components[*i];
+        let field = &c.name;
+        let config = match wiring[*i] {
+            Some(Wire {
+                config: Some(config),
+                ..
+            }) => quote!(#config),
+            Some(Wire { targets, .. }) => {
+                let target = &targets[0];
+                quote!(#target)
+            }
+            None => quote!(()),
+        };
+        match c.kind {
+            Kind::Package => quote! {
+                let #field = ::drogue_device::Package::mount(&device.#field, #config, spawner);
+            },
+            _ => quote! {
+                let #field = device.#field.mount(#config, spawner);
+            },
+        }
+    });
+    let mounted_names = mounted.iter().map(|c| &c.name);
+
+    let shutdowns = order.iter().rev().map(|i| {
+        let c = &device.components[*i];
+        let field = &c.name;
+        match c.kind {
+            Kind::Package => quote! {
+                ::drogue_device::Package::shutdown(&self.#field).await;
+            },
+            _ => quote! {
+                self.#field.shutdown().await;
+            },
+        }
+    });
+
+    quote! {
+        #vis struct #name {
+            #(#fields,)*
+        }
+
+        /// The values each actor, package and state of the device is created from
+        #vis struct #config_name {
+            #(#config_fields,)*
+        }
+
+        /// The addresses of the actors and packages of the device
+        #[allow(dead_code)]
+        #[derive(Clone, Copy)]
+        #vis struct #addresses_name {
+            #(#address_fields,)*
+        }
+
+        static #context: ::drogue_device::DeviceContext<#name> = ::drogue_device::DeviceContext::new();
+
+        impl #name {
+            /// Configure the device
+            #vis fn configure(config: #config_name) {
+                #context.configure(#name {
+                    #(#configure_fields,)*
+                });
+            }
+
+            /// Mount the device, mounting every actor after the actors it is wired to
+            #vis async fn mount<S: ::drogue_device::ActorSpawner>(spawner: S) -> #addresses_name {
+                #context
+                    .mount(|device| async move {
+                        #(#mounts)*
+                        #addresses_name {
+                            #(#mounted_names,)*
+                        }
+                    })
+                    .await
+            }
+
+            /// Unmount the device, shutting down every actor before the actors it is wired to
+            #vis async fn unmount() {
+                #context.unmount().await
+            }
+
+            /// Shut down the device, allowing it to be configured again
+            #vis async fn shutdown() {
+                #context.shutdown().await
+            }
+        }
+
+        impl ::drogue_device::Device for #name {
+            type ShutdownFuture = impl ::core::future::Future<Output = ()>;
+
+            fn shutdown(&'static self) -> Self::ShutdownFuture {
+                async move {
+                    #(#shutdowns)*
+                }
+            }
+        }
+    }
+}
+
+/// Order the mounted components so that each is mounted after the components it is wired to,
+/// or return the components of a cycle.
+fn mount_order(device: &Device, wiring: &[Option<&Wire>]) -> Result<Vec<usize>, Vec<usize>> {
+    const UNVISITED: u8 = 0;
+    const VISITING: u8 = 1;
+    const VISITED: u8 = 2;
+
+    fn visit(
+        device: &Device,
+        wiring: &[Option<&Wire>],
+        index: usize,
+        marks: &mut Vec<u8>,
+        path: &mut Vec<usize>,
+        order: &mut Vec<usize>,
+    ) -> Result<(), Vec<usize>> {
+        match marks[index] {
+            VISITED => return Ok(()),
+            VISITING => {
+                let start = path.iter().position(|i| *i == index).unwrap();
+                let mut cycle = path[start..].to_vec();
+                cycle.push(index);
+                return Err(cycle);
+            }
+            _ => {}
+        }
+        marks[index] = VISITING;
+        path.push(index);
+        if let Some(wire) = wiring[index] {
+            for target in wire.targets.iter() {
+                let target = device
+                    .components
+                    .iter()
+                    .position(|c| c.name == *target)
+                    .unwrap();
+                visit(device, wiring, target, marks, path, order)?;
+            }
+        }
+        path.pop();
+        marks[index] = VISITED;
+        order.push(index);
+        Ok(())
+    }
+
+    let mut marks = vec![UNVISITED; device.components.len()];
+    let mut order = Vec::new();
+    for (index, component) in device.components.iter().enumerate() {
+        if component.kind != Kind::State {
+            visit(
+                device,
+                wiring,
+                index,
+                &mut marks,
+                &mut Vec::new(),
+                &mut order,
+            )?;
+        }
+    }
+    Ok(order)
+}
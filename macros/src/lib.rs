@@ -3,16 +3,40 @@
 
 extern crate proc_macro;
 
+mod actor;
+mod device;
+
 use proc_macro::TokenStream;
 use quote::{format_ident, quote};
 use syn::spanned::Spanned;
 use syn::{self};
 
+/// Run an async test function on a `TestRunner`, passing it a spawner and a `TestContext`
+/// for the device type under test.
+///
+/// With `#[test(virtual_time)]`, the test runs on a virtual clock that only advances when
+/// the test calls `TestContext::advance`, or when the executor is idle waiting for a timer.
 #[proc_macro_attribute]
-pub fn test(_: TokenStream, item: TokenStream) -> TokenStream {
+pub fn test(args: TokenStream, item: TokenStream) -> TokenStream {
+    let args = syn::parse_macro_input!(args as syn::AttributeArgs);
     let task_fn = syn::parse_macro_input!(item as syn::ItemFn);
 
     let mut fail = false;
+    let mut virtual_time = false;
+    for arg in args.iter() {
+        match arg {
+            syn::NestedMeta::Meta(syn::Meta::Path(path)) if path.is_ident("virtual_time") => {
+                virtual_time = true;
+            }
+            _ => {
+                arg.span()
+                    .unwrap()
+                    .error("unknown test argument, expected `virtual_time`")
+                    .emit();
+                fail = true;
+            }
+        }
+    }
     if task_fn.sig.asyncness.is_none() {
         task_fn
             .sig
@@ -78,6 +102,11 @@ pub fn test(_: TokenStream, item: TokenStream) -> TokenStream {
     let device_type = device_type.take().unwrap();
     let task_fn_body = task_fn.block;
     let drogue_test_name = format_ident!("__drogue_test_{}", test_name);
+    let new_runner = if virtual_time {
+        quote!(TestRunner::new_virtual())
+    } else {
+        quote!(TestRunner::new())
+    };
 
     let result = quote! {
 
@@ -91,7 +120,7 @@ pub fn test(_: TokenStream, item: TokenStream) -> TokenStream {
             static DEVICE: ::drogue_device::DeviceContext<#device_type> = ::drogue_device::DeviceContext::new();
             static RUNNER: ::embassy::util::Forever<TestRunner> = ::embassy::util::Forever::new();
 
-            let runner = RUNNER.put(TestRunner::new());
+            let runner = RUNNER.put(#new_runner);
 
             runner.initialize(|spawner| {
                 let runner = unsafe { RUNNER.steal() };
@@ -106,6 +135,60 @@ pub fn test(_: TokenStream, item: TokenStream) -> TokenStream {
     result.into()
 }
 
+/// Generate the `Actor` implementation of a type from an impl block of async handler methods.
+///
+/// Each `async fn` taking `&mut self` becomes a variant of a generated `{Type}Message` enum,
+/// with its return value carried by a `{Type}Response` enum, and a method prefixed with `call_`
+/// is added to `Address<'_, Type>` that performs the request and returns the typed response,
+/// or `ActorError::Abandoned` if the actor was restarted or stopped before responding.
+/// The same methods are added to `PoolAddress<Type, N>`, for pools of the actor.
+///
+/// Methods of handlers taking only owned arguments may be dropped before they complete, which
+/// cancels the request. Those of handlers borrowing any argument must be awaited until they
+/// complete once the request has been enqueued, see `Address::request_async`.
+///
+/// Associated types and constants, and the `on_mount`, `on_stop`, `on_restart` and `priority`
+/// methods are moved into the `Actor` implementation, and an `async fn on_start(&mut self)` is called
+/// when the actor is started.
+#[proc_macro_attribute]
+pub fn actor(_: TokenStream, item: TokenStream) -> TokenStream {
+    let item = syn::parse_macro_input!(item as syn::ItemImpl);
+    actor::expand(item).into()
+}
+
+/// Declare a device of actors, packages and state, and how the actors are wired together.
+///
+/// ```ignore
+/// device! {
+///     pub struct MyDevice: DEVICE {
+///         counter: state AtomicU32,
+///         lora: LoraActor<Sx127x<'static>>,
+///         app: MyApp,
+///         button: Button<'static, ExtiInput<'static, PB2>, MyApp>,
+///     }
+///
+///     button -> app;
+///     app -> lora => AppConfig { lora, counter: &device.counter };
+/// }
+/// ```
+///
+/// A wire `a -> b` passes the address of `b` as the configuration of `a`, unless a
+/// configuration expression is given after `=>`, in which the addresses of the targets
+/// and the mounted `device` are in scope. Actors and packages that are not wired are
+/// mounted with `()`.
+///
+/// This generates the device struct, the `DEVICE` static, a `MyDeviceConfig` struct holding
+/// the values to configure the device with, and `MyDevice::configure` and `MyDevice::mount`,
+/// the latter mounting every actor after the actors it is wired to and returning the
+/// addresses in a `MyDeviceAddresses` struct. The device implements `Device`, shutting
+/// down its actors and packages in the reverse of the order they were mounted in when
+/// calling `MyDevice::unmount` or `MyDevice::shutdown`.
+#[proc_macro]
+pub fn device(item: TokenStream) -> TokenStream {
+    let device = syn::parse_macro_input!(item as device::Device);
+    device::expand(device).into()
+}
+
 #[proc_macro]
 pub fn log_stack(_item: TokenStream) -> TokenStream {
     let result = quote! {